@@ -0,0 +1,25 @@
+mod mesh_to_sdf; pub use mesh_to_sdf::{MeshToSdf, SignMethod};
+mod point_cloud; pub use point_cloud::{PointCloud, PointRasterizer, SplatKernel};
+
+use thiserror::Error;
+
+/// Error types for conversions between meshes, point clouds and volumes
+#[derive(Debug, Error)]
+pub enum ConversionError {
+    #[error("The input has no geometry")]
+    EmptyInput,
+    #[error("The points have no normals")]
+    MissingNormals,
+    #[error("Expected one normal per point, got {normals} normals for {points} points")]
+    NormalCountMismatch { points: usize, normals: usize },
+    #[error("Invalid band width: {0}")]
+    InvalidBandWidth(f32),
+    #[error("Invalid Lipschitz constant: {0}")]
+    InvalidLipschitz(f32),
+    #[error("Invalid kernel width: {0}")]
+    InvalidKernelWidth(f32),
+    #[error("The shape is unbounded and needs explicit bounds")]
+    UnboundedShape,
+    #[error("Triangle {triangle} references missing vertex {vertex}")]
+    InvalidTriangle { triangle: usize, vertex: usize },
+}
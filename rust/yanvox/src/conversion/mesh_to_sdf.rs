@@ -0,0 +1,411 @@
+use crate::voxel::{VoxelVolume, VolumeConfig, FromSignedDistance};
+use crate::math::{Vec3i, Vec3f, Bounds3f};
+use crate::mesh_generation::Mesh;
+use super::ConversionError;
+use std::collections::HashMap;
+
+/// Method deciding whether a voxel lies inside the mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignMethod {
+    /// Generalized winding number, robust to small holes and self-intersections
+    ///
+    /// Evaluated with a bounding volume hierarchy whose distant nodes are
+    /// approximated by their dipole moment, costing a logarithmic number of
+    /// solid angles per band voxel.
+    #[default]
+    WindingNumber,
+    /// Parity of the crossings of a ray cast along +x, requires a watertight mesh
+    RayParity,
+}
+
+/// Converter from a triangle mesh to a narrow-band signed distance volume
+///
+/// Every voxel closer to the mesh than the band width gets the distance to
+/// the closest triangle, negative inside the mesh. Voxels outside the band
+/// are not written and read as the background value.
+pub struct MeshToSdf<'a> {
+    mesh: &'a Mesh,
+    band_width: f32,
+    sign_method: SignMethod,
+}
+
+impl<'a> MeshToSdf<'a> {
+    /// Create a converter with a band of 3 voxels and winding number signs
+    pub fn new(mesh: &'a Mesh) -> Self {
+        Self {
+            mesh,
+            band_width: 3.0,
+            sign_method: SignMethod::default(),
+        }
+    }
+
+    /// Set the half-width of the narrow band, in voxels
+    pub fn with_band_width(mut self, band_width: f32) -> Self {
+        self.band_width = band_width;
+        self
+    }
+
+    /// Set the method deciding the sign of distances
+    pub fn with_sign_method(mut self, sign_method: SignMethod) -> Self {
+        self.sign_method = sign_method;
+        self
+    }
+
+    /// Convert the mesh into a new volume
+    pub fn build<T: FromSignedDistance + Clone + 'static>(&self, config: VolumeConfig) -> Result<VoxelVolume<T>, ConversionError> {
+        let mut volume = VoxelVolume::with_config(config);
+        self.write_into(&mut volume)?;
+        Ok(volume)
+    }
+
+    /// Write the narrow band of the mesh into an existing volume
+    ///
+    /// # Returns
+    /// Number of voxels written
+    pub fn write_into<T: FromSignedDistance + Clone + 'static>(&self, volume: &mut VoxelVolume<T>) -> Result<usize, ConversionError> {
+        if !(self.band_width > 0.0 && self.band_width.is_finite()) {
+            return Err(ConversionError::InvalidBandWidth(self.band_width));
+        }
+        if self.mesh.triangles.is_empty() {
+            return Err(ConversionError::EmptyInput);
+        }
+        let triangles = self.triangle_positions()?;
+        let voxel_size = volume.get_leaf_voxel_size();
+        let band = self.band_width * voxel_size;
+
+        // Unsigned distance to the closest triangle within the band
+        let mut distances: HashMap<Vec3i, f32> = HashMap::new();
+        for triangle in &triangles {
+            let bounds = triangle.iter().fold(Bounds3f::empty(), |bounds, &p| bounds.expand(p));
+            let margin = Vec3f::new(band, band, band);
+            let region = volume.world_to_voxel_bounds(Bounds3f::new(bounds.min - margin, bounds.max + margin));
+            for coord in region.coords() {
+                let position = coord.as_vec3f().scale(voxel_size);
+                let distance = (closest_point_on_triangle(position, triangle) - position).length();
+                if distance < band {
+                    distances.entry(coord)
+                        .and_modify(|d| *d = d.min(distance))
+                        .or_insert(distance);
+                }
+            }
+        }
+
+        let mut coords: Vec<Vec3i> = distances.keys().copied().collect();
+        coords.sort_by_key(|c| (c.z, c.y, c.x));
+        let inside: Vec<bool> = match self.sign_method {
+            SignMethod::WindingNumber => {
+                let tree = WindingTree::new(&triangles);
+                coords.iter().map(|c| tree.winding_number(c.as_vec3f().scale(voxel_size)).abs() > 0.5).collect()
+            }
+            SignMethod::RayParity => RayCaster::new(&triangles, voxel_size).inside(&coords),
+        };
+        for (coord, inside) in coords.iter().zip(inside) {
+            let distance = distances[coord];
+            volume.set_voxel(*coord, T::from_signed_distance(if inside { -distance } else { distance }));
+        }
+        Ok(coords.len())
+    }
+
+    /// Get the corner positions of every triangle
+    fn triangle_positions(&self) -> Result<Vec<[Vec3f; 3]>, ConversionError> {
+        self.mesh.triangles.iter()
+            .enumerate()
+            .map(|(index, triangle)| {
+                let mut corners = [Vec3f::zero(); 3];
+                for (corner, &vertex) in corners.iter_mut().zip(&triangle.indices) {
+                    *corner = self.mesh.vertices.get(vertex)
+                        .ok_or(ConversionError::InvalidTriangle { triangle: index, vertex })?
+                        .position;
+                }
+                Ok(corners)
+            })
+            .collect()
+    }
+}
+
+/// Closest point to `p` on a triangle
+///
+/// From Ericson, Real-Time Collision Detection, section 5.1.5
+fn closest_point_on_triangle(p: Vec3f, [a, b, c]: &[Vec3f; 3]) -> Vec3f {
+    let (a, b, c) = (*a, *b, *c);
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = p - b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab.scale(d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac.scale(d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b).scale((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denominator = 1.0 / (va + vb + vc);
+    a + ab.scale(vb * denominator) + ac.scale(vc * denominator)
+}
+
+/// Signed solid angle of a triangle seen from a point (Van Oosterom and Strackee)
+fn solid_angle(p: Vec3f, [a, b, c]: &[Vec3f; 3]) -> f64 {
+    let (a, b, c) = (*a - p, *b - p, *c - p);
+    let (la, lb, lc) = (a.length(), b.length(), c.length());
+    let numerator = a.dot(&b.cross(&c));
+    let denominator = la * lb * lc + a.dot(&b) * lc + b.dot(&c) * la + c.dot(&a) * lb;
+    2.0 * (numerator as f64).atan2(denominator as f64)
+}
+
+/// Node of a [`WindingTree`]
+struct WindingNode {
+    /// Area-weighted centroid of the triangles
+    center: Vec3f,
+    /// Distance from the center to the farthest triangle corner
+    radius: f32,
+    /// Sum of the area vectors of the triangles
+    dipole: Vec3f,
+    /// Range of `WindingTree::order` holding the triangles
+    start: usize,
+    end: usize,
+    /// Indices of the two children, none for leaves
+    children: Option<[usize; 2]>,
+}
+
+/// Bounding volume hierarchy for the fast winding number (Barill et al. 2018)
+///
+/// The winding number of a closed, outward-oriented mesh is close to 1
+/// inside and 0 outside. Nodes far enough from the query point contribute
+/// the field of their dipole, nearer leaves their exact solid angles.
+struct WindingTree<'a> {
+    triangles: &'a [[Vec3f; 3]],
+    order: Vec<usize>,
+    nodes: Vec<WindingNode>,
+}
+
+impl<'a> WindingTree<'a> {
+    /// Maximum number of triangles in a leaf
+    const LEAF_SIZE: usize = 8;
+    /// Nodes are approximated beyond this multiple of their radius
+    const ACCURACY: f32 = 2.0;
+
+    fn new(triangles: &'a [[Vec3f; 3]]) -> Self {
+        let mut tree = Self { triangles, order: (0..triangles.len()).collect(), nodes: Vec::new() };
+        if !triangles.is_empty() {
+            tree.build(0, triangles.len());
+        }
+        tree
+    }
+
+    /// Add the node over `order[start..end]` and its descendants, returning its index
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let centroid = |t: usize| {
+            let [a, b, c] = self.triangles[t];
+            (a + b + c).scale(1.0 / 3.0)
+        };
+        let mut dipole = Vec3f::zero();
+        let mut weighted = Vec3f::zero();
+        let mut area = 0.0;
+        let mut bounds = Bounds3f::empty();
+        for &t in &self.order[start..end] {
+            let [a, b, c] = self.triangles[t];
+            let vector = (b - a).cross(&(c - a)).scale(0.5);
+            dipole = dipole + vector;
+            weighted = weighted + centroid(t).scale(vector.length());
+            area += vector.length();
+            bounds = bounds.expand(centroid(t));
+        }
+        let center = if area > 0.0 {
+            weighted.scale(1.0 / area)
+        } else {
+            (bounds.min + bounds.max).scale(0.5)
+        };
+        let radius = self.order[start..end].iter()
+            .flat_map(|&t| self.triangles[t])
+            .map(|p| (p - center).length())
+            .fold(0.0, f32::max);
+        let index = self.nodes.len();
+        self.nodes.push(WindingNode { center, radius, dipole, start, end, children: None });
+
+        if end - start > Self::LEAF_SIZE {
+            // Split at the median centroid along the longest axis
+            let size = bounds.size();
+            let axis = if size.x >= size.y && size.x >= size.z { 0 } else if size.y >= size.z { 1 } else { 2 };
+            let key = |t: usize| {
+                let c = centroid(t);
+                [c.x, c.y, c.z][axis]
+            };
+            let middle = (start + end) / 2;
+            self.order[start..end].select_nth_unstable_by(middle - start, |&a, &b| key(a).total_cmp(&key(b)));
+            let left = self.build(start, middle);
+            let right = self.build(middle, end);
+            self.nodes[index].children = Some([left, right]);
+        }
+        index
+    }
+
+    /// Generalized winding number of the mesh around a point
+    fn winding_number(&self, p: Vec3f) -> f32 {
+        let mut total = 0.0f64;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let offset = node.center - p;
+            let distance = offset.length();
+            if distance > Self::ACCURACY * node.radius {
+                total += (node.dipole.dot(&offset) / (distance * distance * distance)) as f64;
+            } else if let Some(children) = node.children {
+                stack.extend(children);
+            } else {
+                total += self.order[node.start..node.end].iter()
+                    .map(|&t| solid_angle(p, &self.triangles[t]))
+                    .sum::<f64>();
+            }
+        }
+        (total / (4.0 * std::f64::consts::PI)) as f32
+    }
+}
+
+/// Inside test by counting crossings of rays cast along +x
+struct RayCaster<'a> {
+    triangles: &'a [[Vec3f; 3]],
+    voxel_size: f32,
+    /// Triangles whose y-z projection may contain each ray, keyed by voxel (y, z)
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl<'a> RayCaster<'a> {
+    /// Rays are shifted off the voxel lattice so they never pass exactly
+    /// through the mesh vertices and edges that meshing places on it
+    const OFFSET: (f32, f32) = (0.001_234_5, 0.002_345_6);
+
+    fn new(triangles: &'a [[Vec3f; 3]], voxel_size: f32) -> Self {
+        let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (index, triangle) in triangles.iter().enumerate() {
+            let range = |values: [f32; 3], offset: f32| {
+                let min = values.iter().copied().fold(f32::MAX, f32::min) / voxel_size - offset;
+                let max = values.iter().copied().fold(f32::MIN, f32::max) / voxel_size - offset;
+                min.ceil() as i32..=max.floor() as i32
+            };
+            for y in range(triangle.map(|p| p.y), Self::OFFSET.0) {
+                for z in range(triangle.map(|p| p.z), Self::OFFSET.1) {
+                    cells.entry((y, z)).or_default().push(index);
+                }
+            }
+        }
+        Self { triangles, voxel_size, cells }
+    }
+
+    /// Positions along x where the ray through voxel row (y, z) crosses the mesh, sorted
+    fn crossings(&self, y: i32, z: i32) -> Vec<f32> {
+        let py = (y as f32 + Self::OFFSET.0) * self.voxel_size;
+        let pz = (z as f32 + Self::OFFSET.1) * self.voxel_size;
+        let mut crossings: Vec<f32> = self.cells.get(&(y, z)).into_iter().flatten()
+            .filter_map(|&index| {
+                let [a, b, c] = self.triangles[index];
+                let edge = |p: Vec3f, q: Vec3f| (q.y - p.y) * (pz - p.z) - (q.z - p.z) * (py - p.y);
+                let (wa, wb, wc) = (edge(b, c), edge(c, a), edge(a, b));
+                let inside = (wa > 0.0 && wb > 0.0 && wc > 0.0) || (wa < 0.0 && wb < 0.0 && wc < 0.0);
+                inside.then(|| (wa * a.x + wb * b.x + wc * c.x) / (wa + wb + wc))
+            })
+            .collect();
+        crossings.sort_by(f32::total_cmp);
+        crossings
+    }
+
+    /// Check which voxels are inside, given coordinates sorted by (z, y, x)
+    fn inside(&self, coords: &[Vec3i]) -> Vec<bool> {
+        let mut inside = Vec::with_capacity(coords.len());
+        let mut row = None;
+        let mut crossings = Vec::new();
+        for coord in coords {
+            if row != Some((coord.y, coord.z)) {
+                row = Some((coord.y, coord.z));
+                crossings = self.crossings(coord.y, coord.z);
+            }
+            let x = coord.x as f32 * self.voxel_size;
+            let after = crossings.len() - crossings.partition_point(|&c| c <= x);
+            inside.push(after % 2 == 1);
+        }
+        inside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::{SdfVoxel, config, sphere_volume};
+    use crate::mesh_generation::MeshBuilder;
+    use test_log::test;
+
+    fn sphere_mesh() -> Mesh {
+        MeshBuilder::new(&sphere_volume(Vec3f::zero(), 1.0)).with_iso_level(0.0).build().unwrap()
+    }
+
+    #[test]
+    fn test_sphere_round_trip() {
+        let mesh = sphere_mesh();
+        for sign_method in [SignMethod::WindingNumber, SignMethod::RayParity] {
+            let volume: VoxelVolume<SdfVoxel> = MeshToSdf::new(&mesh)
+                .with_band_width(2.0)
+                .with_sign_method(sign_method)
+                .build(config())
+                .unwrap();
+            assert!(volume.active_count() > 0);
+            for (coord, voxel) in volume.active_voxels() {
+                let exact = coord.as_vec3f().scale(0.1).length() - 1.0;
+                assert!((voxel.0 - exact).abs() < 0.02, "{:?}: {} vs {}", sign_method, voxel.0, exact);
+            }
+
+            let remeshed = MeshBuilder::new(&volume).with_iso_level(0.0).build().unwrap();
+            assert!(remeshed.triangle_count() > 0);
+            for vertex in &remeshed.vertices {
+                assert!((vertex.position.length() - 1.0).abs() < 0.02);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fast_winding_number() {
+        let mesh = sphere_mesh();
+        let triangles = MeshToSdf::new(&mesh).triangle_positions().unwrap();
+        let tree = WindingTree::new(&triangles);
+        for p in [(0.0, 0.0, 0.0), (0.5, -0.3, 0.2), (0.9, 0.0, 0.0), (1.1, 0.0, 0.0), (0.0, 2.0, 1.0), (5.0, 5.0, 5.0)] {
+            let p = Vec3f::new(p.0, p.1, p.2);
+            let exact = triangles.iter().map(|t| solid_angle(p, t)).sum::<f64>() / (4.0 * std::f64::consts::PI);
+            let fast = tree.winding_number(p);
+            // The first-order approximation is coarse, but only decides the sign
+            assert!((fast - exact as f32).abs() < 0.05, "{:?}: {} vs {}", p, fast, exact);
+            assert_eq!(fast > 0.5, p.length() < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let mesh = sphere_mesh();
+        assert!(matches!(
+            MeshToSdf::new(&mesh).with_band_width(0.0).build::<SdfVoxel>(config()),
+            Err(ConversionError::InvalidBandWidth(_))
+        ));
+        assert!(matches!(MeshToSdf::new(&Mesh::new()).build::<SdfVoxel>(config()), Err(ConversionError::EmptyInput)));
+    }
+}
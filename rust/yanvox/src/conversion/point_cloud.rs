@@ -0,0 +1,353 @@
+use crate::voxel::{VoxelVolume, VolumeConfig, FromSignedDistance};
+use crate::math::{Vec3i, Vec3f, Bounds3f};
+use crate::mesh_generation::Mesh;
+use super::ConversionError;
+use std::collections::HashMap;
+use std::io::{BufRead, Error, ErrorKind, Result as IoResult};
+use std::path::Path;
+
+/// A set of points with optional normals
+#[derive(Debug, Clone, Default)]
+pub struct PointCloud {
+    pub points: Vec<Vec3f>,
+    /// Unit normal per point, either empty or one entry per point
+    pub normals: Vec<Vec3f>,
+}
+
+impl PointCloud {
+    /// Create an empty point cloud
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a point cloud without normals
+    pub fn from_points(points: Vec<Vec3f>) -> Self {
+        Self { points, normals: Vec::new() }
+    }
+
+    /// Create a point cloud of oriented points, with one normal per point
+    pub fn from_oriented_points(points: Vec<Vec3f>, normals: Vec<Vec3f>) -> Result<Self, ConversionError> {
+        if points.len() != normals.len() {
+            return Err(ConversionError::NormalCountMismatch { points: points.len(), normals: normals.len() });
+        }
+        Ok(Self { points, normals })
+    }
+
+    /// Get the number of points
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Check if the point cloud is empty
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Check if the points carry normals
+    pub fn has_normals(&self) -> bool {
+        !self.normals.is_empty()
+    }
+
+    /// Get the bounds of the points
+    pub fn bounds(&self) -> Bounds3f {
+        self.points.iter().fold(Bounds3f::empty(), |bounds, &p| bounds.expand(p))
+    }
+
+    /// Read points from XYZ text data
+    ///
+    /// Each line holds `x y z`, optionally followed by `nx ny nz`; further
+    /// columns are ignored. Blank lines and lines starting with `#` are
+    /// skipped. Normals are kept only if every point has them.
+    pub fn read_xyz<R: BufRead>(reader: &mut R) -> IoResult<PointCloud> {
+        let mut cloud = PointCloud::new();
+        let mut normals = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let values = line.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|w| !w.is_empty())
+                .map(|w| w.parse::<f32>().map_err(|_| Error::new(ErrorKind::InvalidData, format!("invalid XYZ line `{}`", line))))
+                .collect::<IoResult<Vec<f32>>>()?;
+            if values.len() < 3 {
+                return Err(Error::new(ErrorKind::InvalidData, format!("invalid XYZ line `{}`", line)));
+            }
+            cloud.points.push(Vec3f::new(values[0], values[1], values[2]));
+            if values.len() >= 6 {
+                normals.push(Vec3f::new(values[3], values[4], values[5]).normalize());
+            }
+        }
+        if normals.len() == cloud.points.len() {
+            cloud.normals = normals;
+        }
+        Ok(cloud)
+    }
+
+    /// Read points and normals from PLY data, ignoring any faces
+    pub fn read_ply<R: BufRead>(reader: &mut R) -> IoResult<PointCloud> {
+        Ok(Self::from(Mesh::read_ply(reader)?))
+    }
+
+    /// Read a point cloud from a file, picking the format from the file extension
+    ///
+    /// Supported extensions are `xyz`, `txt` and `pts` (XYZ text) and `ply`.
+    pub fn read_file<P: AsRef<Path>>(path: P) -> IoResult<PointCloud> {
+        let path = path.as_ref();
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        match path.extension().and_then(|s| s.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("xyz") | Some("txt") | Some("pts") => Self::read_xyz(&mut reader),
+            Some("ply") => Self::read_ply(&mut reader),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unsupported point cloud file extension: {}", path.display()),
+            )),
+        }
+    }
+}
+
+impl From<Mesh> for PointCloud {
+    /// Use the mesh vertices and their normals as points
+    fn from(mesh: Mesh) -> Self {
+        Self {
+            points: mesh.vertices.iter().map(|v| v.position).collect(),
+            normals: mesh.normals.iter().map(|n| n.normalize()).collect(),
+        }
+    }
+}
+
+/// Kernel spreading each point over nearby voxels when rasterizing density
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplatKernel {
+    /// All weight on the nearest voxel
+    Nearest,
+    /// Trilinear weights on the 8 surrounding voxels
+    Trilinear,
+    /// Gaussian weights with standard deviation `sigma` voxels, cut off at 3 sigma
+    ///
+    /// `sigma` must be positive and finite.
+    Gaussian { sigma: f32 },
+}
+
+/// Rasterizes a point cloud into voxel volumes
+pub struct PointRasterizer<'a> {
+    cloud: &'a PointCloud,
+}
+
+impl<'a> PointRasterizer<'a> {
+    /// Create a rasterizer for a point cloud
+    pub fn new(cloud: &'a PointCloud) -> Self {
+        Self { cloud }
+    }
+
+    fn check_not_empty(&self) -> Result<(), ConversionError> {
+        if self.cloud.is_empty() {
+            return Err(ConversionError::EmptyInput);
+        }
+        Ok(())
+    }
+
+    /// Count the points falling nearest to each voxel
+    pub fn occupancy(&self, config: VolumeConfig) -> Result<VoxelVolume<u32>, ConversionError> {
+        self.check_not_empty()?;
+        let mut volume = VoxelVolume::with_config(config);
+        let voxel_size = volume.get_leaf_voxel_size();
+        let mut counts: HashMap<Vec3i, u32> = HashMap::new();
+        for &point in &self.cloud.points {
+            *counts.entry(nearest_voxel(point, voxel_size)).or_default() += 1;
+        }
+        for (coord, count) in counts {
+            volume.set_voxel(coord, count);
+        }
+        Ok(volume)
+    }
+
+    /// Rasterize the point density, in points per unit volume
+    ///
+    /// Each point spreads a unit mass over nearby voxels with the kernel, so
+    /// the density integrated over the volume equals the number of points.
+    pub fn density(&self, kernel: SplatKernel, config: VolumeConfig) -> Result<VoxelVolume<f32>, ConversionError> {
+        self.check_not_empty()?;
+        if let SplatKernel::Gaussian { sigma } = kernel && !(sigma > 0.0 && sigma.is_finite()) {
+            return Err(ConversionError::InvalidKernelWidth(sigma));
+        }
+        let mut volume = VoxelVolume::with_config(config);
+        let voxel_size = volume.get_leaf_voxel_size();
+        let mut mass: HashMap<Vec3i, f32> = HashMap::new();
+        let mut weights = Vec::new();
+        for &point in &self.cloud.points {
+            weights.clear();
+            match kernel {
+                SplatKernel::Nearest => weights.push((nearest_voxel(point, voxel_size), 1.0)),
+                SplatKernel::Trilinear => weights.extend(volume.trilinear_weights(point)),
+                SplatKernel::Gaussian { sigma } => {
+                    let grid = point.scale(1.0 / voxel_size);
+                    let reach = (3.0 * sigma).ceil().max(1.0) as i32;
+                    let centre = nearest_voxel(point, voxel_size);
+                    for dz in -reach..=reach {
+                        for dy in -reach..=reach {
+                            for dx in -reach..=reach {
+                                let coord = centre + Vec3i::new(dx, dy, dz);
+                                let r2 = (coord.as_vec3f() - grid).dot(&(coord.as_vec3f() - grid));
+                                let weight = (-0.5 * r2 / (sigma * sigma)).exp();
+                                if r2 <= 9.0 * sigma * sigma && weight > 0.0 {
+                                    weights.push((coord, weight));
+                                }
+                            }
+                        }
+                    }
+                    if weights.is_empty() {
+                        weights.push((centre, 1.0));
+                    }
+                }
+            }
+            let total: f32 = weights.iter().map(|(_, w)| w).sum();
+            for &(coord, weight) in &weights {
+                if weight > 0.0 {
+                    *mass.entry(coord).or_default() += weight / total;
+                }
+            }
+        }
+        let cell_volume = voxel_size * voxel_size * voxel_size;
+        for (coord, mass) in mass {
+            volume.set_voxel(coord, mass / cell_volume);
+        }
+        Ok(volume)
+    }
+
+    /// Approximate a narrow-band signed distance field from oriented points
+    ///
+    /// Each voxel within `band_width` voxels of a point gets its distance
+    /// to the tangent plane of the nearest point, negative behind the
+    /// normal. This is accurate for dense, evenly sampled surfaces.
+    pub fn signed_distance<T: FromSignedDistance + Clone + 'static>(&self, band_width: f32, config: VolumeConfig) -> Result<VoxelVolume<T>, ConversionError> {
+        self.check_not_empty()?;
+        if !self.cloud.has_normals() {
+            return Err(ConversionError::MissingNormals);
+        }
+        if !(band_width > 0.0 && band_width.is_finite()) {
+            return Err(ConversionError::InvalidBandWidth(band_width));
+        }
+        let mut volume = VoxelVolume::with_config(config);
+        let voxel_size = volume.get_leaf_voxel_size();
+        let band = band_width * voxel_size;
+
+        // Nearest point for each voxel in the band
+        let reach = band_width.ceil() as i32;
+        let mut nearest: HashMap<Vec3i, (f32, usize)> = HashMap::new();
+        for (index, &point) in self.cloud.points.iter().enumerate() {
+            let centre = nearest_voxel(point, voxel_size);
+            for dz in -reach..=reach {
+                for dy in -reach..=reach {
+                    for dx in -reach..=reach {
+                        let coord = centre + Vec3i::new(dx, dy, dz);
+                        let distance = (coord.as_vec3f().scale(voxel_size) - point).length();
+                        if distance < band {
+                            let entry = nearest.entry(coord).or_insert((f32::MAX, index));
+                            if distance < entry.0 {
+                                *entry = (distance, index);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for (coord, (_, index)) in nearest {
+            let offset = coord.as_vec3f().scale(voxel_size) - self.cloud.points[index];
+            volume.set_voxel(coord, T::from_signed_distance(offset.dot(&self.cloud.normals[index])));
+        }
+        Ok(volume)
+    }
+}
+
+/// Get the voxel nearest to a world-space position
+fn nearest_voxel(point: Vec3f, voxel_size: f32) -> Vec3i {
+    let grid = point.scale(1.0 / voxel_size);
+    Vec3i::new(grid.x.round() as i32, grid.y.round() as i32, grid.z.round() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::{SdfVoxel, config};
+    use crate::mesh_generation::MeshBuilder;
+    use test_log::test;
+
+    /// Points on the unit sphere with outward normals
+    fn sphere_cloud() -> PointCloud {
+        let mut points = Vec::new();
+        let n = 200;
+        for i in 0..n {
+            for j in 0..2 * n {
+                let theta = std::f32::consts::PI * (i as f32 + 0.5) / n as f32;
+                let phi = std::f32::consts::PI * j as f32 / n as f32;
+                points.push(Vec3f::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()));
+            }
+        }
+        PointCloud::from_oriented_points(points.clone(), points).unwrap()
+    }
+
+    #[test]
+    fn test_occupancy_and_density() {
+        let cloud = PointCloud::from_points(vec![
+            Vec3f::new(0.0, 0.0, 0.0),
+            Vec3f::new(0.01, 0.0, 0.0),
+            Vec3f::new(0.52, 0.31, -0.2),
+        ]);
+        let rasterizer = PointRasterizer::new(&cloud);
+        let occupancy = rasterizer.occupancy(config()).unwrap();
+        assert_eq!(*occupancy.get_voxel(Vec3i::zero()), 2);
+        assert_eq!(occupancy.active_voxels().map(|(_, c)| *c).sum::<u32>(), 3);
+
+        for kernel in [SplatKernel::Nearest, SplatKernel::Trilinear, SplatKernel::Gaussian { sigma: 1.0 }] {
+            let density = rasterizer.density(kernel, config()).unwrap();
+            let mass: f32 = density.active_voxels().map(|(_, d)| d * 0.001).sum();
+            assert!((mass - 3.0).abs() < 1e-3, "{:?}: {}", kernel, mass);
+        }
+        assert!(matches!(PointRasterizer::new(&PointCloud::new()).occupancy(config()), Err(ConversionError::EmptyInput)));
+    }
+
+    #[test]
+    fn test_invalid_gaussian_sigma() {
+        let cloud = PointCloud::from_points(vec![Vec3f::new(0.0, 0.0, 0.0)]);
+        let rasterizer = PointRasterizer::new(&cloud);
+        for sigma in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                rasterizer.density(SplatKernel::Gaussian { sigma }, config()),
+                Err(ConversionError::InvalidKernelWidth(_))
+            ), "{}", sigma);
+        }
+    }
+
+    #[test]
+    fn test_oriented_points_to_sdf() {
+        let cloud = sphere_cloud();
+        let volume: VoxelVolume<SdfVoxel> = PointRasterizer::new(&cloud).signed_distance(2.0, config()).unwrap();
+        for (coord, voxel) in volume.active_voxels() {
+            let exact = coord.as_vec3f().scale(0.1).length() - 1.0;
+            assert!((voxel.0 - exact).abs() < 0.02);
+        }
+        let mesh = MeshBuilder::new(&volume).with_iso_level(0.0).build().unwrap();
+        assert!(mesh.vertices.iter().all(|v| (v.position.length() - 1.0).abs() < 0.02));
+
+        assert!(matches!(
+            PointCloud::from_oriented_points(cloud.points.clone(), Vec::new()),
+            Err(ConversionError::NormalCountMismatch { normals: 0, .. })
+        ));
+        let unoriented = PointCloud::from_points(cloud.points.clone());
+        assert!(matches!(
+            PointRasterizer::new(&unoriented).signed_distance::<SdfVoxel>(2.0, config()),
+            Err(ConversionError::MissingNormals)
+        ));
+    }
+
+    #[test]
+    fn test_read_xyz() {
+        let xyz = "# scan\n0 0 0 0 0 2\n1.5,2,3,0,1,0\n\n";
+        let cloud = PointCloud::read_xyz(&mut xyz.as_bytes()).unwrap();
+        assert_eq!(cloud.points, vec![Vec3f::zero(), Vec3f::new(1.5, 2.0, 3.0)]);
+        assert_eq!(cloud.normals, vec![Vec3f::new(0.0, 0.0, 1.0), Vec3f::new(0.0, 1.0, 0.0)]);
+        assert!(PointCloud::read_xyz(&mut "1 2\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,30 @@
+//! Union-find over indices
+
+/// Disjoint sets of indices, keeping the smallest index of each set as its root
+pub(crate) struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    /// Create `size` singleton sets
+    pub(crate) fn new(size: usize) -> Self {
+        Self { parents: (0..size).collect() }
+    }
+
+    /// Find the root of the set containing `i`, halving the path on the way
+    pub(crate) fn find(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    /// Merge the sets containing `a` and `b`
+    pub(crate) fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[a.max(b)] = a.min(b);
+        }
+    }
+}
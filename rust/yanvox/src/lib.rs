@@ -2,3 +2,6 @@ pub mod math;
 pub mod voxel;
 pub mod voxel_data;
 pub mod mesh_generation;
+pub mod conversion;
+pub mod primitives;
+mod disjoint_set;
@@ -150,6 +150,10 @@ impl Vec3f {
       }
     }
 
+    pub fn dot(&self, other: &Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
     pub fn cross(&self, other: &Self) -> Self {
       Self {
         x:  self.y * other.z - self.z * other.y,
@@ -228,14 +232,43 @@ impl Bounds3i {
         self.min.z < other.max.z && self.max.z > other.min.z
     }
 
+    /// Check if other bounds lie entirely inside these
+    pub fn contains_bounds(self, other: Self) -> bool {
+        other.min.x >= self.min.x && other.max.x <= self.max.x &&
+        other.min.y >= self.min.y && other.max.y <= self.max.y &&
+        other.min.z >= self.min.z && other.max.z <= self.max.z
+    }
+
+    /// Get the overlap of two bounds, empty in size if they do not intersect
+    pub fn intersection(self, other: Self) -> Self {
+        Self {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
     pub fn size(self) -> Vec3i {
         self.max - self.min
     }
 
+    /// Check if the bounds hold no coordinates
+    pub fn is_empty(self) -> bool {
+        self.max.x <= self.min.x || self.max.y <= self.min.y || self.max.z <= self.min.z
+    }
+
     pub fn volume(self) -> i64 {
         let size = self.size();
         size.x as i64 * size.y as i64 * size.z as i64
     }
+
+    /// Iterate over the coordinates inside the bounds, x varying fastest
+    pub fn coords(self) -> impl Iterator<Item = Vec3i> {
+        (self.min.z..self.max.z).flat_map(move |z| {
+            (self.min.y..self.max.y).flat_map(move |y| {
+                (self.min.x..self.max.x).map(move |x| Vec3i::new(x, y, z))
+            })
+        })
+    }
 }
 
 /// 3D axis-aligned bounding box with floating point coordinates
@@ -320,6 +353,49 @@ impl Bounds3f {
     }
 }
 
+/// Surface area and mass properties of a closed shape of unit density
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MassProperties {
+    pub surface_area: f32,
+    /// Enclosed volume, negative for inward-facing surfaces
+    pub volume: f32,
+    /// Centre of mass
+    pub centroid: Vec3f,
+    /// Inertia tensor about the centroid
+    pub inertia: [[f32; 3]; 3],
+}
+
+impl MassProperties {
+    /// Build mass properties from the volume moments of a shape
+    ///
+    /// `first` is the integral of the position and `second` of the outer
+    /// product of the position with itself over the enclosed volume.
+    pub fn from_moments(surface_area: f64, volume: f64, first: [f64; 3], second: [[f64; 3]; 3]) -> Self {
+        let centroid = if volume != 0.0 { first.map(|m| m / volume) } else { [0.0; 3] };
+        // Covariance about the centroid, then I = tr(C) Id - C
+        let mut covariance = second;
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] -= volume * centroid[i] * centroid[j];
+            }
+        }
+        let trace = covariance[0][0] + covariance[1][1] + covariance[2][2];
+        let mut inertia = [[0.0f32; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                let diagonal = if i == j { trace } else { 0.0 };
+                inertia[i][j] = (diagonal - covariance[i][j]) as f32;
+            }
+        }
+        Self {
+            surface_area: surface_area as f32,
+            volume: volume as f32,
+            centroid: Vec3f::new(centroid[0] as f32, centroid[1] as f32, centroid[2] as f32),
+            inertia,
+        }
+    }
+}
+
 /// Type aliases for common use cases
 pub type Vec3 = Vec3i;
 pub type Bounds3 = Bounds3i;
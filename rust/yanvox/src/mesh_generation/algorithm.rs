@@ -1,50 +1,179 @@
-use crate::voxel::{VoxelVolume, SignedDistance};
-use crate::math::{Vec3i, Vec3f};
+use crate::voxel::{VoxelVolume, VoxelData, SignedDistance};
+use crate::math::{Vec3i, Vec3f, Bounds3i};
 use super::mesh::{Mesh, Vertex, Triangle};
 use super::marching_cubes::{CORNER_OFFSETS, EDGE_VERTEX_INDICES, EDGE_MASKS, TRIANGLE_TABLE};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use thiserror::Error;
 
 /// Error types for algorithm
 #[derive(Debug, Error)]
 pub enum AlgorithmError {
+    #[error("No active voxels in the meshed region")]
+    EmptyVolume,
+    #[error("Region of {voxels} voxels exceeds the limit of {max} voxels")]
+    RegionTooLarge { voxels: i64, max: i64 },
+    #[error("Mesh generation was cancelled")]
+    Cancelled,
+    #[error("Non-finite value {value} at voxel {coord:?}")]
+    NonFiniteValue { coord: Vec3i, value: f32 },
+    #[error("Chunk {chunk:?} and neighbour {neighbour:?} differ by more than one level of detail")]
+    UnsupportedLodTransition { chunk: Vec3i, neighbour: Vec3i },
+}
+
+/// Read-only view of a voxel volume handed to a mesher
+///
+/// The view restricts meshing to an optional voxel-space region and carries
+/// an optional cancellation flag that meshers poll while they run.
+pub struct VolumeView<'a, T: VoxelData> {
+    volume: &'a VoxelVolume<T>,
+    region: Option<Bounds3i>,
+    cancel_flag: Option<Arc<AtomicBool>>,
+}
+
+impl<'a, T: VoxelData + Clone + 'static> VolumeView<'a, T> {
+    /// Create a view over the whole volume
+    pub fn new(volume: &'a VoxelVolume<T>) -> Self {
+        Self {
+            volume,
+            region: None,
+            cancel_flag: None,
+        }
+    }
+
+    /// Restrict the view to a voxel-space region (max exclusive)
+    pub fn with_region(mut self, region: Bounds3i) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Attach a flag that cancels mesh generation when set to `true`
+    pub fn with_cancel_flag(mut self, cancel_flag: Arc<AtomicBool>) -> Self {
+        self.cancel_flag = Some(cancel_flag);
+        self
+    }
+
+    /// The underlying voxel volume
+    pub fn volume(&self) -> &'a VoxelVolume<T> {
+        self.volume
+    }
+
+    /// The voxel-space region to mesh, if restricted
+    pub fn region(&self) -> Option<Bounds3i> {
+        self.region
+    }
+
+    /// The voxel-space bounds covered by the view
+    pub fn bounds(&self) -> Bounds3i {
+        match self.region {
+            Some(region) => region,
+            None => self.volume.bounds(),
+        }
+    }
+
+    /// Check if a voxel coordinate lies within the view
+    pub fn contains(&self, coord: Vec3i) -> bool {
+        self.region.is_none_or(|region| region.contains(coord))
+    }
+
+    /// Iterate over the active voxels within the view
+    ///
+    /// With a region, only the leaves overlapping it are visited.
+    pub fn active_voxels(&self) -> Box<dyn Iterator<Item = (Vec3i, &'a T)> + 'a> {
+        match self.region {
+            Some(region) => self.volume.active_voxels_in(region),
+            None => self.volume.active_voxels(),
+        }
+    }
+
+    /// Check if cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_flag.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Return `AlgorithmError::Cancelled` if cancellation has been requested
+    pub fn check_cancelled(&self) -> Result<(), AlgorithmError> {
+        if self.is_cancelled() {
+            Err(AlgorithmError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Surface extraction algorithm used by `MeshBuilder`
+///
+/// Implement this trait to plug a custom algorithm into the builder.
+pub trait Mesher<T: VoxelData> {
+    /// Generate a mesh for the voxels in `view` at the given iso level
+    fn generate_mesh(&self, view: &VolumeView<'_, T>, iso_level: f32) -> Result<Mesh, AlgorithmError>;
+
+    /// Whether the algorithm extracts an iso surface
+    ///
+    /// Meshers returning `false` ignore the iso level, and `MeshBuilder`
+    /// does not require one to be set.
+    fn uses_iso_level(&self) -> bool {
+        true
+    }
 }
 
 /// Core marching cubes algorithm implementation
+///
+/// Active voxels are meshed leaf by leaf. Leaf results are stitched in leaf
+/// order into a single indexed mesh where vertices on shared cube edges are
+/// merged. See [`MarchingCubesAlgorithm::parallel`] for meshing on several
+/// threads.
+#[derive(Debug, Clone, Default)]
 pub struct MarchingCubesAlgorithm {
-    // Configuration parameters could be added here
+    /// Maximum number of voxels in the meshed region, unlimited if `None`
+    max_region_voxels: Option<i64>,
+    /// Whether to emit vertex normals from the signed distance gradient
+    gradient_normals: bool,
 }
 
 impl MarchingCubesAlgorithm {
     pub fn new() -> Self {
-        Self {}
+        Self::default()
     }
 
-    /// Generate a mesh using the marching cubes algorithm
-    pub fn generate_mesh<T: SignedDistance + Clone + 'static>(
+    /// Refuse to mesh regions containing more than `max` voxels
+    pub fn with_max_region_voxels(mut self, max: i64) -> Self {
+        self.max_region_voxels = Some(max);
+        self
+    }
+
+    /// Emit per-vertex normals from the signed distance gradient
+    pub fn with_gradient_normals(mut self) -> Self {
+        self.gradient_normals = true;
+        self
+    }
+
+    /// Mesh the cubes at the given coordinates of a single leaf
+    fn mesh_leaf<T: SignedDistance + Clone + 'static>(
         &self,
-        volume: &VoxelVolume<T>,
+        view: &VolumeView<'_, T>,
+        coords: &[Vec3i],
         iso_level: f32,
-    ) -> Result<Mesh, AlgorithmError> {
-        let mut mesh = Mesh::new();
-
-        // Use the active voxels iterator from VoxelVolume
-        for (coord, _voxel) in volume.active_voxels() {
-            self.process_cube(volume, &mut mesh, coord, iso_level)?;
+    ) -> Result<PartialMesh, AlgorithmError> {
+        view.check_cancelled()?;
+        let mut partial = PartialMesh::default();
+        for &coord in coords {
+            self.process_cube(view.volume(), &mut partial, coord, iso_level)?;
         }
-
-        Ok(mesh)
+        Ok(partial)
     }
 
     /// Process a single cube for marching cubes with proper edge vertex interpolation
     fn process_cube<T: SignedDistance + Clone + 'static>(
         &self,
         volume: &VoxelVolume<T>,
-        mesh: &mut Mesh,
+        mesh: &mut PartialMesh,
         coord: Vec3i,
         iso_level: f32,
     ) -> Result<(), AlgorithmError> {
         // Get the 8 corner values of the cube
-        if let Some(corner_values) = self.get_cube_corner_values(volume, coord) {
+        if let Some(corner_values) = self.get_cube_corner_values(volume, coord)? {
             // Calculate the cube configuration index
             let cube_index = self.calculate_cube_index(&corner_values, iso_level);
             
@@ -57,18 +186,26 @@ impl MarchingCubesAlgorithm {
             let edge_mask = EDGE_MASKS[cube_index as usize];
             
             // Calculate vertex positions on active edges
-            let mut edge_vertices = [Vec3f::new(0.0, 0.0, 0.0); 12];
+            let mut edge_vertices = [0usize; 12];
             let leaf_size = volume.get_leaf_voxel_size();
+            let corner_offsets = self.corner_offsets();
             
-            for edge in 0..12 {
+            for (edge, edge_vertex) in edge_vertices.iter_mut().enumerate() {
                 if (edge_mask & (1 << edge)) != 0u16 {
-                    edge_vertices[edge] = self.interpolate_edge_vertex(
-                        &corner_values,
-                        coord,
-                        edge,
-                        iso_level,
-                        leaf_size
-                    );
+                    let [c1, c2] = EDGE_VERTEX_INDICES[edge];
+                    let key = EdgeKey::new(coord + corner_offsets[c1 as usize], coord + corner_offsets[c2 as usize]);
+                    *edge_vertex = mesh.vertex(key, || {
+                        let position = self.interpolate_edge_vertex(
+                            &corner_values,
+                            coord,
+                            edge,
+                            iso_level,
+                            leaf_size
+                        );
+                        let normal = self.gradient_normals
+                            .then(|| volume.signed_distance_gradient(position).normalize());
+                        (position, normal)
+                    });
                 }
             }
             
@@ -76,16 +213,11 @@ impl MarchingCubesAlgorithm {
             let triangle_data = TRIANGLE_TABLE[cube_index as usize];
             let mut i = 0;
             while i < 16 && triangle_data[i] != -1i32 {
-                let v1_idx = triangle_data[i] as usize;
-                let v2_idx = triangle_data[i + 1] as usize;
-                let v3_idx = triangle_data[i + 2] as usize;
+                let v1 = edge_vertices[triangle_data[i] as usize];
+                let v2 = edge_vertices[triangle_data[i + 1] as usize];
+                let v3 = edge_vertices[triangle_data[i + 2] as usize];
                 
-                // Add vertices to mesh and create triangle
-                let v1 = mesh.add_vertex(Vertex { position: edge_vertices[v1_idx] });
-                let v2 = mesh.add_vertex(Vertex { position: edge_vertices[v2_idx] });
-                let v3 = mesh.add_vertex(Vertex { position: edge_vertices[v3_idx] });
-                
-                mesh.add_triangle(Triangle { indices: [v1, v2, v3] });
+                mesh.triangles.push([v1, v2, v3]);
                 
                 i += 3;
             }
@@ -95,21 +227,27 @@ impl MarchingCubesAlgorithm {
     }
 
     /// Get the signed distance values at the 8 corners of a cube
+    ///
+    /// Returns `None` if any corner is inactive
     fn get_cube_corner_values<T: SignedDistance + Clone + 'static>(
         &self,
         volume: &VoxelVolume<T>,
         coord: Vec3i,
-    ) -> Option<[f32; 8]> {
+    ) -> Result<Option<[f32; 8]>, AlgorithmError> {
         let offsets = self.corner_offsets();
 
         let mut values = [0.0; 8];
         for (i, offset) in offsets.iter().enumerate() {
             let corner_coord = coord + *offset;
             let voxel = volume.get_voxel(corner_coord);
-            if ! voxel.is_active() { return None; }
-            values[i] = voxel.signed_distance();
+            if ! voxel.is_active() { return Ok(None); }
+            let value = voxel.signed_distance();
+            if !value.is_finite() {
+                return Err(AlgorithmError::NonFiniteValue { coord: corner_coord, value });
+            }
+            values[i] = value;
         }
-        Some(values)
+        Ok(Some(values))
     }
 
     /// Calculate the cube configuration index based on corner values and iso level
@@ -174,3 +312,162 @@ impl MarchingCubesAlgorithm {
       ]
     }
 }
+
+impl MarchingCubesAlgorithm {
+    /// Mesh on all available threads
+    ///
+    /// Only available for voxel types that can be shared between threads.
+    pub fn parallel(self) -> ParallelMarchingCubes {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        ParallelMarchingCubes { algorithm: self, threads }
+    }
+
+    /// Generate a mesh for a whole volume using the marching cubes algorithm
+    ///
+    /// Shorthand for [`Mesher::generate_mesh`] with a [`VolumeView`] over
+    /// the volume.
+    pub fn generate_mesh<T: SignedDistance + Clone + 'static>(
+        &self,
+        volume: &VoxelVolume<T>,
+        iso_level: f32,
+    ) -> Result<Mesh, AlgorithmError> {
+        Mesher::generate_mesh(self, &VolumeView::new(volume), iso_level)
+    }
+
+    /// Group the active voxels of the view by leaf, in a deterministic order
+    fn leaves<T: SignedDistance + Clone + 'static>(&self, view: &VolumeView<'_, T>) -> Result<Vec<(Vec3i, Vec<Vec3i>)>, AlgorithmError> {
+        if let Some(max) = self.max_region_voxels {
+            let voxels = view.bounds().volume();
+            if voxels > max {
+                return Err(AlgorithmError::RegionTooLarge { voxels, max });
+            }
+        }
+
+        let volume = view.volume();
+        let mut leaves: HashMap<Vec3i, Vec<Vec3i>> = HashMap::new();
+        for (coord, _voxel) in view.active_voxels() {
+            leaves.entry(volume.leaf_key(coord)).or_default().push(coord);
+        }
+        if leaves.is_empty() {
+            return Err(AlgorithmError::EmptyVolume);
+        }
+        let mut leaves: Vec<(Vec3i, Vec<Vec3i>)> = leaves.into_iter().collect();
+        leaves.sort_by_key(|(key, _)| (key.z, key.y, key.x));
+        Ok(leaves)
+    }
+
+    /// Stitch leaf meshes in order, merging vertices on shared edges
+    fn stitch(partials: Vec<PartialMesh>) -> Mesh {
+        let mut mesh = Mesh::new();
+        let mut vertices: HashMap<EdgeKey, usize> = HashMap::new();
+        for partial in partials {
+            let indices: Vec<usize> = partial.vertices.iter()
+                .map(|&(key, position, normal)| *vertices.entry(key)
+                    .or_insert_with(|| match normal {
+                        Some(normal) => mesh.add_vertex_with_normal(Vertex { position }, normal),
+                        None => mesh.add_vertex(Vertex { position }),
+                    }))
+                .collect();
+            for triangle in partial.triangles {
+                mesh.add_triangle(Triangle { indices: triangle.map(|i| indices[i]) });
+            }
+        }
+        mesh
+    }
+}
+
+impl<T: SignedDistance + Clone + 'static> Mesher<T> for MarchingCubesAlgorithm {
+    /// Generate a mesh using the marching cubes algorithm, on a single thread
+    fn generate_mesh(&self, view: &VolumeView<'_, T>, iso_level: f32) -> Result<Mesh, AlgorithmError> {
+        let partials = self.leaves(view)?
+            .iter()
+            .map(|(_, coords)| self.mesh_leaf(view, coords, iso_level))
+            .collect::<Result<_, _>>()?;
+        Ok(Self::stitch(partials))
+    }
+}
+
+/// Marching cubes meshing leaves on several threads
+///
+/// Created with [`MarchingCubesAlgorithm::parallel`]. Leaves are split
+/// into one batch per thread; the output is the same as that of the
+/// single-threaded algorithm.
+#[derive(Debug, Clone)]
+pub struct ParallelMarchingCubes {
+    algorithm: MarchingCubesAlgorithm,
+    threads: usize,
+}
+
+impl ParallelMarchingCubes {
+    /// Mesh on up to `threads` threads instead of all available ones
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+}
+
+impl Default for ParallelMarchingCubes {
+    fn default() -> Self {
+        MarchingCubesAlgorithm::new().parallel()
+    }
+}
+
+impl<T: SignedDistance + Clone + Sync + 'static> Mesher<T> for ParallelMarchingCubes {
+    fn generate_mesh(&self, view: &VolumeView<'_, T>, iso_level: f32) -> Result<Mesh, AlgorithmError> {
+        let algorithm = &self.algorithm;
+        let leaves = algorithm.leaves(view)?;
+        let threads = self.threads.min(leaves.len());
+        let mesh_batch = |batch: &[(Vec3i, Vec<Vec3i>)]| {
+            batch.iter()
+                .map(|(_, coords)| algorithm.mesh_leaf(view, coords, iso_level))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        if threads <= 1 {
+            return Ok(MarchingCubesAlgorithm::stitch(mesh_batch(&leaves)?));
+        }
+
+        let batch_size = leaves.len().div_ceil(threads);
+        let results: Vec<Result<Vec<PartialMesh>, AlgorithmError>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = leaves.chunks(batch_size)
+                .map(|batch| scope.spawn(move || mesh_batch(batch)))
+                .collect();
+            handles.into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                .collect()
+        });
+        let mut partials = Vec::with_capacity(leaves.len());
+        for result in results {
+            partials.extend(result?);
+        }
+        Ok(MarchingCubesAlgorithm::stitch(partials))
+    }
+}
+
+/// Cube edge identified by its two end voxels, lowest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct EdgeKey(Vec3i, Vec3i);
+
+impl EdgeKey {
+    fn new(a: Vec3i, b: Vec3i) -> Self {
+        if (a.x, a.y, a.z) <= (b.x, b.y, b.z) { Self(a, b) } else { Self(b, a) }
+    }
+}
+
+/// Mesh of a single leaf, with vertices keyed by the cube edge they lie on
+#[derive(Default)]
+struct PartialMesh {
+    vertices: Vec<(EdgeKey, Vec3f, Option<Vec3f>)>,
+    lookup: HashMap<EdgeKey, usize>,
+    triangles: Vec<[usize; 3]>,
+}
+
+impl PartialMesh {
+    /// Get or create the vertex on an edge from its position and optional normal
+    fn vertex(&mut self, key: EdgeKey, vertex: impl FnOnce() -> (Vec3f, Option<Vec3f>)) -> usize {
+        *self.lookup.entry(key).or_insert_with(|| {
+            let (position, normal) = vertex();
+            self.vertices.push((key, position, normal));
+            self.vertices.len() - 1
+        })
+    }
+}
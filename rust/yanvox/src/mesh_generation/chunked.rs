@@ -0,0 +1,172 @@
+use crate::voxel::{VoxelVolume, VoxelData};
+use crate::math::{Vec3i, Bounds3i};
+use super::mesh::Mesh;
+use super::algorithm::{AlgorithmError, Mesher, VolumeView};
+use std::collections::{HashMap, HashSet};
+
+/// Mesher splitting a volume into cubic chunks meshed independently
+///
+/// Chunks are `2^chunk_log2` voxels per side, typically the leaf or internal
+/// node size. Combined with the volume's dirty leaf tracking, only the chunks
+/// affected by edits since the last checkpoint need to be regenerated:
+///
+/// ```ignore
+/// let mesher = ChunkedMesher::new(MarchingCubesAlgorithm::new(), volume.leaf_log2());
+/// let mut meshes = mesher.mesh_all(&volume, 0.0)?;
+/// volume.checkpoint();
+///
+/// volume.set_voxel(coord, value);
+/// meshes.extend(mesher.mesh_dirty(&volume, 0.0)?);
+/// volume.checkpoint();
+/// ```
+pub struct ChunkedMesher<M> {
+    mesher: M,
+    chunk_log2: u32,
+}
+
+impl<M> ChunkedMesher<M> {
+    /// Create a chunked mesher running `mesher` on chunks of `2^chunk_log2` voxels per side
+    pub fn new(mesher: M, chunk_log2: u32) -> Self {
+        Self { mesher, chunk_log2 }
+    }
+
+    /// Get the key (lower corner) of the chunk containing a voxel
+    pub fn chunk_key(&self, coord: Vec3i) -> Vec3i {
+        let size = 1 << self.chunk_log2;
+        Vec3i::new(coord.x & !(size - 1), coord.y & !(size - 1), coord.z & !(size - 1))
+    }
+
+    /// Get the voxel-space bounds of a chunk
+    pub fn chunk_bounds(&self, key: Vec3i) -> Bounds3i {
+        let size = 1 << self.chunk_log2;
+        Bounds3i::new(key, key + Vec3i::new(size, size, size))
+    }
+
+    /// Get the keys of all chunks intersecting a voxel-space region
+    fn chunks_in(&self, bounds: Bounds3i) -> impl Iterator<Item = Vec3i> + '_ {
+        let size = 1 << self.chunk_log2;
+        let min = self.chunk_key(bounds.min);
+        let max = self.chunk_key(bounds.max - Vec3i::one());
+        (min.z..=max.z).step_by(size).flat_map(move |z| {
+            (min.y..=max.y).step_by(size).flat_map(move |y| {
+                (min.x..=max.x).step_by(size).map(move |x| Vec3i::new(x, y, z))
+            })
+        })
+    }
+
+    /// Get the keys of the chunks affected by the leaves modified since the last checkpoint
+    ///
+    /// A modified voxel changes the cells and faces of its direct neighbours,
+    /// so each dirty leaf is grown by one voxel before finding chunks.
+    pub fn dirty_chunks<T: VoxelData + Clone + 'static>(&self, volume: &VoxelVolume<T>) -> HashSet<Vec3i> {
+        volume.dirty_bounds()
+            .flat_map(|bounds| {
+                let border = Bounds3i::new(bounds.min - Vec3i::one(), bounds.max + Vec3i::one());
+                self.chunks_in(border).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Mesh the given chunks
+    ///
+    /// Chunks without any surface, including those whose mesher reports
+    /// `AlgorithmError::EmptyVolume`, map to an empty mesh, so callers can drop
+    /// their previous mesh.
+    pub fn mesh_chunks<T: VoxelData + Clone + 'static>(
+        &self,
+        volume: &VoxelVolume<T>,
+        iso_level: f32,
+        keys: impl IntoIterator<Item = Vec3i>,
+    ) -> Result<HashMap<Vec3i, Mesh>, AlgorithmError>
+    where
+        M: Mesher<T>,
+    {
+        let mut meshes = HashMap::new();
+        for key in keys {
+            let view = VolumeView::new(volume).with_region(self.chunk_bounds(key));
+            let mesh = match self.mesher.generate_mesh(&view, iso_level) {
+                Ok(mesh) => mesh,
+                Err(AlgorithmError::EmptyVolume) => Mesh::new(),
+                Err(error) => return Err(error),
+            };
+            meshes.insert(key, mesh);
+        }
+        Ok(meshes)
+    }
+
+    /// Mesh every chunk containing active voxels
+    pub fn mesh_all<T: VoxelData + Clone + 'static>(
+        &self,
+        volume: &VoxelVolume<T>,
+        iso_level: f32,
+    ) -> Result<HashMap<Vec3i, Mesh>, AlgorithmError>
+    where
+        M: Mesher<T>,
+    {
+        let keys: HashSet<Vec3i> = volume.active_voxels()
+            .map(|(coord, _)| self.chunk_key(coord))
+            .collect();
+        self.mesh_chunks(volume, iso_level, keys)
+    }
+
+    /// Mesh only the chunks affected by edits since the volume's last checkpoint
+    ///
+    /// Call `VoxelVolume::checkpoint` once the returned meshes are applied.
+    pub fn mesh_dirty<T: VoxelData + Clone + 'static>(
+        &self,
+        volume: &VoxelVolume<T>,
+        iso_level: f32,
+    ) -> Result<HashMap<Vec3i, Mesh>, AlgorithmError>
+    where
+        M: Mesher<T>,
+    {
+        self.mesh_chunks(volume, iso_level, self.dirty_chunks(volume))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::{BoolVoxel, tests::{config, sphere_volume}};
+    use crate::mesh_generation::{GreedyMesher, MarchingCubesAlgorithm};
+    use crate::math::Vec3f;
+    use test_log::test;
+
+    #[test]
+    fn test_chunks_match_full_mesh() {
+        let volume = sphere_volume(Vec3f::zero(), 0.53);
+
+        let full = crate::mesh_generation::MeshBuilder::new(&volume).with_iso_level(0.0).build().unwrap();
+        let mesher = ChunkedMesher::new(MarchingCubesAlgorithm::new(), 3);
+        let chunks = mesher.mesh_all(&volume, 0.0).unwrap();
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.values().map(|mesh| mesh.triangle_count()).sum::<usize>(), full.triangle_count());
+    }
+
+    #[test]
+    fn test_only_dirty_chunks_are_meshed() {
+        let mut volume = VoxelVolume::<BoolVoxel>::with_config(config());
+        for x in 0..16 {
+            volume.set_voxel(Vec3i::new(x, 0, 0), BoolVoxel(true));
+        }
+        let mesher = ChunkedMesher::new(GreedyMesher::active(), 3);
+        let mut meshes = mesher.mesh_all(&volume, 0.0).unwrap();
+        assert_eq!(meshes.len(), 2);
+        // Culling across the chunk boundary leaves no internal wall
+        assert_eq!(meshes.values().map(|mesh| mesh.triangle_count()).sum::<usize>(), 20);
+        volume.checkpoint();
+
+        volume.set_voxel(Vec3i::new(12, 1, 0), BoolVoxel(true));
+        let dirty = mesher.mesh_dirty(&volume, 0.0).unwrap();
+        assert!(dirty.contains_key(&Vec3i::new(8, 0, 0)));
+        assert!(!dirty.contains_key(&Vec3i::new(0, 0, 0)));
+        meshes.extend(dirty);
+        volume.checkpoint();
+
+        // Removing a voxel at the chunk border also remeshes the neighbouring chunk
+        volume.remove_voxel(Vec3i::new(8, 0, 0));
+        let dirty = mesher.mesh_dirty(&volume, 0.0).unwrap();
+        assert!(dirty.contains_key(&Vec3i::new(0, 0, 0)));
+        assert!(dirty.contains_key(&Vec3i::new(8, 0, 0)));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::voxel::VoxelData;
+use crate::math::Vec3i;
+use super::mesh::{Mesh, Vertex, Triangle};
+use super::algorithm::{AlgorithmError, Mesher, VolumeView};
+use std::collections::{BTreeMap, HashMap};
+
+/// Face-culling, greedy-merging cube mesher for block volumes
+///
+/// Every solid voxel is treated as a cube of one leaf voxel size. Faces
+/// between two solid voxels are culled, and coplanar faces sharing a
+/// material are merged into the largest possible rectangles. Each output
+/// triangle carries the material of the voxel it belongs to.
+///
+/// Only active voxels can be solid, and only the slices holding visible
+/// faces are visited, so sparse volumes cost time in their solid voxels
+/// rather than their bounding box.
+///
+/// Each face belongs to the solid voxel it bounds. A restricted region only
+/// emits the faces of its own voxels while still culling against voxels
+/// outside it, so meshes of adjacent regions fit together without walls.
+pub struct GreedyMesher<T> {
+    is_solid: Box<dyn Fn(&T) -> bool>,
+    material: Box<dyn Fn(&T) -> u32>,
+}
+
+/// A visible face in a slice: material and whether it faces the positive axis
+type Face = (u32, bool);
+
+impl<T: VoxelData + Clone + 'static> GreedyMesher<T> {
+    /// Create a greedy mesher with a predicate deciding which voxels are solid
+    pub fn new(is_solid: impl Fn(&T) -> bool + 'static) -> Self {
+        Self {
+            is_solid: Box::new(is_solid),
+            material: Box::new(|_| 0),
+        }
+    }
+
+    /// Create a greedy mesher treating active voxels as solid
+    pub fn active() -> Self {
+        Self::new(|voxel: &T| voxel.is_active())
+    }
+
+    /// Set the function mapping a solid voxel to its material ID
+    pub fn with_material(mut self, material: impl Fn(&T) -> u32 + 'static) -> Self {
+        self.material = Box::new(material);
+        self
+    }
+
+    /// Get the material of a voxel if it is solid
+    fn solid_material(&self, view: &VolumeView<'_, T>, coord: Vec3i) -> Option<u32> {
+        let voxel = view.volume().get_voxel(coord);
+        if (self.is_solid)(voxel) {
+            Some((self.material)(voxel))
+        } else {
+            None
+        }
+    }
+
+    /// Add the two triangles of a merged quad
+    ///
+    /// `base` is the quad corner in voxel space, `du` and `dv` its edges.
+    fn add_quad(mesh: &mut Mesh, base: Vec3i, du: Vec3i, dv: Vec3i, positive: bool, material: u32, leaf_size: f32) {
+        let corners = [base, base + du, base + du + dv, base + dv];
+        let indices = corners.map(|corner| {
+            mesh.add_vertex(Vertex { position: corner.as_vec3f().scale(leaf_size) })
+        });
+        // du x dv points along the positive axis
+        let (first, second) = if positive {
+            ([indices[0], indices[1], indices[2]], [indices[0], indices[2], indices[3]])
+        } else {
+            ([indices[0], indices[2], indices[1]], [indices[0], indices[3], indices[2]])
+        };
+        mesh.add_triangle_with_material(Triangle { indices: first }, material);
+        mesh.add_triangle_with_material(Triangle { indices: second }, material);
+    }
+}
+
+/// Get a component of a vector by axis index
+fn axis(v: Vec3i, d: usize) -> i32 {
+    match d {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+/// Build a vector with `value` on axis `d` and zero elsewhere
+fn unit(d: usize, value: i32) -> Vec3i {
+    match d {
+        0 => Vec3i::new(value, 0, 0),
+        1 => Vec3i::new(0, value, 0),
+        _ => Vec3i::new(0, 0, value),
+    }
+}
+
+impl<T: VoxelData + Clone + 'static> Mesher<T> for GreedyMesher<T> {
+    fn generate_mesh(&self, view: &VolumeView<'_, T>, _iso_level: f32) -> Result<Mesh, AlgorithmError> {
+        // Visible faces by axis and plane, where plane lies between voxel layers `plane - 1` and `plane`
+        let mut slices: BTreeMap<(usize, i32), HashMap<(i32, i32), Face>> = BTreeMap::new();
+        let mut empty = true;
+        for (coord, voxel) in view.active_voxels() {
+            empty = false;
+            if !(self.is_solid)(voxel) {
+                continue;
+            }
+            let material = (self.material)(voxel);
+            for d in 0..3 {
+                let cell = (axis(coord, (d + 1) % 3), axis(coord, (d + 2) % 3));
+                for positive in [false, true] {
+                    let step = if positive { 1 } else { -1 };
+                    if self.solid_material(view, coord + unit(d, step)).is_none() {
+                        let plane = axis(coord, d) + positive as i32;
+                        slices.entry((d, plane)).or_default().insert(cell, (material, positive));
+                    }
+                }
+            }
+        }
+        if empty {
+            return Err(AlgorithmError::EmptyVolume);
+        }
+
+        let leaf_size = view.volume().get_leaf_voxel_size();
+        let mut mesh = Mesh::new();
+        for ((d, plane), mut faces) in slices {
+            view.check_cancelled()?;
+            let u = (d + 1) % 3;
+            let v = (d + 2) % 3;
+            let mut cells: Vec<(i32, i32)> = faces.keys().copied().collect();
+            cells.sort_by_key(|&(i, j)| (j, i));
+
+            // Merge the faces into rectangles
+            for (i, j) in cells {
+                let Some(face) = faces.get(&(i, j)).copied() else {
+                    continue;
+                };
+                let mut width = 1;
+                while faces.get(&(i + width, j)) == Some(&face) {
+                    width += 1;
+                }
+                let mut height = 1;
+                while (0..width).all(|k| faces.get(&(i + k, j + height)) == Some(&face)) {
+                    height += 1;
+                }
+
+                for row in 0..height {
+                    for k in 0..width {
+                        faces.remove(&(i + k, j + row));
+                    }
+                }
+                let (material, positive) = face;
+                let base = unit(d, plane) + unit(u, i) + unit(v, j);
+                Self::add_quad(&mut mesh, base, unit(u, width), unit(v, height), positive, material, leaf_size);
+            }
+        }
+
+        Ok(mesh)
+    }
+
+    fn uses_iso_level(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::VoxelVolume;
+    use crate::voxel_data::{BoolVoxel, IntVoxel, tests::config_with_size};
+    use crate::mesh_generation::MeshBuilder;
+    use crate::math::Vec3f;
+    use test_log::test;
+
+    fn face_normal(mesh: &Mesh, triangle: &Triangle) -> Vec3f {
+        let p = triangle.indices.map(|i| mesh.vertices[i].position);
+        (p[1] - p[0]).cross(&(p[2] - p[0])).normalize()
+    }
+
+    #[test]
+    fn test_solid_block_is_six_quads() {
+        let mut volume = VoxelVolume::<BoolVoxel>::with_config(config_with_size(1.0));
+        for x in 0..3 { for y in 0..3 { for z in 0..3 {
+            volume.set_voxel(Vec3i::new(x, y, z), BoolVoxel(true));
+        }}}
+
+        let mesh = MeshBuilder::from_mesher(&volume, GreedyMesher::active()).build().unwrap();
+        assert_eq!(mesh.triangle_count(), 12);
+        assert!(mesh.has_materials());
+
+        // All normals point away from the block centre
+        let centre = Vec3f::new(1.5, 1.5, 1.5);
+        for triangle in &mesh.triangles {
+            let position = mesh.vertices[triangle.indices[0]].position;
+            let normal = face_normal(&mesh, triangle);
+            let outward = position - centre;
+            assert!(normal.x * outward.x + normal.y * outward.y + normal.z * outward.z > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_materials_are_not_merged() {
+        let mut volume = VoxelVolume::<IntVoxel>::with_config(config_with_size(1.0));
+        volume.set_voxel(Vec3i::new(0, 0, 0), IntVoxel(1));
+        volume.set_voxel(Vec3i::new(1, 0, 0), IntVoxel(2));
+
+        let mesh = MeshBuilder::from_mesher(&volume, GreedyMesher::active().with_material(|v: &IntVoxel| v.0 as u32))
+            .build()
+            .unwrap();
+        // Two end caps plus two quads on each of the four long sides
+        assert_eq!(mesh.triangle_count(), 20);
+        assert_eq!(mesh.triangle_materials.iter().filter(|&&m| m == 1).count(), 10);
+        assert_eq!(mesh.triangle_materials.iter().filter(|&&m| m == 2).count(), 10);
+    }
+
+    #[test]
+    fn test_distant_blocks() {
+        let mut volume = VoxelVolume::<BoolVoxel>::with_config(config_with_size(1.0));
+        volume.set_voxel(Vec3i::new(0, 0, 0), BoolVoxel(true));
+        volume.set_voxel(Vec3i::new(1, 0, 0), BoolVoxel(true));
+        volume.set_voxel(Vec3i::new(100_000, -100_000, 100_000), BoolVoxel(true));
+
+        let mesh = MeshBuilder::from_mesher(&volume, GreedyMesher::active()).build().unwrap();
+        assert_eq!(mesh.triangle_count(), 24);
+        assert!(mesh.vertices.iter().any(|v| v.position.x == 100_001.0));
+    }
+}
@@ -1,6 +1,17 @@
 use crate::math::Vec3f;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Write, Result as IoResult};
 
+mod stl;
+mod obj;
+mod ply;
+mod gltf;
+mod smoothing;
+mod decimation;
+mod validation;
+mod measures;
+pub use validation::MeshReport;
+
 /// A 3D vertex with position
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vertex {
@@ -13,11 +24,41 @@ pub struct Triangle {
     pub indices: [usize; 3],
 }
 
+/// A named per-vertex attribute channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexAttribute {
+    /// Number of components per vertex
+    pub components: usize,
+    /// Attribute values, `components` consecutive values per vertex
+    pub values: Vec<f32>,
+}
+
+impl VertexAttribute {
+    /// Get the values of a vertex
+    pub fn get(&self, index: usize) -> &[f32] {
+        &self.values[index * self.components..(index + 1) * self.components]
+    }
+}
+
+/// Colour given to vertices added before the first colour
+const DEFAULT_COLOR: [f32; 4] = [1.0; 4];
+
 /// A mesh containing vertices and triangles
+///
+/// Per-vertex channels (normals, colours and named attributes) are either
+/// empty or hold one entry per vertex.
 #[derive(Debug, Clone)]
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub triangles: Vec<Triangle>,
+    /// Material ID per triangle, either empty or one entry per triangle
+    pub triangle_materials: Vec<u32>,
+    /// Unit normal per vertex, either empty or one entry per vertex
+    pub normals: Vec<Vec3f>,
+    /// Linear RGBA colour per vertex, either empty or one entry per vertex
+    pub colors: Vec<[f32; 4]>,
+    /// Named attribute channels
+    pub attributes: BTreeMap<String, VertexAttribute>,
 }
 
 impl Mesh {
@@ -26,6 +67,10 @@ impl Mesh {
         Self {
             vertices: Vec::new(),
             triangles: Vec::new(),
+            triangle_materials: Vec::new(),
+            normals: Vec::new(),
+            colors: Vec::new(),
+            attributes: BTreeMap::new(),
         }
     }
 
@@ -40,20 +85,188 @@ impl Mesh {
     }
 
     /// Add a vertex to the mesh and return its index
+    ///
+    /// Existing vertex channels are extended with default values
     pub fn add_vertex(&mut self, vertex: Vertex) -> usize {
         self.vertices.push(vertex);
+        self.pad_vertex_channels();
         self.vertices.len() - 1
     }
 
+    /// Add a vertex with a normal to the mesh and return its index
+    ///
+    /// Vertices added before the first normal get a zero normal
+    pub fn add_vertex_with_normal(&mut self, vertex: Vertex, normal: Vec3f) -> usize {
+        let index = self.add_vertex(vertex);
+        self.normals.resize(index, Vec3f::zero());
+        self.normals.push(normal);
+        index
+    }
+
+    /// Extend non-empty vertex channels to the vertex count
+    fn pad_vertex_channels(&mut self) {
+        let count = self.vertices.len();
+        if !self.normals.is_empty() {
+            self.normals.resize(count, Vec3f::zero());
+        }
+        if !self.colors.is_empty() {
+            self.colors.resize(count, DEFAULT_COLOR);
+        }
+        for attribute in self.attributes.values_mut() {
+            attribute.values.resize(count * attribute.components, 0.0);
+        }
+    }
+
+    /// Check if the mesh carries per-vertex normals
+    pub fn has_normals(&self) -> bool {
+        !self.normals.is_empty()
+    }
+
+    /// Get the normal of a vertex, if the mesh carries normals
+    pub fn vertex_normal(&self, index: usize) -> Option<Vec3f> {
+        self.normals.get(index).copied()
+    }
+
+    /// Check if the mesh carries per-vertex colours
+    pub fn has_colors(&self) -> bool {
+        !self.colors.is_empty()
+    }
+
+    /// Get the colour of a vertex, if the mesh carries colours
+    pub fn vertex_color(&self, index: usize) -> Option<[f32; 4]> {
+        self.colors.get(index).copied()
+    }
+
+    /// Set the colour of a vertex
+    ///
+    /// Other vertices get opaque white if the mesh had no colours
+    pub fn set_vertex_color(&mut self, index: usize, color: [f32; 4]) {
+        self.colors.resize(self.vertices.len(), DEFAULT_COLOR);
+        self.colors[index] = color;
+    }
+
+    /// Get a named attribute channel
+    pub fn attribute(&self, name: &str) -> Option<&VertexAttribute> {
+        self.attributes.get(name)
+    }
+
+    /// Get a named attribute channel, creating it zero-filled if missing
+    ///
+    /// # Panics
+    /// Panics if the channel exists with a different number of components
+    pub fn attribute_mut(&mut self, name: &str, components: usize) -> &mut VertexAttribute {
+        let count = self.vertices.len();
+        let attribute = self.attributes.entry(name.to_string()).or_insert_with(|| VertexAttribute {
+            components,
+            values: vec![0.0; count * components],
+        });
+        assert_eq!(attribute.components, components, "attribute `{name}` has {} components", attribute.components);
+        attribute
+    }
+
+    /// Set the values of a named attribute for a vertex
+    ///
+    /// The channel is created zero-filled with `values.len()` components if missing
+    pub fn set_vertex_attribute(&mut self, name: &str, index: usize, values: &[f32]) {
+        let attribute = self.attribute_mut(name, values.len());
+        attribute.values[index * values.len()..(index + 1) * values.len()].copy_from_slice(values);
+    }
+
+    /// Compute per-vertex normals as the area-weighted average of adjacent face normals
+    pub fn compute_vertex_normals(&mut self) {
+        self.normals = self.area_weighted_normals();
+    }
+
+    /// Get the vertex normals, or area-weighted normals if the mesh has none
+    fn normals_or_computed(&self) -> Vec<Vec3f> {
+        if self.has_normals() { self.normals.clone() } else { self.area_weighted_normals() }
+    }
+
+    /// Area-weighted average of the face normals around each vertex
+    fn area_weighted_normals(&self) -> Vec<Vec3f> {
+        let mut normals = vec![Vec3f::zero(); self.vertices.len()];
+        for triangle in &self.triangles {
+            let [p0, p1, p2] = triangle.indices.map(|i| self.vertices[i].position);
+            // The cross product length is twice the triangle area
+            let weighted = (p1 - p0).cross(&(p2 - p0));
+            for &i in &triangle.indices {
+                normals[i] = normals[i] + weighted;
+            }
+        }
+        normals.into_iter().map(Vec3f::normalize).collect()
+    }
+
     /// Add a triangle to the mesh
+    ///
+    /// If the mesh carries materials, the triangle gets material 0
     pub fn add_triangle(&mut self, triangle: Triangle) {
         self.triangles.push(triangle);
+        if !self.triangle_materials.is_empty() {
+            self.triangle_materials.push(0);
+        }
+    }
+
+    /// Add a triangle with a material ID to the mesh
+    ///
+    /// Triangles added before the first material get material 0
+    pub fn add_triangle_with_material(&mut self, triangle: Triangle, material: u32) {
+        self.triangle_materials.resize(self.triangles.len(), 0);
+        self.triangles.push(triangle);
+        self.triangle_materials.push(material);
+    }
+
+    /// Check if the mesh carries per-triangle materials
+    pub fn has_materials(&self) -> bool {
+        !self.triangle_materials.is_empty()
+    }
+
+    /// Get the material ID of a triangle, if the mesh carries materials
+    pub fn triangle_material(&self, index: usize) -> Option<u32> {
+        self.triangle_materials.get(index).copied()
+    }
+
+    /// Append another mesh, offsetting its triangle indices
+    ///
+    /// Vertex channels present in only one of the meshes are filled with
+    /// default values for the vertices of the other
+    pub fn append(&mut self, other: &Mesh) {
+        let offset = self.vertices.len();
+        self.vertices.extend_from_slice(&other.vertices);
+        if other.has_normals() {
+            self.normals.resize(offset, Vec3f::zero());
+            self.normals.extend_from_slice(&other.normals);
+        }
+        if other.has_colors() {
+            self.colors.resize(offset, DEFAULT_COLOR);
+            self.colors.extend_from_slice(&other.colors);
+        }
+        for (name, other_attribute) in &other.attributes {
+            let attribute = self.attributes.entry(name.clone()).or_insert_with(|| VertexAttribute {
+                components: other_attribute.components,
+                values: vec![0.0; offset * other_attribute.components],
+            });
+            if attribute.components == other_attribute.components {
+                attribute.values.extend_from_slice(&other_attribute.values);
+            }
+        }
+        self.pad_vertex_channels();
+        for (index, triangle) in other.triangles.iter().enumerate() {
+            let triangle = Triangle { indices: triangle.indices.map(|i| i + offset) };
+            match other.triangle_material(index) {
+                Some(material) => self.add_triangle_with_material(triangle, material),
+                None => self.add_triangle(triangle),
+            }
+        }
     }
 
     /// Clear all vertices and triangles
     pub fn clear(&mut self) {
         self.vertices.clear();
         self.triangles.clear();
+        self.triangle_materials.clear();
+        self.normals.clear();
+        self.colors.clear();
+        self.attributes.clear();
     }
 
     /// Check if the mesh is empty
@@ -145,6 +358,144 @@ impl Mesh {
         Ok(())
     }
 
+    /// Weld vertices closer than `tolerance` to each other
+    ///
+    /// Each cluster keeps the first of its vertices, with its channel
+    /// values. Triangles that become degenerate are removed.
+    ///
+    /// # Returns
+    /// Number of vertices removed
+    pub fn weld_vertices(&mut self, tolerance: f32) -> usize {
+        let cell_size = tolerance.max(f32::MIN_POSITIVE);
+        let cell = |p: Vec3f| [p.x, p.y, p.z].map(|v| (v / cell_size).floor() as i64);
+        let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        let mut kept = 0;
+        for (index, vertex) in self.vertices.iter().enumerate() {
+            let [x, y, z] = cell(vertex.position);
+            let existing = (-1..=1)
+                .flat_map(|dz| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| [x + dx, y + dy, z + dz])))
+                .filter_map(|key| cells.get(&key))
+                .flatten()
+                .find(|&&other| (self.vertices[other].position - vertex.position).length() <= tolerance)
+                .copied();
+            match existing {
+                Some(other) => remap.push(remap[other]),
+                None => {
+                    cells.entry([x, y, z]).or_default().push(index);
+                    remap.push(Some(kept));
+                    kept += 1;
+                }
+            }
+        }
+        let removed = self.vertices.len() - kept;
+        self.reindex_vertices(&remap);
+        removed
+    }
+
+    /// Move vertices to new indices, dropping those mapped to `None`
+    ///
+    /// Vertices mapped to the same index are merged, keeping the channel
+    /// values of the first. Triangles referencing dropped vertices or made
+    /// degenerate are removed along with their materials.
+    fn reindex_vertices(&mut self, remap: &[Option<usize>]) {
+        let count = remap.iter().flatten().map(|&i| i + 1).max().unwrap_or(0);
+        let mut sources = vec![usize::MAX; count];
+        for (old, new) in remap.iter().enumerate() {
+            if let Some(new) = *new
+                && sources[new] == usize::MAX {
+                sources[new] = old;
+            }
+        }
+
+        self.vertices = sources.iter().map(|&old| self.vertices[old]).collect();
+        if self.has_normals() {
+            self.normals = sources.iter().map(|&old| self.normals[old]).collect();
+        }
+        if self.has_colors() {
+            self.colors = sources.iter().map(|&old| self.colors[old]).collect();
+        }
+        for attribute in self.attributes.values_mut() {
+            let components = attribute.components;
+            attribute.values = sources.iter()
+                .flat_map(|&old| attribute.values[old * components..(old + 1) * components].to_vec())
+                .collect();
+        }
+
+        let has_materials = self.has_materials();
+        let mut triangles = Vec::with_capacity(self.triangles.len());
+        let mut materials = Vec::new();
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            let [Some(a), Some(b), Some(c)] = triangle.indices.map(|i| remap[i]) else {
+                continue;
+            };
+            if a == b || b == c || c == a {
+                continue;
+            }
+            triangles.push(Triangle { indices: [a, b, c] });
+            if has_materials {
+                materials.push(self.triangle_materials[index]);
+            }
+        }
+        self.triangles = triangles;
+        self.triangle_materials = materials;
+    }
+
+    /// Remove vertices not referenced by any triangle
+    fn remove_unreferenced_vertices(&mut self) {
+        let mut used = vec![false; self.vertices.len()];
+        for triangle in &self.triangles {
+            for &i in &triangle.indices {
+                used[i] = true;
+            }
+        }
+        let mut next = 0;
+        let remap: Vec<Option<usize>> = used.iter()
+            .map(|&used| used.then(|| {
+                next += 1;
+                next - 1
+            }))
+            .collect();
+        self.reindex_vertices(&remap);
+    }
+
+    /// Read a mesh from a file, picking the format from the file extension
+    ///
+    /// Supported extensions are `stl` (ASCII or binary), `obj` and `ply`.
+    pub fn read_file<P: AsRef<std::path::Path>>(path: P) -> IoResult<Mesh> {
+        let path = path.as_ref();
+        match path.extension().and_then(|s| s.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("stl") | Some("astl") | Some("ascii") => Self::read_stl_file(path),
+            Some("obj") => Self::read_obj_file(path),
+            Some("ply") => Self::read_ply_file(path),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("unsupported mesh file extension: {}", path.display()),
+            )),
+        }
+    }
+
+    /// Export mesh to a file, picking the format from the file extension
+    ///
+    /// Supported extensions are `stl` (binary STL), `astl` and `ascii`
+    /// (ASCII STL), `obj` (Wavefront OBJ with normals, plus an MTL file
+    /// when the mesh carries materials), `ply` (binary PLY), `gltf` (glTF
+    /// with an embedded buffer) and `glb` (binary glTF).
+    pub fn export_file<P: AsRef<std::path::Path>>(&self, path: P) -> IoResult<()> {
+        let path = path.as_ref();
+        match path.extension().and_then(|s| s.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("stl") | Some("astl") | Some("ascii") => self.export_stl_file(path),
+            Some("obj") => self.export_obj_file(path, true),
+            Some("ply") => self.export_ply_file(path),
+            Some("gltf") => self.export_gltf_file(path),
+            Some("glb") => self.export_glb_file(path),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("unsupported mesh file extension: {}", path.display()),
+            )),
+        }
+    }
+
     /// Export mesh to STL file (auto-detects format based on file extension)
     pub fn export_stl_file<P: AsRef<std::path::Path>>(&self, path: P) -> IoResult<()> {
         let path = path.as_ref();
@@ -167,8 +518,110 @@ impl Mesh {
     }
 }
 
+/// Merges vertices with identical positions while a mesh is read
+#[derive(Default)]
+struct VertexWelder {
+    vertices: HashMap<[u32; 3], usize>,
+}
+
+impl VertexWelder {
+    /// Get the index of the vertex at a position, adding it if new
+    fn weld(&mut self, mesh: &mut Mesh, position: Vec3f) -> usize {
+        // Adding zero maps -0.0 to 0.0
+        let key = [position.x, position.y, position.z].map(|v| (v + 0.0).to_bits());
+        *self.vertices.entry(key).or_insert_with(|| mesh.add_vertex(Vertex { position }))
+    }
+}
+
 impl Default for Mesh {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::voxel_data::tests::sphere_volume;
+    use crate::mesh_generation::MeshBuilder;
+    use test_log::test;
+
+    /// Marching cubes mesh of the unit sphere
+    pub(in crate::mesh_generation) fn sphere_mesh() -> Mesh {
+        MeshBuilder::new(&sphere_volume(Vec3f::zero(), 1.0)).with_iso_level(0.0).build().unwrap()
+    }
+
+    /// Unit sphere mesh with deterministic radial noise of up to 2%
+    pub(in crate::mesh_generation) fn noisy_sphere() -> Mesh {
+        let mut mesh = sphere_mesh();
+        let mut state = 12345u32;
+        for vertex in &mut mesh.vertices {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let noise = ((state >> 16) as f32 / 65535.0 - 0.5) * 0.04;
+            vertex.position = vertex.position.scale(1.0 + noise);
+        }
+        mesh
+    }
+
+    /// Closed tetrahedron with outward-facing triangles
+    pub(in crate::mesh_generation) fn tetrahedron() -> Mesh {
+        let mut mesh = Mesh::new();
+        for p in [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0)] {
+            mesh.add_vertex(Vertex { position: Vec3f::new(p.0, p.1, p.2) });
+        }
+        for indices in [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]] {
+            mesh.add_triangle(Triangle { indices });
+        }
+        mesh
+    }
+
+    /// Unit square in the z = 0 plane, split into two triangles
+    pub(in crate::mesh_generation) fn two_triangles() -> Mesh {
+        let mut mesh = Mesh::new();
+        for p in [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (1.0, 1.0, 0.0), (0.0, 1.0, 0.0)] {
+            mesh.add_vertex(Vertex { position: Vec3f::new(p.0, p.1, p.2) });
+        }
+        mesh.add_triangle(Triangle { indices: [0, 1, 2] });
+        mesh.add_triangle(Triangle { indices: [0, 2, 3] });
+        mesh
+    }
+
+    /// Number of edges used by a single triangle
+    pub(in crate::mesh_generation) fn boundary_edge_count(mesh: &Mesh) -> usize {
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for triangle in &mesh.triangles {
+            let [a, b, c] = triangle.indices;
+            for (p, q) in [(a, b), (b, c), (c, a)] {
+                *edges.entry((p.min(q), p.max(q))).or_default() += 1;
+            }
+        }
+        edges.values().filter(|&&count| count == 1).count()
+    }
+
+    #[test]
+    fn test_weld_vertices() {
+        // Triangle soup of a closed sphere, with jittered duplicates
+        let sphere = sphere_mesh();
+        let mut soup = Mesh::new();
+        for (index, triangle) in sphere.triangles.iter().enumerate() {
+            let indices = triangle.indices.map(|i| {
+                let jitter = Vec3f::new(1e-5, -1e-5, 0.0).scale((index % 3) as f32);
+                soup.add_vertex(Vertex { position: sphere.vertices[i].position + jitter })
+            });
+            soup.add_triangle_with_material(Triangle { indices }, index as u32 % 2);
+        }
+        assert!(boundary_edge_count(&soup) > 0);
+
+        // Marching cubes may place vertices of different edges on the same corner
+        let mut expected = sphere.clone();
+        expected.weld_vertices(1e-4);
+        assert!(expected.vertex_count() <= sphere.vertex_count());
+
+        let removed = soup.weld_vertices(1e-4);
+        assert_eq!(soup.vertex_count(), expected.vertex_count());
+        assert_eq!(removed, 3 * sphere.triangle_count() - expected.vertex_count());
+        assert_eq!(soup.triangle_count(), expected.triangle_count());
+        assert_eq!(soup.triangle_materials.len(), soup.triangle_count());
+        assert_eq!(boundary_edge_count(&soup), 0);
+    }
+}
@@ -0,0 +1,358 @@
+use super::{Mesh, Triangle};
+use crate::math::Vec3f;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Symmetric 4x4 error quadric, stored as its upper triangle
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Quadric of the squared distance to the plane `n.x + d = 0`, scaled by `weight`
+    fn plane(n: [f64; 3], d: f64, weight: f64) -> Self {
+        let [a, b, c] = n;
+        Self([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|v| v * weight))
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (value, other) in self.0.iter_mut().zip(other.0) {
+            *value += other;
+        }
+    }
+
+    /// Error of a position
+    fn error(&self, p: [f64; 3]) -> f64 {
+        let q = &self.0;
+        let [x, y, z] = p;
+        q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9]
+    }
+
+    /// Position minimizing the error, if the quadric is well conditioned
+    fn minimizer(&self) -> Option<[f64; 3]> {
+        let q = &self.0;
+        let m = [[q[0], q[1], q[2]], [q[1], q[4], q[5]], [q[2], q[5], q[7]]];
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        let scale = m.iter().flatten().map(|v| v.abs()).fold(0.0, f64::max);
+        if det.abs() <= 1e-10 * scale * scale * scale {
+            return None;
+        }
+        let rhs = [-q[3], -q[6], -q[8]];
+        // Cramer's rule
+        let solve = |column: usize| {
+            let mut m = m;
+            for row in 0..3 {
+                m[row][column] = rhs[row];
+            }
+            (m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+                - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+                + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])) / det
+        };
+        Some([solve(0), solve(1), solve(2)])
+    }
+}
+
+/// Candidate edge collapse, ordered by increasing cost
+struct Collapse {
+    cost: f64,
+    edge: (usize, usize),
+    target: [f64; 3],
+    /// Vertex versions when the candidate was computed
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed for a min-heap, ties broken by edge for determinism
+        other.cost.total_cmp(&self.cost).then_with(|| other.edge.cmp(&self.edge))
+    }
+}
+
+/// Weight of the planes keeping boundary edges in place
+const BOUNDARY_WEIGHT: f64 = 1000.0;
+
+/// Edge-collapse state of a mesh being decimated
+struct Decimator {
+    positions: Vec<[f64; 3]>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    alive_vertices: Vec<bool>,
+    triangles: Vec<[usize; 3]>,
+    alive_triangles: Vec<bool>,
+    /// Triangles around each vertex, including dead ones
+    vertex_triangles: Vec<Vec<usize>>,
+}
+
+fn to_f64(p: Vec3f) -> [f64; 3] {
+    [p.x as f64, p.y as f64, p.z as f64]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(a: [f64; 3]) -> Option<[f64; 3]> {
+    let length = dot(a, a).sqrt();
+    (length > 0.0).then(|| a.map(|v| v / length))
+}
+
+impl Decimator {
+    fn new(mesh: &Mesh) -> Self {
+        let positions: Vec<[f64; 3]> = mesh.vertices.iter().map(|v| to_f64(v.position)).collect();
+        let triangles: Vec<[usize; 3]> = mesh.triangles.iter().map(|t| t.indices).collect();
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut vertex_triangles = vec![Vec::new(); positions.len()];
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (index, &[a, b, c]) in triangles.iter().enumerate() {
+            if let Some(n) = normalize(cross(sub(positions[b], positions[a]), sub(positions[c], positions[a]))) {
+                let plane = Quadric::plane(n, -dot(n, positions[a]), 1.0);
+                for v in [a, b, c] {
+                    quadrics[v].add(&plane);
+                }
+            }
+            for v in [a, b, c] {
+                vertex_triangles[v].push(index);
+            }
+            for (p, q) in [(a, b), (b, c), (c, a)] {
+                edges.entry((p.min(q), p.max(q))).or_default().push(index);
+            }
+        }
+
+        // Planes through boundary edges, perpendicular to their triangle
+        for (&(p, q), adjacent) in &edges {
+            if let [triangle] = adjacent.as_slice() {
+                let [a, b, c] = triangles[*triangle];
+                let face = cross(sub(positions[b], positions[a]), sub(positions[c], positions[a]));
+                let edge = sub(positions[q], positions[p]);
+                if let Some(n) = normalize(cross(edge, face)) {
+                    let plane = Quadric::plane(n, -dot(n, positions[p]), BOUNDARY_WEIGHT * dot(edge, edge));
+                    quadrics[p].add(&plane);
+                    quadrics[q].add(&plane);
+                }
+            }
+        }
+
+        Self {
+            alive_vertices: vec![true; positions.len()],
+            versions: vec![0; positions.len()],
+            alive_triangles: vec![true; triangles.len()],
+            positions,
+            quadrics,
+            triangles,
+            vertex_triangles,
+        }
+    }
+
+    /// Distinct live neighbours of a vertex
+    fn neighbours(&self, v: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self.vertex_triangles[v].iter()
+            .filter(|&&t| self.alive_triangles[t])
+            .flat_map(|&t| self.triangles[t])
+            .filter(|&n| n != v)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    /// Compute the best collapse of an edge
+    fn candidate(&self, u: usize, v: usize) -> Collapse {
+        let mut quadric = self.quadrics[u];
+        quadric.add(&self.quadrics[v]);
+        let (pu, pv) = (self.positions[u], self.positions[v]);
+        let midpoint = [(pu[0] + pv[0]) / 2.0, (pu[1] + pv[1]) / 2.0, (pu[2] + pv[2]) / 2.0];
+        let mut options = vec![pu, pv, midpoint];
+        if let Some(optimal) = quadric.minimizer() {
+            // Keep the optimum near the edge to avoid spikes in flat regions
+            let reach = dot(sub(pu, pv), sub(pu, pv));
+            if dot(sub(optimal, midpoint), sub(optimal, midpoint)) <= reach {
+                options.insert(0, optimal);
+            }
+        }
+        let (cost, target) = options.into_iter()
+            .map(|p| (quadric.error(p).max(0.0), p))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap();
+        Collapse { cost, edge: (u, v), target, versions: (self.versions[u], self.versions[v]) }
+    }
+
+    /// Check that collapsing `u` and `v` keeps the mesh manifold and does not flip triangles
+    fn can_collapse(&self, u: usize, v: usize, target: [f64; 3]) -> bool {
+        // Link condition: shared neighbours are exactly the vertices opposite the edge
+        let shared_triangles = self.vertex_triangles[u].iter()
+            .filter(|&&t| self.alive_triangles[t] && self.triangles[t].contains(&v))
+            .count();
+        let nu = self.neighbours(u);
+        let shared_neighbours = self.neighbours(v).iter().filter(|n| nu.binary_search(n).is_ok()).count();
+        if shared_triangles == 0 || shared_neighbours != shared_triangles {
+            return false;
+        }
+
+        for (moving, other) in [(u, v), (v, u)] {
+            for &t in &self.vertex_triangles[moving] {
+                let triangle = self.triangles[t];
+                if !self.alive_triangles[t] || triangle.contains(&other) {
+                    continue;
+                }
+                let corners = triangle.map(|i| self.positions[i]);
+                let moved = triangle.map(|i| if i == moving { target } else { self.positions[i] });
+                let before = cross(sub(corners[1], corners[0]), sub(corners[2], corners[0]));
+                let after = cross(sub(moved[1], moved[0]), sub(moved[2], moved[0]));
+                if dot(before, after) <= 0.0 {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Collapse `v` into `u` at `target`, returning the number of triangles removed
+    fn collapse(&mut self, u: usize, v: usize, target: [f64; 3]) -> usize {
+        let mut removed = 0;
+        for t in std::mem::take(&mut self.vertex_triangles[v]) {
+            if !self.alive_triangles[t] {
+                continue;
+            }
+            if self.triangles[t].contains(&u) {
+                self.alive_triangles[t] = false;
+                removed += 1;
+            } else {
+                for i in self.triangles[t].iter_mut() {
+                    if *i == v {
+                        *i = u;
+                    }
+                }
+                self.vertex_triangles[u].push(t);
+            }
+        }
+        let alive = &self.alive_triangles;
+        self.vertex_triangles[u].retain(|&t| alive[t]);
+        self.positions[u] = target;
+        let quadric = self.quadrics[v];
+        self.quadrics[u].add(&quadric);
+        self.alive_vertices[v] = false;
+        self.versions[u] += 1;
+        self.versions[v] += 1;
+        removed
+    }
+}
+
+impl Mesh {
+    /// Reduce the mesh to at most `target_triangles` triangles by quadric error edge collapses
+    ///
+    /// Edges are collapsed in order of increasing quadric error (Garland and
+    /// Heckbert), skipping collapses that would make the mesh non-manifold or
+    /// flip triangles, and boundary edges are held in place by penalty
+    /// planes. Decimation stops early if no valid collapse remains. Surviving
+    /// vertices keep their channel values and triangles their materials,
+    /// normals being recomputed for the moved vertices.
+    ///
+    /// # Returns
+    /// Number of triangles removed
+    pub fn decimate(&mut self, target_triangles: usize) -> usize {
+        let initial = self.triangles.len();
+        let mut decimator = Decimator::new(self);
+        let mut heap = BinaryHeap::new();
+        let mut edges: Vec<(usize, usize)> = decimator.triangles.iter()
+            .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+            .map(|(p, q)| (p.min(q), p.max(q)))
+            .filter(|(p, q)| p != q)
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        for (u, v) in edges {
+            heap.push(decimator.candidate(u, v));
+        }
+
+        let mut remaining = initial;
+        while remaining > target_triangles {
+            let Some(collapse) = heap.pop() else { break };
+            let (u, v) = collapse.edge;
+            if !decimator.alive_vertices[u] || !decimator.alive_vertices[v]
+                || collapse.versions != (decimator.versions[u], decimator.versions[v]) {
+                continue;
+            }
+            if !decimator.can_collapse(u, v, collapse.target) {
+                continue;
+            }
+            remaining -= decimator.collapse(u, v, collapse.target);
+            for n in decimator.neighbours(u) {
+                heap.push(decimator.candidate(u.min(n), u.max(n)));
+            }
+        }
+
+        // Write back positions and live triangles
+        for (vertex, position) in self.vertices.iter_mut().zip(&decimator.positions) {
+            vertex.position = Vec3f::new(position[0] as f32, position[1] as f32, position[2] as f32);
+        }
+        let has_materials = self.has_materials();
+        let mut triangles = Vec::with_capacity(remaining);
+        let mut materials = Vec::new();
+        for (index, triangle) in decimator.triangles.iter().enumerate() {
+            if decimator.alive_triangles[index] {
+                triangles.push(Triangle { indices: *triangle });
+                if has_materials {
+                    materials.push(self.triangle_materials[index]);
+                }
+            }
+        }
+        self.triangles = triangles;
+        self.triangle_materials = materials;
+        self.remove_unreferenced_vertices();
+        if self.has_normals() {
+            self.compute_vertex_normals();
+        }
+        initial - self.triangles.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{sphere_mesh, boundary_edge_count};
+    use test_log::test;
+
+    #[test]
+    fn test_decimate_sphere() {
+        let mut mesh = sphere_mesh();
+        mesh.compute_vertex_normals();
+        let initial = mesh.triangle_count();
+        assert!(initial > 2000);
+
+        let removed = mesh.decimate(500);
+        assert_eq!(removed, initial - mesh.triangle_count());
+        assert!(mesh.triangle_count() <= 500 && mesh.triangle_count() > 400);
+        assert_eq!(boundary_edge_count(&mesh), 0);
+        for vertex in &mesh.vertices {
+            assert!((vertex.position.length() - 1.0).abs() < 0.05);
+        }
+        let mut recomputed = mesh.clone();
+        recomputed.compute_vertex_normals();
+        assert_eq!(mesh.normals, recomputed.normals);
+    }
+}
@@ -0,0 +1,279 @@
+use super::Mesh;
+use super::obj::{material_color, material_name};
+use crate::math::Vec3f;
+use serde_json::{json, Value};
+use std::io::{Write, Error, ErrorKind, Result as IoResult};
+use std::path::Path;
+
+/// glTF component type for 32-bit floats
+const FLOAT: u32 = 5126;
+/// glTF component type for 32-bit unsigned integers
+const UNSIGNED_INT: u32 = 5125;
+/// Buffer view target for vertex attributes
+const ARRAY_BUFFER: u32 = 34962;
+/// Buffer view target for indices
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// glTF document and binary buffer under construction
+struct GltfWriter {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GltfWriter {
+    /// Append data as a new buffer view and accessor, returning the accessor index
+    fn add_accessor(&mut self, data: &[u8], target: u32, component_type: u32, count: usize, kind: &str, min_max: Option<(Vec<f32>, Vec<f32>)>) -> usize {
+        let offset = self.buffer.len();
+        self.buffer.extend_from_slice(data);
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": data.len(),
+            "target": target,
+        }));
+        let mut accessor = json!({
+            "bufferView": self.buffer_views.len() - 1,
+            "componentType": component_type,
+            "count": count,
+            "type": kind,
+        });
+        if let Some((min, max)) = min_max {
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+}
+
+fn floats_to_bytes(values: impl IntoIterator<Item = f32>) -> Vec<u8> {
+    values.into_iter().flat_map(f32::to_le_bytes).collect()
+}
+
+impl Mesh {
+    /// Build the glTF document and its binary buffer
+    ///
+    /// The buffer `uri` is left for the caller to set.
+    fn gltf_document(&self) -> IoResult<(Value, Vec<u8>)> {
+        // glTF has no empty accessors or buffer views
+        if self.triangles.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "cannot export a mesh without triangles to glTF"));
+        }
+        let mut writer = GltfWriter { buffer: Vec::new(), buffer_views: Vec::new(), accessors: Vec::new() };
+        let count = self.vertices.len();
+
+        // Shared vertex attributes
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for vertex in &self.vertices {
+            let p = [vertex.position.x, vertex.position.y, vertex.position.z];
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        let positions = floats_to_bytes(self.vertices.iter().flat_map(|v| [v.position.x, v.position.y, v.position.z]));
+        let position = writer.add_accessor(&positions, ARRAY_BUFFER, FLOAT, count, "VEC3", Some((min.to_vec(), max.to_vec())));
+        let mut attributes = json!({ "POSITION": position });
+        // Normals must be unit length, so they are left out if any is zero
+        let normals = self.normals_or_computed();
+        if normals.iter().all(|n| n.length() > 0.0) {
+            let normals = floats_to_bytes(normals.into_iter().map(Vec3f::normalize).flat_map(|n| [n.x, n.y, n.z]));
+            attributes["NORMAL"] = json!(writer.add_accessor(&normals, ARRAY_BUFFER, FLOAT, count, "VEC3", None));
+        }
+        if self.has_colors() {
+            let colors = floats_to_bytes(self.colors.iter().flatten().copied());
+            attributes["COLOR_0"] = json!(writer.add_accessor(&colors, ARRAY_BUFFER, FLOAT, count, "VEC4", None));
+        }
+
+        // One primitive per material
+        let groups: Vec<Option<u32>> = if self.has_materials() {
+            self.material_ids().into_iter().map(Some).collect()
+        } else {
+            vec![None]
+        };
+        let mut primitives = Vec::new();
+        let mut materials = Vec::new();
+        for group in groups {
+            let indices: Vec<u8> = self.triangles.iter()
+                .enumerate()
+                .filter(|(index, _)| group.is_none() || self.triangle_material(*index) == group)
+                .flat_map(|(_, triangle)| triangle.indices)
+                .flat_map(|i| (i as u32).to_le_bytes())
+                .collect();
+            let accessor = writer.add_accessor(&indices, ELEMENT_ARRAY_BUFFER, UNSIGNED_INT, indices.len() / 4, "SCALAR", None);
+            let mut primitive = json!({ "attributes": attributes, "indices": accessor, "mode": 4 });
+            if let Some(material) = group {
+                let [r, g, b] = material_color(material);
+                materials.push(json!({
+                    "name": material_name(material),
+                    "pbrMetallicRoughness": { "baseColorFactor": [r, g, b, 1.0], "metallicFactor": 0.0 },
+                }));
+                primitive["material"] = json!(materials.len() - 1);
+            }
+            primitives.push(primitive);
+        }
+
+        let mut document = json!({
+            "asset": { "version": "2.0", "generator": "yanvox" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "mesh": 0 }],
+            "meshes": [{ "name": "yanvox_mesh", "primitives": primitives }],
+            "buffers": [{ "byteLength": writer.buffer.len() }],
+            "bufferViews": writer.buffer_views,
+            "accessors": writer.accessors,
+        });
+        if !materials.is_empty() {
+            document["materials"] = json!(materials);
+        }
+        Ok((document, writer.buffer))
+    }
+
+    /// Export mesh to glTF 2.0 JSON with the binary buffer embedded as a data URI
+    ///
+    /// Positions, normals (the vertex normals, or area-weighted normals if
+    /// the mesh has none) and colours are shared by all primitives; each
+    /// material gets its own indexed primitive. Normals are left out if any
+    /// vertex has a zero normal, and meshes without triangles are rejected
+    /// with `InvalidInput`.
+    pub fn export_gltf<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        let (mut document, buffer) = self.gltf_document()?;
+        document["buffers"][0]["uri"] = json!(format!("data:application/octet-stream;base64,{}", base64_encode(&buffer)));
+        serde_json::to_writer(&mut *writer, &document)?;
+        Ok(())
+    }
+
+    /// Export mesh to binary glTF (GLB)
+    ///
+    /// See [`Mesh::export_gltf`] for the content written.
+    pub fn export_glb<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        let (document, mut buffer) = self.gltf_document()?;
+        let mut json = serde_json::to_vec(&document)?;
+        // Chunks are 4-byte aligned, JSON padded with spaces and binary with zeros
+        json.resize(json.len().next_multiple_of(4), b' ');
+        buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+        let length = 12 + 8 + json.len() + 8 + buffer.len();
+        writer.write_all(b"glTF")?;
+        writer.write_all(&2u32.to_le_bytes())?;
+        writer.write_all(&(length as u32).to_le_bytes())?;
+        writer.write_all(&(json.len() as u32).to_le_bytes())?;
+        writer.write_all(b"JSON")?;
+        writer.write_all(&json)?;
+        writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+        writer.write_all(b"BIN\0")?;
+        writer.write_all(&buffer)
+    }
+
+    /// Export mesh to a glTF file with an embedded buffer
+    pub fn export_gltf_file<P: AsRef<Path>>(&self, path: P) -> IoResult<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.export_gltf(&mut file)?;
+        file.flush()
+    }
+
+    /// Export mesh to a GLB file
+    pub fn export_glb_file<P: AsRef<Path>>(&self, path: P) -> IoResult<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.export_glb(&mut file)?;
+        file.flush()
+    }
+}
+
+/// Standard base64 encoding with padding
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for k in 0..4 {
+            if k <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * k) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh_generation::{Vertex, Triangle};
+    use test_log::test;
+
+    fn quad() -> Mesh {
+        let mut mesh = Mesh::new();
+        for p in [(0.0, 0.0, 0.0), (2.0, 0.0, 0.0), (2.0, 1.0, 0.0), (0.0, 1.0, -1.0)] {
+            mesh.add_vertex(Vertex { position: Vec3f::new(p.0, p.1, p.2) });
+        }
+        mesh.add_triangle_with_material(Triangle { indices: [0, 1, 2] }, 3);
+        mesh.add_triangle_with_material(Triangle { indices: [0, 2, 3] }, 5);
+        mesh
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_export_glb() {
+        let mut glb = Vec::new();
+        quad().export_glb(&mut glb).unwrap();
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize, glb.len());
+        assert_eq!(glb.len() % 4, 0);
+
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        let document: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        let primitives = document["meshes"][0]["primitives"].as_array().unwrap();
+        assert_eq!(primitives.len(), 2);
+        assert_eq!(document["materials"].as_array().unwrap().len(), 2);
+
+        let position = &document["accessors"][primitives[0]["attributes"]["POSITION"].as_u64().unwrap() as usize];
+        assert_eq!(position["min"], json!([0.0, 0.0, -1.0]));
+        assert_eq!(position["max"], json!([2.0, 1.0, 0.0]));
+        let indices = &document["accessors"][primitives[1]["indices"].as_u64().unwrap() as usize];
+        assert_eq!(indices["count"], json!(3));
+    }
+
+    #[test]
+    fn test_export_gltf() {
+        let mut mesh = quad();
+        mesh.triangle_materials.clear();
+        mesh.set_vertex_color(0, [1.0, 0.0, 0.0, 1.0]);
+        let mut buffer = Vec::new();
+        mesh.export_gltf(&mut buffer).unwrap();
+        let document: Value = serde_json::from_slice(&buffer).unwrap();
+        let primitives = document["meshes"][0]["primitives"].as_array().unwrap();
+        assert_eq!(primitives.len(), 1);
+        assert!(primitives[0]["attributes"]["COLOR_0"].is_u64());
+        assert!(document["buffers"][0]["uri"].as_str().unwrap().starts_with("data:application/octet-stream;base64,"));
+        // 4 positions, 4 normals, 4 colours and 6 indices
+        assert_eq!(document["buffers"][0]["byteLength"], json!(4 * 12 + 4 * 12 + 4 * 16 + 6 * 4));
+    }
+
+    #[test]
+    fn test_export_invalid_meshes() {
+        let mut buffer = Vec::new();
+        let error = Mesh::new().export_glb(&mut buffer).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+
+        // An unreferenced vertex has no normal
+        let mut mesh = quad();
+        mesh.add_vertex(Vertex { position: Vec3f::new(5.0, 5.0, 5.0) });
+        mesh.export_gltf(&mut buffer).unwrap();
+        let document: Value = serde_json::from_slice(&buffer).unwrap();
+        let attributes = &document["meshes"][0]["primitives"][0]["attributes"];
+        assert!(attributes["POSITION"].is_u64());
+        assert!(attributes.get("NORMAL").is_none());
+    }
+}
@@ -0,0 +1,120 @@
+use super::Mesh;
+use crate::math::{Vec3f, Bounds3f, MassProperties};
+
+fn to_f64(p: Vec3f) -> [f64; 3] {
+    [p.x as f64, p.y as f64, p.z as f64]
+}
+
+impl Mesh {
+    /// Get the axis-aligned bounds of the vertices
+    pub fn bounds(&self) -> Bounds3f {
+        self.vertices.iter().fold(Bounds3f::empty(), |bounds, v| bounds.expand(v.position))
+    }
+
+    /// Get the total area of the triangles
+    pub fn surface_area(&self) -> f32 {
+        self.triangles.iter()
+            .map(|triangle| {
+                let [p0, p1, p2] = triangle.indices.map(|i| self.vertices[i].position);
+                0.5 * (p1 - p0).cross(&(p2 - p0)).length() as f64
+            })
+            .sum::<f64>() as f32
+    }
+
+    /// Get the signed volume enclosed by the mesh
+    ///
+    /// Positive for closed, outward-facing meshes and negative if they face inwards.
+    pub fn volume(&self) -> f32 {
+        self.mass_properties().volume
+    }
+
+    /// Get the centre of mass of the enclosed solid
+    pub fn centroid(&self) -> Vec3f {
+        self.mass_properties().centroid
+    }
+
+    /// Get the inertia tensor of the enclosed solid about its centroid, at unit density
+    pub fn inertia_tensor(&self) -> [[f32; 3]; 3] {
+        self.mass_properties().inertia
+    }
+
+    /// Compute the surface area and mass properties of the enclosed solid
+    ///
+    /// Volume integrals are summed over the tetrahedra formed by each
+    /// triangle and the origin (divergence theorem), so the mesh must be
+    /// closed and consistently oriented for the results to be meaningful.
+    pub fn mass_properties(&self) -> MassProperties {
+        let mut volume = 0.0;
+        let mut first = [0.0; 3];
+        let mut second = [[0.0; 3]; 3];
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.indices.map(|i| to_f64(self.vertices[i].position));
+            let det = a[0] * (b[1] * c[2] - b[2] * c[1])
+                - a[1] * (b[0] * c[2] - b[2] * c[0])
+                + a[2] * (b[0] * c[1] - b[1] * c[0]);
+            volume += det / 6.0;
+            let sum = [a[0] + b[0] + c[0], a[1] + b[1] + c[1], a[2] + b[2] + c[2]];
+            for i in 0..3 {
+                first[i] += det / 24.0 * sum[i];
+                for j in 0..3 {
+                    let outer = a[i] * a[j] + b[i] * b[j] + c[i] * c[j] + sum[i] * sum[j];
+                    second[i][j] += det / 120.0 * outer;
+                }
+            }
+        }
+        MassProperties::from_moments(self.surface_area() as f64, volume, first, second)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh_generation::{Mesh, Vertex, Triangle};
+    use crate::math::Vec3f;
+    use super::super::tests::sphere_mesh;
+    use crate::voxel_data::tests::sphere_volume;
+    use test_log::test;
+
+    #[test]
+    fn test_unit_cube() {
+        let mut mesh = Mesh::new();
+        for i in 0..8 {
+            let p = Vec3f::new((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32);
+            mesh.add_vertex(Vertex { position: p + Vec3f::new(1.0, 2.0, 3.0) });
+        }
+        for indices in [
+            [0, 2, 1], [1, 2, 3], [4, 5, 6], [5, 7, 6], [0, 1, 4], [1, 5, 4],
+            [2, 6, 3], [3, 6, 7], [0, 4, 2], [2, 4, 6], [1, 3, 5], [3, 7, 5],
+        ] {
+            mesh.add_triangle(Triangle { indices });
+        }
+        let properties = mesh.mass_properties();
+        assert!((properties.surface_area - 6.0).abs() < 1e-5);
+        assert!((properties.volume - 1.0).abs() < 1e-5);
+        assert!((properties.centroid - Vec3f::new(1.5, 2.5, 3.5)).length() < 1e-5);
+        for i in 0..3 {
+            for j in 0..3 {
+                let expected = if i == j { 1.0 / 6.0 } else { 0.0 };
+                assert!((properties.inertia[i][j] - expected).abs() < 1e-4);
+            }
+        }
+        assert_eq!(mesh.bounds().min, Vec3f::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn test_sphere_mesh_matches_field() {
+        let mesh = sphere_mesh();
+        let from_mesh = mesh.mass_properties();
+        let from_field = sphere_volume(Vec3f::zero(), 1.0).estimate_mass_properties(0.0);
+
+        let area = 4.0 * std::f32::consts::PI;
+        let volume = area / 3.0;
+        let inertia = 0.4 * volume;
+        for properties in [from_mesh, from_field] {
+            assert!((properties.surface_area - area).abs() < 0.03 * area, "{:?}", properties);
+            assert!((properties.volume - volume).abs() < 0.03 * volume, "{:?}", properties);
+            assert!(properties.centroid.length() < 0.01);
+            assert!((properties.inertia[0][0] - inertia).abs() < 0.05 * inertia);
+            assert!(properties.inertia[0][1].abs() < 0.01 * inertia);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use super::{Mesh, Triangle, VertexWelder};
+use crate::math::Vec3f;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, Write, Error, ErrorKind, Result as IoResult};
+use std::path::Path;
+
+impl Mesh {
+    /// Export mesh to Wavefront OBJ format
+    ///
+    /// Vertex colours are written as the widely supported `v x y z r g b`
+    /// extension. With `normals`, the vertex normals (or area-weighted
+    /// normals if the mesh has none) are written as `vn` records. When the
+    /// mesh carries materials, faces are grouped by `usemtl material_<id>`
+    /// and `mtllib` names the material library to reference.
+    pub fn export_obj<W: Write>(&self, writer: &mut W, normals: bool, mtllib: Option<&str>) -> IoResult<()> {
+        writeln!(writer, "# yanvox_mesh")?;
+        if let Some(mtllib) = mtllib {
+            writeln!(writer, "mtllib {}", mtllib)?;
+        }
+
+        for (index, vertex) in self.vertices.iter().enumerate() {
+            let p = vertex.position;
+            match self.vertex_color(index) {
+                Some([r, g, b, _]) => writeln!(writer, "v {} {} {} {} {} {}", p.x, p.y, p.z, r, g, b)?,
+                None => writeln!(writer, "v {} {} {}", p.x, p.y, p.z)?,
+            }
+        }
+        if normals {
+            for n in self.normals_or_computed() {
+                writeln!(writer, "vn {} {} {}", n.x, n.y, n.z)?;
+            }
+        }
+
+        let write_face = |writer: &mut W, index: usize| -> IoResult<()> {
+            let [a, b, c] = self.triangles[index].indices.map(|i| i + 1);
+            if normals {
+                writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")
+            } else {
+                writeln!(writer, "f {a} {b} {c}")
+            }
+        };
+        if self.has_materials() {
+            for material in self.material_ids() {
+                writeln!(writer, "usemtl {}", material_name(material))?;
+                for index in 0..self.triangles.len() {
+                    if self.triangle_materials[index] == material {
+                        write_face(writer, index)?;
+                    }
+                }
+            }
+        } else {
+            for index in 0..self.triangles.len() {
+                write_face(writer, index)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Export the materials of the mesh as a Wavefront MTL library
+    ///
+    /// Each material gets a distinct diffuse colour.
+    pub fn export_mtl<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        for material in self.material_ids() {
+            let [r, g, b] = material_color(material);
+            writeln!(writer, "newmtl {}", material_name(material))?;
+            writeln!(writer, "Kd {} {} {}", r, g, b)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Export mesh to an OBJ file
+    ///
+    /// If the mesh carries materials, an MTL file with the same name and the
+    /// `mtl` extension is written next to it and referenced from the OBJ file.
+    pub fn export_obj_file<P: AsRef<Path>>(&self, path: P, normals: bool) -> IoResult<()> {
+        let path = path.as_ref();
+        let mut mtllib = None;
+        if self.has_materials() {
+            let mtl_path = path.with_extension("mtl");
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&mtl_path)?);
+            self.export_mtl(&mut file)?;
+            file.flush()?;
+            mtllib = mtl_path.file_name().map(|name| name.to_string_lossy().into_owned());
+        }
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.export_obj(&mut file, normals, mtllib.as_deref())?;
+        file.flush()
+    }
+
+    /// Read a mesh from Wavefront OBJ data
+    ///
+    /// Vertex positions, `v x y z r g b` vertex colours and faces are read;
+    /// polygons are fan-triangulated and coincident vertices welded. Faces
+    /// following `usemtl` get a material: names of the form `material_<id>`
+    /// map to `<id>` if all names follow that form, otherwise materials are
+    /// numbered in order of first use. Normals and texture coordinates are
+    /// ignored.
+    pub fn read_obj<R: BufRead>(reader: &mut R) -> IoResult<Mesh> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+        let mut mesh = Mesh::new();
+        let mut welder = VertexWelder::default();
+        // Welded index of each OBJ vertex
+        let mut indices: Vec<usize> = Vec::new();
+        let mut colors: Vec<Option<[f32; 4]>> = Vec::new();
+        let mut material_names: Vec<String> = Vec::new();
+        let mut current_material = None;
+        let mut faces: Vec<([usize; 3], Option<usize>)> = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let values = words
+                        .map(|w| w.parse::<f32>().map_err(|_| invalid(format!("invalid OBJ vertex `{}`", line))))
+                        .collect::<IoResult<Vec<f32>>>()?;
+                    if values.len() < 3 {
+                        return Err(invalid(format!("invalid OBJ vertex `{}`", line)));
+                    }
+                    let index = welder.weld(&mut mesh, Vec3f::new(values[0], values[1], values[2]));
+                    indices.push(index);
+                    if colors.len() <= index {
+                        colors.resize(index + 1, None);
+                        if values.len() >= 6 {
+                            colors[index] = Some([values[3], values[4], values[5], 1.0]);
+                        }
+                    }
+                }
+                Some("f") => {
+                    let polygon = words
+                        .map(|w| {
+                            let index: i64 = w.split('/').next().unwrap_or("").parse()
+                                .map_err(|_| invalid(format!("invalid OBJ face `{}`", line)))?;
+                            // Indices are 1-based, negative ones relative to the end
+                            let index = if index < 0 { indices.len() as i64 + index } else { index - 1 };
+                            indices.get(index as usize).copied()
+                                .ok_or_else(|| invalid(format!("OBJ face references missing vertex in `{}`", line)))
+                        })
+                        .collect::<IoResult<Vec<usize>>>()?;
+                    for k in 1..polygon.len().saturating_sub(1) {
+                        faces.push(([polygon[0], polygon[k], polygon[k + 1]], current_material));
+                    }
+                }
+                Some("usemtl") => {
+                    let name = words.collect::<Vec<_>>().join(" ");
+                    current_material = Some(match material_names.iter().position(|n| *n == name) {
+                        Some(index) => index,
+                        None => {
+                            material_names.push(name);
+                            material_names.len() - 1
+                        }
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let numbered: Option<Vec<u32>> = material_names.iter()
+            .map(|name| name.strip_prefix("material_").and_then(|id| id.parse().ok()))
+            .collect();
+        let material_ids = numbered.unwrap_or_else(|| (0..material_names.len() as u32).collect());
+        let ids: HashMap<usize, u32> = material_ids.into_iter().enumerate().collect();
+        for (indices, material) in faces {
+            match material {
+                Some(material) => mesh.add_triangle_with_material(Triangle { indices }, ids[&material]),
+                None => mesh.add_triangle(Triangle { indices }),
+            }
+        }
+        if colors.iter().any(Option::is_some) {
+            mesh.colors = colors.into_iter().map(|c| c.unwrap_or([1.0; 4])).collect();
+        }
+        Ok(mesh)
+    }
+
+    /// Read a mesh from an OBJ file
+    pub fn read_obj_file<P: AsRef<Path>>(path: P) -> IoResult<Mesh> {
+        Self::read_obj(&mut std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    /// Distinct material IDs in ascending order
+    pub(crate) fn material_ids(&self) -> BTreeSet<u32> {
+        self.triangle_materials.iter().copied().collect()
+    }
+}
+
+/// Name of a material in exported files
+pub(crate) fn material_name(material: u32) -> String {
+    format!("material_{}", material)
+}
+
+/// Distinct display colour for a material, spreading hues by the golden ratio
+pub(crate) fn material_color(material: u32) -> [f32; 3] {
+    let hue = (material as f32 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let [r, g, b] = match hue as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    };
+    // Soften to pastel shades
+    [0.3 + 0.6 * r, 0.3 + 0.6 * g, 0.3 + 0.6 * b]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh_generation::{Mesh, Triangle};
+    use super::super::tests::two_triangles;
+    use test_log::test;
+
+    #[test]
+    fn test_export_obj() {
+        let mesh = two_triangles();
+        let mut buffer = Vec::new();
+        mesh.export_obj(&mut buffer, true, None).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().filter(|l| l.starts_with("v ")).count(), 4);
+        assert_eq!(text.lines().filter(|l| l.starts_with("vn 0 0 1")).count(), 4);
+        assert!(text.contains("f 1//1 2//2 3//3"));
+        assert!(!text.contains("usemtl"));
+    }
+
+    #[test]
+    fn test_export_obj_file_with_materials() {
+        let mut mesh = Mesh::new();
+        let other = two_triangles();
+        mesh.append(&other);
+        mesh.add_triangle_with_material(Triangle { indices: [1, 2, 3] }, 7);
+
+        let dir = std::env::temp_dir().join(format!("yanvox_obj_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mesh.obj");
+        mesh.export_file(&path).unwrap();
+
+        let obj = std::fs::read_to_string(&path).unwrap();
+        let mtl = std::fs::read_to_string(dir.join("mesh.mtl")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(obj.contains("mtllib mesh.mtl"));
+        assert!(obj.contains("usemtl material_0"));
+        assert!(obj.contains("usemtl material_7"));
+        assert!(mtl.contains("newmtl material_7"));
+        assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), 3);
+
+        assert!(mesh.export_file(dir.join("mesh.xyz")).is_err());
+    }
+
+    #[test]
+    fn test_read_obj() {
+        let mut mesh = two_triangles();
+        mesh.set_vertex_color(2, [0.0, 0.5, 1.0, 1.0]);
+        mesh.triangle_materials = vec![4, 2];
+        let mut buffer = Vec::new();
+        mesh.export_obj(&mut buffer, true, None).unwrap();
+        let read = Mesh::read_obj(&mut buffer.as_slice()).unwrap();
+        assert_eq!(read.vertices, mesh.vertices);
+        assert_eq!(read.colors, mesh.colors);
+        // Faces are grouped by material on export
+        assert_eq!(read.triangles, vec![mesh.triangles[1], mesh.triangles[0]]);
+        assert_eq!(read.triangle_materials, vec![2, 4]);
+
+        // Duplicated vertices are welded and polygons triangulated
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 0\nusemtl red\nf 5/1/1 2/2/2 3/3/3 -2\n";
+        let read = Mesh::read_obj(&mut obj.as_bytes()).unwrap();
+        assert_eq!(read.vertex_count(), 4);
+        assert_eq!(read.triangles, vec![Triangle { indices: [0, 1, 2] }, Triangle { indices: [0, 2, 3] }]);
+        assert_eq!(read.triangle_materials, vec![0, 0]);
+        assert!(Mesh::read_obj(&mut "f 1 2 3\n".as_bytes()).is_err());
+    }
+}
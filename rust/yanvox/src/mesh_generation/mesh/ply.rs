@@ -0,0 +1,608 @@
+use super::{Mesh, Vertex, Triangle, VertexAttribute};
+use crate::math::Vec3f;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Read, Write, Error, ErrorKind, Result as IoResult};
+use std::path::Path;
+
+/// Encoding of the body of a PLY file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Scalar type of a PLY property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> IoResult<Self> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(invalid(format!("unknown PLY type `{}`", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// Read a binary value
+    fn read<R: Read>(self, reader: &mut R, format: Format) -> IoResult<f64> {
+        let mut bytes = [0u8; 8];
+        let bytes = &mut bytes[..self.size()];
+        reader.read_exact(bytes).map_err(|error| match error.kind() {
+            ErrorKind::UnexpectedEof => invalid("unexpected end of PLY data"),
+            _ => error,
+        })?;
+        if format == Format::BinaryBigEndian {
+            bytes.reverse();
+        }
+        Ok(match self {
+            Scalar::I8 => bytes[0] as i8 as f64,
+            Scalar::U8 => bytes[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::U32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::F32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::F64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+        })
+    }
+}
+
+/// A property of a PLY element
+#[derive(Debug, Clone)]
+struct Property {
+    name: String,
+    /// Count type for list properties
+    list: Option<Scalar>,
+    scalar: Scalar,
+}
+
+/// An element declared in a PLY header
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+/// Convert a value read as a list length or vertex index, rejecting
+/// negative, fractional and out-of-range values
+fn to_index(value: f64, limit: usize, what: &str) -> IoResult<usize> {
+    if value >= 0.0 && value.fract() == 0.0 && value < limit as f64 {
+        Ok(value as usize)
+    } else {
+        Err(invalid(format!("invalid PLY {} {}", what, value)))
+    }
+}
+
+/// Largest list length accepted in a row
+const MAX_LIST_LENGTH: usize = 1 << 16;
+
+/// Vertex property names used for the mesh channels
+const POSITION: [&str; 3] = ["x", "y", "z"];
+const NORMAL: [&str; 3] = ["nx", "ny", "nz"];
+const COLOR: [&str; 4] = ["red", "green", "blue", "alpha"];
+
+/// Layout of the vertex properties written for a mesh
+struct VertexLayout {
+    /// Attribute property names with the channel and component they come from
+    attributes: Vec<(String, String, usize)>,
+}
+
+impl VertexLayout {
+    fn new(mesh: &Mesh) -> Self {
+        let mut attributes = Vec::new();
+        for (name, attribute) in &mesh.attributes {
+            if attribute.components == 1 {
+                attributes.push((name.clone(), name.clone(), 0));
+            } else {
+                for component in 0..attribute.components {
+                    attributes.push((format!("{}_{}", name, component), name.clone(), component));
+                }
+            }
+        }
+        Self { attributes }
+    }
+}
+
+impl Mesh {
+    /// Write the PLY header for this mesh
+    fn write_ply_header<W: Write>(&self, writer: &mut W, format: &str, layout: &VertexLayout) -> IoResult<()> {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format {} 1.0", format)?;
+        writeln!(writer, "comment yanvox_mesh")?;
+        writeln!(writer, "element vertex {}", self.vertices.len())?;
+        for name in POSITION {
+            writeln!(writer, "property float {}", name)?;
+        }
+        if self.has_normals() {
+            for name in NORMAL {
+                writeln!(writer, "property float {}", name)?;
+            }
+        }
+        if self.has_colors() {
+            for name in COLOR {
+                writeln!(writer, "property uchar {}", name)?;
+            }
+        }
+        for (name, _, _) in &layout.attributes {
+            writeln!(writer, "property float {}", name)?;
+        }
+        writeln!(writer, "element face {}", self.triangles.len())?;
+        writeln!(writer, "property list uchar int vertex_indices")?;
+        if self.has_materials() {
+            writeln!(writer, "property uint material")?;
+        }
+        writeln!(writer, "end_header")
+    }
+
+    /// Float values of a vertex in header order, colours excluded
+    fn ply_vertex_floats(&self, index: usize, layout: &VertexLayout) -> Vec<f32> {
+        let p = self.vertices[index].position;
+        let mut values = vec![p.x, p.y, p.z];
+        if let Some(n) = self.vertex_normal(index) {
+            values.extend([n.x, n.y, n.z]);
+        }
+        for (_, channel, component) in &layout.attributes {
+            values.push(self.attributes[channel].get(index)[*component]);
+        }
+        values
+    }
+
+    /// Export mesh to ASCII PLY format
+    ///
+    /// Normals, colours, named attributes and materials are written as
+    /// vertex and face properties. Multi-component attributes are split into
+    /// `<name>_<component>` properties.
+    pub fn export_ply_ascii<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        let layout = VertexLayout::new(self);
+        self.write_ply_header(writer, "ascii", &layout)?;
+        for index in 0..self.vertices.len() {
+            let floats = self.ply_vertex_floats(index, &layout);
+            let (geometry, attributes) = floats.split_at(if self.has_normals() { 6 } else { 3 });
+            let mut fields: Vec<String> = geometry.iter().map(f32::to_string).collect();
+            if let Some(color) = self.vertex_color(index) {
+                fields.extend(color.map(|c| color_to_byte(c).to_string()));
+            }
+            fields.extend(attributes.iter().map(f32::to_string));
+            writeln!(writer, "{}", fields.join(" "))?;
+        }
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            let [a, b, c] = triangle.indices;
+            match self.triangle_material(index) {
+                Some(material) => writeln!(writer, "3 {} {} {} {}", a, b, c, material)?,
+                None => writeln!(writer, "3 {} {} {}", a, b, c)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Export mesh to binary little-endian PLY format
+    ///
+    /// See [`Mesh::export_ply_ascii`] for the properties written.
+    pub fn export_ply_binary<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        let layout = VertexLayout::new(self);
+        self.write_ply_header(writer, "binary_little_endian", &layout)?;
+        for index in 0..self.vertices.len() {
+            let floats = self.ply_vertex_floats(index, &layout);
+            let (geometry, attributes) = floats.split_at(if self.has_normals() { 6 } else { 3 });
+            for value in geometry {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            if let Some(color) = self.vertex_color(index) {
+                writer.write_all(&color.map(color_to_byte))?;
+            }
+            for value in attributes {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            writer.write_all(&[3u8])?;
+            for i in triangle.indices {
+                writer.write_all(&(i as i32).to_le_bytes())?;
+            }
+            if let Some(material) = self.triangle_material(index) {
+                writer.write_all(&material.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Export mesh to a binary PLY file
+    pub fn export_ply_file<P: AsRef<Path>>(&self, path: P) -> IoResult<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.export_ply_binary(&mut file)?;
+        file.flush()
+    }
+
+    /// Read a mesh from ASCII or binary PLY data
+    ///
+    /// Vertex positions, normals (`nx`, `ny`, `nz`), colours (`red`,
+    /// `green`, `blue`, optional `alpha`, as bytes or floats) and any other
+    /// vertex properties as named attributes are read, with
+    /// `<name>_0`..`<name>_n` properties merged into one channel. Polygons
+    /// are fan-triangulated and a `material` face property is read as the
+    /// triangle material. Other elements are skipped.
+    pub fn read_ply<R: BufRead>(reader: &mut R) -> IoResult<Mesh> {
+        let (format, elements) = read_ply_header(reader)?;
+        let mut mesh = Mesh::new();
+        for element in &elements {
+            // The header count is untrusted, so nothing is preallocated from it
+            let mut row = Row::default();
+            match element.name.as_str() {
+                "vertex" => {
+                    let columns = VertexColumns::new(element)?;
+                    let mut attributes = columns.attributes.iter()
+                        .map(|(_, columns)| VertexAttribute { components: columns.len(), values: Vec::new() })
+                        .collect::<Vec<_>>();
+                    for _ in 0..element.count {
+                        row.read(reader, element, format, None)?;
+                        columns.push(&mut mesh, &mut attributes, &row.scalars);
+                    }
+                    for ((name, _), attribute) in columns.attributes.into_iter().zip(attributes) {
+                        mesh.attributes.insert(name, attribute);
+                    }
+                }
+                "face" => {
+                    let indices = element.properties.iter()
+                        .position(|p| p.list.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index"))
+                        .ok_or_else(|| invalid("PLY faces have no vertex_indices property"))?;
+                    let material = element.properties.iter().position(|p| p.list.is_none() && p.name == "material");
+                    for _ in 0..element.count {
+                        row.read(reader, element, format, Some(indices))?;
+                        push_face(&mut mesh, &row, material)?;
+                    }
+                }
+                _ => {
+                    for _ in 0..element.count {
+                        row.read(reader, element, format, None)?;
+                    }
+                }
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Read a mesh from a PLY file
+    pub fn read_ply_file<P: AsRef<Path>>(path: P) -> IoResult<Mesh> {
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        Self::read_ply(&mut reader)
+    }
+}
+
+fn color_to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Read a header line, without the line terminator
+fn read_header_line<R: BufRead>(reader: &mut R) -> IoResult<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(invalid("unexpected end of PLY header"));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn read_ply_header<R: BufRead>(reader: &mut R) -> IoResult<(Format, Vec<Element>)> {
+    if read_header_line(reader)?.trim() != "ply" {
+        return Err(invalid("missing PLY magic number"));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        let line = read_header_line(reader)?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => break,
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid(format!("unknown PLY format `{}`", name))),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid(format!("invalid element count `{}`", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, scalar, name] => elements.last_mut()
+                .ok_or_else(|| invalid("property before element"))?
+                .properties.push(Property {
+                    name: name.to_string(),
+                    list: Some(Scalar::parse(count)?),
+                    scalar: Scalar::parse(scalar)?,
+                }),
+            ["property", scalar, name] => elements.last_mut()
+                .ok_or_else(|| invalid("property before element"))?
+                .properties.push(Property { name: name.to_string(), list: None, scalar: Scalar::parse(scalar)? }),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid(format!("invalid PLY header line `{}`", line))),
+        }
+    }
+    let format = format.ok_or_else(|| invalid("missing PLY format"))?;
+    Ok((format, elements))
+}
+
+/// Values of one element row, with buffers reused from row to row
+///
+/// Scalar properties are kept by property index, list properties holding
+/// their length. The items of a single list property are kept.
+#[derive(Default)]
+struct Row {
+    scalars: Vec<f64>,
+    list: Vec<f64>,
+    /// Values of the current ASCII line not read yet, last first
+    tokens: Vec<f64>,
+}
+
+impl Row {
+    /// Read the next row, keeping the items of the list property at index `list`
+    fn read<R: BufRead>(&mut self, reader: &mut R, element: &Element, format: Format, list: Option<usize>) -> IoResult<()> {
+        self.scalars.clear();
+        self.list.clear();
+        for (index, property) in element.properties.iter().enumerate() {
+            let Some(count) = property.list else {
+                let value = self.next(reader, property.scalar, format)?;
+                self.scalars.push(value);
+                continue;
+            };
+            let count = to_index(self.next(reader, count, format)?, MAX_LIST_LENGTH, "list length")?;
+            self.scalars.push(count as f64);
+            for _ in 0..count {
+                let value = self.next(reader, property.scalar, format)?;
+                if list == Some(index) {
+                    self.list.push(value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn next<R: BufRead>(&mut self, reader: &mut R, scalar: Scalar, format: Format) -> IoResult<f64> {
+        if format != Format::Ascii {
+            return scalar.read(reader, format);
+        }
+        while self.tokens.is_empty() {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid("unexpected end of PLY data"));
+            }
+            for token in line.split_whitespace().rev() {
+                self.tokens.push(token.parse().map_err(|_| invalid(format!("invalid PLY value `{}`", token)))?);
+            }
+        }
+        Ok(self.tokens.pop().unwrap())
+    }
+}
+
+/// Vertex property indices of the mesh channels
+struct VertexColumns {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    /// Colour properties, with the scale bringing each to 0..1
+    color: Option<[(usize, f64); 3]>,
+    alpha: Option<(usize, f64)>,
+    /// Remaining scalar properties as named attributes, merging `<name>_<i>` runs
+    attributes: Vec<(String, Vec<usize>)>,
+}
+
+impl VertexColumns {
+    fn new(element: &Element) -> IoResult<Self> {
+        let properties = &element.properties;
+        let find = |name: &str| properties.iter().position(|p| p.list.is_none() && p.name == name);
+        let [Some(x), Some(y), Some(z)] = POSITION.map(find) else {
+            return Err(invalid("PLY vertices have no x, y, z properties"));
+        };
+        let mut used = vec![x, y, z];
+        let normal = match NORMAL.map(find) {
+            [Some(nx), Some(ny), Some(nz)] => Some([nx, ny, nz]),
+            _ => None,
+        };
+        used.extend(normal.into_iter().flatten());
+        let scale = |index: usize| match properties[index].scalar {
+            Scalar::F32 | Scalar::F64 => 1.0,
+            Scalar::U16 => 1.0 / 65535.0,
+            _ => 1.0 / 255.0,
+        };
+        let (color, alpha) = match COLOR.map(find) {
+            [Some(r), Some(g), Some(b), alpha] => {
+                used.extend([r, g, b]);
+                used.extend(alpha);
+                (Some([r, g, b].map(|index| (index, scale(index)))), alpha.map(|a| (a, scale(a))))
+            }
+            _ => (None, None),
+        };
+
+        let mut attributes = BTreeMap::new();
+        let mut index = 0;
+        while index < properties.len() {
+            if used.contains(&index) || properties[index].list.is_some() {
+                index += 1;
+                continue;
+            }
+            let name = &properties[index].name;
+            let mut run = vec![index];
+            if let Some(base) = name.strip_suffix("_0") {
+                while let Some(next) = properties.get(index + run.len()) {
+                    if next.list.is_none() && next.name == format!("{}_{}", base, run.len()) {
+                        run.push(index + run.len());
+                    } else {
+                        break;
+                    }
+                }
+                if run.len() > 1 {
+                    index += run.len();
+                    attributes.insert(base.to_string(), run);
+                    continue;
+                }
+            }
+            attributes.insert(name.clone(), run);
+            index += 1;
+        }
+        Ok(Self { position: [x, y, z], normal, color, alpha, attributes: attributes.into_iter().collect() })
+    }
+
+    /// Append the vertex of a row to the mesh and the attribute channels
+    fn push(&self, mesh: &mut Mesh, attributes: &mut [VertexAttribute], scalars: &[f64]) {
+        let vector = |[x, y, z]: [usize; 3]| Vec3f::new(scalars[x] as f32, scalars[y] as f32, scalars[z] as f32);
+        mesh.vertices.push(Vertex { position: vector(self.position) });
+        if let Some(normal) = self.normal {
+            mesh.normals.push(vector(normal));
+        }
+        if let Some(color) = self.color {
+            let channel = |(index, scale): (usize, f64)| (scalars[index] * scale) as f32;
+            let [r, g, b] = color.map(channel);
+            mesh.colors.push([r, g, b, self.alpha.map_or(1.0, channel)]);
+        }
+        for ((_, columns), attribute) in self.attributes.iter().zip(attributes) {
+            attribute.values.extend(columns.iter().map(|&column| scalars[column] as f32));
+        }
+    }
+}
+
+/// Fan-triangulate the polygon of a face row
+fn push_face(mesh: &mut Mesh, row: &Row, material: Option<usize>) -> IoResult<()> {
+    let polygon: Vec<usize> = row.list.iter()
+        .map(|&i| to_index(i, mesh.vertices.len(), "face vertex index"))
+        .collect::<IoResult<_>>()?;
+    for k in 1..polygon.len().saturating_sub(1) {
+        let triangle = Triangle { indices: [polygon[0], polygon[k], polygon[k + 1]] };
+        match material {
+            Some(material) => mesh.add_triangle_with_material(triangle, row.scalars[material] as u32),
+            None => mesh.add_triangle(triangle),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh_generation::Mesh;
+    use super::super::tests::tetrahedron;
+    use std::io::ErrorKind;
+    use test_log::test;
+
+    fn stl(mesh: &Mesh) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        mesh.export_stl_binary(&mut buffer).unwrap();
+        buffer
+    }
+
+    fn round_trip(mesh: &Mesh) -> [Mesh; 2] {
+        let mut ascii = Vec::new();
+        mesh.export_ply_ascii(&mut ascii).unwrap();
+        let mut binary = Vec::new();
+        mesh.export_ply_binary(&mut binary).unwrap();
+        [
+            Mesh::read_ply(&mut ascii.as_slice()).unwrap(),
+            Mesh::read_ply(&mut binary.as_slice()).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip_matches_stl() {
+        let mesh = tetrahedron();
+        for read in round_trip(&mesh) {
+            assert_eq!(stl(&read), stl(&mesh));
+            assert!(!read.has_normals() && !read.has_colors() && !read.has_materials());
+        }
+    }
+
+    #[test]
+    fn test_round_trip_attributes() {
+        let mut mesh = tetrahedron();
+        mesh.compute_vertex_normals();
+        for index in 0..mesh.vertex_count() {
+            mesh.set_vertex_color(index, [index as f32 / 3.0, 0.0, 1.0, 1.0]);
+            mesh.set_vertex_attribute("uv", index, &[index as f32, 0.5]);
+            mesh.set_vertex_attribute("weight", index, &[index as f32 * 0.25]);
+        }
+        mesh.triangle_materials = vec![0, 1, 1, 4];
+
+        for read in round_trip(&mesh) {
+            assert_eq!(stl(&read), stl(&mesh));
+            assert_eq!(read.normals, mesh.normals);
+            assert_eq!(read.attributes, mesh.attributes);
+            assert_eq!(read.triangle_materials, mesh.triangle_materials);
+            for (read, written) in read.colors.iter().zip(&mesh.colors) {
+                for (r, w) in read.iter().zip(written) {
+                    assert!((r - w).abs() <= 0.5 / 255.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_polygons() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+                   element face 1\nproperty list uchar int vertex_index\nend_header\n\
+                   0 0 0\n1 0 0\n1 1 0\n0 1 0\n4 0 1 2 3\n";
+        let mesh = Mesh::read_ply(&mut ply.as_bytes()).unwrap();
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.triangle_count(), 2);
+
+        let truncated = &ply[..ply.len() - 4];
+        assert!(Mesh::read_ply(&mut truncated.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_skip_other_elements() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+                   property float weight\nelement edge 1\nproperty list uchar int vertex_index\nproperty uchar crease\n\
+                   element face 1\nproperty uchar material\nproperty list uchar int vertex_indices\nend_header\n\
+                   0 0 0 0.5\n1 0 0 1\n0 1\n0 2\n2 0 1 7\n2 3 0 1 2\n";
+        let mesh = Mesh::read_ply(&mut ply.as_bytes()).unwrap();
+        assert_eq!(mesh.vertex_count(), 3);
+        assert_eq!(mesh.attributes["weight"].values, vec![0.5, 1.0, 2.0]);
+        assert_eq!(mesh.triangle_count(), 1);
+        assert_eq!(mesh.triangle_materials, vec![2]);
+    }
+
+    #[test]
+    fn test_reject_invalid_data() {
+        let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+                      element face 1\nproperty list uchar int vertex_index\nend_header\n0 0 0\n1 0 0\n0 1 0\n";
+        for face in ["3 0 1 -1\n", "3 0 1 3\n", "3 0 1 1.5\n", "-3 0 1 2\n"] {
+            let ply = format!("{}{}", header, face);
+            let error = Mesh::read_ply(&mut ply.as_bytes()).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{}", face);
+        }
+
+        // A huge element count fails at the end of the data instead of allocating
+        let ply = "ply\nformat binary_little_endian 1.0\nelement vertex 4000000000000000000\n\
+                   property float x\nproperty float y\nproperty float z\nend_header\n\0\0\0\0";
+        let error = Mesh::read_ply(&mut ply.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,119 @@
+use super::Mesh;
+use crate::math::Vec3f;
+use std::collections::HashMap;
+
+impl Mesh {
+    /// Smooth the mesh by moving each vertex towards the average of its neighbours
+    ///
+    /// Each iteration moves vertices by `lambda` (typically 0.5) of the way
+    /// to their neighbour average. Boundary vertices are kept in place.
+    /// Repeated Laplacian smoothing shrinks the mesh; prefer
+    /// [`Mesh::smooth_taubin`] to preserve its shape.
+    pub fn smooth_laplacian(&mut self, iterations: usize, lambda: f32) {
+        let neighbours = self.vertex_neighbours();
+        for _ in 0..iterations {
+            self.laplacian_step(&neighbours, lambda);
+        }
+        self.refresh_normals();
+    }
+
+    /// Smooth the mesh with Taubin's lambda/mu filter
+    ///
+    /// Each iteration is a shrinking Laplacian step of `lambda` followed by
+    /// an inflating step of `mu`, with `mu < -lambda < 0` (typically 0.5 and
+    /// -0.53), which removes noise without shrinking the shape. Boundary
+    /// vertices are kept in place.
+    pub fn smooth_taubin(&mut self, iterations: usize, lambda: f32, mu: f32) {
+        let neighbours = self.vertex_neighbours();
+        for _ in 0..iterations {
+            self.laplacian_step(&neighbours, lambda);
+            self.laplacian_step(&neighbours, mu);
+        }
+        self.refresh_normals();
+    }
+
+    /// Move every interior vertex by `factor` towards its neighbour average
+    fn laplacian_step(&mut self, neighbours: &[Option<Vec<usize>>], factor: f32) {
+        let positions: Vec<Vec3f> = self.vertices.iter().map(|v| v.position).collect();
+        for (vertex, neighbours) in self.vertices.iter_mut().zip(neighbours) {
+            let Some(neighbours) = neighbours else { continue };
+            if neighbours.is_empty() {
+                continue;
+            }
+            let sum = neighbours.iter().fold(Vec3f::zero(), |sum, &n| sum + positions[n]);
+            let average = sum.scale(1.0 / neighbours.len() as f32);
+            vertex.position = vertex.position + (average - vertex.position).scale(factor);
+        }
+    }
+
+    /// Neighbours of every vertex, or `None` for boundary vertices
+    fn vertex_neighbours(&self) -> Vec<Option<Vec<usize>>> {
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.indices;
+            for (p, q) in [(a, b), (b, c), (c, a)] {
+                *edges.entry((p.min(q), p.max(q))).or_default() += 1;
+            }
+        }
+        let mut neighbours: Vec<Option<Vec<usize>>> = vec![Some(Vec::new()); self.vertices.len()];
+        let mut boundary = vec![false; self.vertices.len()];
+        let mut edges: Vec<((usize, usize), usize)> = edges.into_iter().collect();
+        edges.sort_unstable();
+        for ((p, q), count) in edges {
+            if count == 1 {
+                boundary[p] = true;
+                boundary[q] = true;
+            }
+            for (from, to) in [(p, q), (q, p)] {
+                if let Some(list) = &mut neighbours[from] {
+                    list.push(to);
+                }
+            }
+        }
+        for (neighbours, boundary) in neighbours.iter_mut().zip(boundary) {
+            if boundary {
+                *neighbours = None;
+            }
+        }
+        neighbours
+    }
+
+    /// Recompute vertex normals after the geometry changed, if the mesh has normals
+    fn refresh_normals(&mut self) {
+        if self.has_normals() {
+            self.compute_vertex_normals();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh_generation::Mesh;
+    use super::super::tests::noisy_sphere;
+    use test_log::test;
+
+    fn radius_stats(mesh: &Mesh) -> (f32, f32) {
+        let radii: Vec<f32> = mesh.vertices.iter().map(|v| v.position.length()).collect();
+        let mean = radii.iter().sum::<f32>() / radii.len() as f32;
+        let variance = radii.iter().map(|r| (r - mean) * (r - mean)).sum::<f32>() / radii.len() as f32;
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn test_taubin_does_not_shrink() {
+        let noisy = noisy_sphere();
+        let (_, noise) = radius_stats(&noisy);
+
+        let mut taubin = noisy.clone();
+        taubin.smooth_taubin(10, 0.5, -0.53);
+        let (taubin_mean, taubin_noise) = radius_stats(&taubin);
+
+        let mut laplacian = noisy.clone();
+        laplacian.smooth_laplacian(10, 0.5);
+        let (laplacian_mean, _) = radius_stats(&laplacian);
+
+        assert!(taubin_noise < noise * 0.5);
+        assert!((taubin_mean - 1.0).abs() < 0.01);
+        assert!(laplacian_mean < taubin_mean - 0.01);
+    }
+}
@@ -0,0 +1,118 @@
+use super::{Mesh, Triangle, VertexWelder};
+use crate::math::Vec3f;
+use std::io::{Read, Error, ErrorKind, Result as IoResult};
+use std::path::Path;
+
+/// Size of the binary STL header
+const HEADER_SIZE: usize = 80;
+/// Size of a binary STL facet record
+const FACET_SIZE: usize = 50;
+
+impl Mesh {
+    /// Read a mesh from ASCII or binary STL data
+    ///
+    /// The encoding is detected from the data: binary files are recognised
+    /// by being large enough for the facet count in the header, since binary
+    /// headers may also start with `solid` and some writers append trailing
+    /// bytes. Coincident vertices are welded and facet normals are ignored.
+    pub fn read_stl<R: Read>(reader: &mut R) -> IoResult<Mesh> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if data.len() >= HEADER_SIZE + 4 {
+            let count = u32::from_le_bytes(data[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap()) as usize;
+            let size = count.checked_mul(FACET_SIZE).and_then(|facets| facets.checked_add(HEADER_SIZE + 4));
+            if size.is_some_and(|size| data.len() >= size) {
+                return Ok(Self::read_stl_binary(&data[HEADER_SIZE + 4..], count));
+            }
+        }
+        if data.trim_ascii_start().starts_with(b"solid") {
+            let text = std::str::from_utf8(&data).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            return Self::read_stl_ascii(text);
+        }
+        Err(Error::new(ErrorKind::InvalidData, "data is neither ASCII nor binary STL"))
+    }
+
+    /// Read a mesh from an STL file
+    pub fn read_stl_file<P: AsRef<Path>>(path: P) -> IoResult<Mesh> {
+        Self::read_stl(&mut std::fs::File::open(path)?)
+    }
+
+    fn read_stl_binary(facets: &[u8], count: usize) -> Mesh {
+        let mut mesh = Mesh::new();
+        let mut welder = VertexWelder::default();
+        let float = |bytes: &[u8], index: usize| f32::from_le_bytes(bytes[index * 4..index * 4 + 4].try_into().unwrap());
+        for facet in facets.chunks_exact(FACET_SIZE).take(count) {
+            // Skip the 12-byte normal
+            let indices = [3, 6, 9].map(|offset| {
+                let position = Vec3f::new(float(facet, offset), float(facet, offset + 1), float(facet, offset + 2));
+                welder.weld(&mut mesh, position)
+            });
+            mesh.add_triangle(Triangle { indices });
+        }
+        mesh
+    }
+
+    fn read_stl_ascii(text: &str) -> IoResult<Mesh> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+        let mut mesh = Mesh::new();
+        let mut welder = VertexWelder::default();
+        let mut facet = Vec::with_capacity(3);
+        for line in text.lines() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["vertex", x, y, z] => {
+                    let parse = |value: &str| value.parse::<f32>().map_err(|_| invalid(format!("invalid STL coordinate `{}`", value)));
+                    let position = Vec3f::new(parse(x)?, parse(y)?, parse(z)?);
+                    facet.push(welder.weld(&mut mesh, position));
+                }
+                ["endloop"] => {
+                    // Fan-triangulate facets with more than three vertices
+                    for k in 1..facet.len().saturating_sub(1) {
+                        mesh.add_triangle(Triangle { indices: [facet[0], facet[k], facet[k + 1]] });
+                    }
+                    facet.clear();
+                }
+                ["vertex", ..] => return Err(invalid(format!("invalid STL vertex line `{}`", line.trim()))),
+                _ => {}
+            }
+        }
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh_generation::Mesh;
+    use super::super::tests::tetrahedron;
+    use test_log::test;
+
+    #[test]
+    fn test_read_stl_welds_vertices() {
+        let mesh = tetrahedron();
+        let mut binary = Vec::new();
+        mesh.export_stl_binary(&mut binary).unwrap();
+        let mut ascii = Vec::new();
+        mesh.export_stl_ascii(&mut ascii).unwrap();
+
+        for data in [&binary, &ascii] {
+            let read = Mesh::read_stl(&mut data.as_slice()).unwrap();
+            assert_eq!(read.vertex_count(), mesh.vertex_count());
+            let mut written = Vec::new();
+            read.export_stl_binary(&mut written).unwrap();
+            assert_eq!(written, binary);
+        }
+        assert!(Mesh::read_stl(&mut &b"not an stl"[..]).is_err());
+    }
+
+    #[test]
+    fn test_binary_header_starting_with_solid() {
+        let mut binary = Vec::new();
+        tetrahedron().export_stl_binary(&mut binary).unwrap();
+        binary[..5].copy_from_slice(b"solid");
+        assert_eq!(Mesh::read_stl(&mut binary.as_slice()).unwrap().triangle_count(), 4);
+
+        // Trailing bytes after the facets are ignored
+        binary.extend_from_slice(b"\nendsolid\n");
+        assert_eq!(Mesh::read_stl(&mut binary.as_slice()).unwrap().triangle_count(), 4);
+    }
+}
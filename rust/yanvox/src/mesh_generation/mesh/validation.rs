@@ -0,0 +1,403 @@
+use super::{Mesh, Triangle};
+use crate::disjoint_set::DisjointSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Topology and quality report of a mesh
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MeshReport {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    /// Vertices not used by any triangle
+    pub unreferenced_vertices: usize,
+    /// Edges used by a single triangle
+    pub boundary_edges: usize,
+    /// Edges shared by more than two triangles
+    pub non_manifold_edges: usize,
+    /// Vertices whose triangles form several fans not connected through edges
+    pub non_manifold_vertices: usize,
+    /// Triangles with repeated vertices or zero area
+    pub degenerate_triangles: usize,
+    /// Triangles using the same vertices as an earlier triangle
+    pub duplicate_triangles: usize,
+    /// Edges shared by two triangles traversing it in the same direction
+    pub inconsistent_edges: usize,
+    /// Groups of triangles connected through shared edges
+    pub components: usize,
+}
+
+impl MeshReport {
+    /// Check if every edge is shared by exactly two triangles
+    pub fn is_watertight(&self) -> bool {
+        self.boundary_edges == 0 && self.non_manifold_edges == 0
+    }
+
+    /// Check if the mesh is a valid, consistently oriented closed surface
+    pub fn is_printable(&self) -> bool {
+        self.is_watertight()
+            && self.triangle_count > 0
+            && self.non_manifold_vertices == 0
+            && self.degenerate_triangles == 0
+            && self.duplicate_triangles == 0
+            && self.inconsistent_edges == 0
+    }
+}
+
+/// Undirected edge key
+fn edge_key(p: usize, q: usize) -> (usize, usize) {
+    (p.min(q), p.max(q))
+}
+
+impl Mesh {
+    /// Triangles using each undirected edge, in triangle order
+    ///
+    /// Triangles with repeated vertices are left out.
+    fn edge_triangles(&self) -> HashMap<(usize, usize), Vec<usize>> {
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            let [a, b, c] = triangle.indices;
+            if a == b || b == c || c == a {
+                continue;
+            }
+            for (p, q) in [(a, b), (b, c), (c, a)] {
+                edges.entry(edge_key(p, q)).or_default().push(index);
+            }
+        }
+        edges
+    }
+
+    /// Check if a triangle traverses the edge from `p` to `q`
+    fn has_directed_edge(&self, triangle: usize, p: usize, q: usize) -> bool {
+        let [a, b, c] = self.triangles[triangle].indices;
+        [(a, b), (b, c), (c, a)].contains(&(p, q))
+    }
+
+    /// Check if a triangle has repeated vertices or zero area
+    fn is_degenerate(&self, triangle: &Triangle) -> bool {
+        let [a, b, c] = triangle.indices;
+        if a == b || b == c || c == a {
+            return true;
+        }
+        let [p0, p1, p2] = triangle.indices.map(|i| self.vertices[i].position);
+        (p1 - p0).cross(&(p2 - p0)).length() == 0.0
+    }
+
+    /// Component index of every triangle, triangles being connected through shared edges
+    fn triangle_components(&self) -> (Vec<usize>, usize) {
+        let mut set = DisjointSet::new(self.triangles.len());
+        for triangles in self.edge_triangles().values() {
+            for pair in triangles.windows(2) {
+                set.union(pair[0], pair[1]);
+            }
+        }
+        let mut labels = HashMap::new();
+        let components = (0..self.triangles.len())
+            .map(|t| {
+                let root = set.find(t);
+                let next = labels.len();
+                *labels.entry(root).or_insert(next)
+            })
+            .collect();
+        (components, labels.len())
+    }
+
+    /// Analyze the topology and quality of the mesh
+    pub fn analyze(&self) -> MeshReport {
+        let edges = self.edge_triangles();
+        let mut report = MeshReport {
+            vertex_count: self.vertices.len(),
+            triangle_count: self.triangles.len(),
+            ..MeshReport::default()
+        };
+
+        let mut referenced = vec![false; self.vertices.len()];
+        let mut seen = HashSet::new();
+        for triangle in &self.triangles {
+            for &i in &triangle.indices {
+                referenced[i] = true;
+            }
+            if self.is_degenerate(triangle) {
+                report.degenerate_triangles += 1;
+            }
+            let mut sorted = triangle.indices;
+            sorted.sort_unstable();
+            if !seen.insert(sorted) {
+                report.duplicate_triangles += 1;
+            }
+        }
+        report.unreferenced_vertices = referenced.iter().filter(|&&r| !r).count();
+
+        for (&(p, q), triangles) in &edges {
+            match triangles.len() {
+                1 => report.boundary_edges += 1,
+                2 => {
+                    if self.has_directed_edge(triangles[0], p, q) == self.has_directed_edge(triangles[1], p, q) {
+                        report.inconsistent_edges += 1;
+                    }
+                }
+                _ => report.non_manifold_edges += 1,
+            }
+        }
+
+        // A manifold vertex has a single fan of triangles connected through its
+        // edges, triangles with repeated vertices having no edges of their own
+        let mut vertex_triangles: Vec<Vec<usize>> = vec![Vec::new(); self.vertices.len()];
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            let [a, b, c] = triangle.indices;
+            if a == b || b == c || c == a {
+                continue;
+            }
+            for &i in &triangle.indices {
+                vertex_triangles[i].push(index);
+            }
+        }
+        for (vertex, triangles) in vertex_triangles.iter().enumerate() {
+            if triangles.len() < 2 {
+                continue;
+            }
+            let mut set = DisjointSet::new(triangles.len());
+            for (i, &t) in triangles.iter().enumerate() {
+                for &n in &self.triangles[t].indices {
+                    if n == vertex {
+                        continue;
+                    }
+                    for &other in edges.get(&edge_key(vertex, n)).into_iter().flatten() {
+                        if let Some(j) = triangles.iter().position(|&u| u == other) {
+                            set.union(i, j);
+                        }
+                    }
+                }
+            }
+            if (0..triangles.len()).any(|i| set.find(i) != 0) {
+                report.non_manifold_vertices += 1;
+            }
+        }
+
+        report.components = self.triangle_components().1;
+        report
+    }
+
+    /// Remove triangles with repeated vertices or zero area
+    ///
+    /// # Returns
+    /// Number of triangles removed
+    pub fn remove_degenerate_triangles(&mut self) -> usize {
+        let keep: Vec<bool> = self.triangles.iter().map(|t| !self.is_degenerate(t)).collect();
+        self.retain_triangles(&keep)
+    }
+
+    /// Remove triangles using the same vertices as an earlier triangle
+    ///
+    /// # Returns
+    /// Number of triangles removed
+    pub fn remove_duplicate_triangles(&mut self) -> usize {
+        let mut seen = HashSet::new();
+        let keep: Vec<bool> = self.triangles.iter()
+            .map(|t| {
+                let mut sorted = t.indices;
+                sorted.sort_unstable();
+                seen.insert(sorted)
+            })
+            .collect();
+        self.retain_triangles(&keep)
+    }
+
+    /// Remove connected components with fewer than `min_triangles` triangles
+    ///
+    /// # Returns
+    /// Number of triangles removed
+    pub fn remove_small_components(&mut self, min_triangles: usize) -> usize {
+        let (components, count) = self.triangle_components();
+        let mut sizes = vec![0; count];
+        for &c in &components {
+            sizes[c] += 1;
+        }
+        let keep: Vec<bool> = components.iter().map(|&c| sizes[c] >= min_triangles).collect();
+        let removed = self.retain_triangles(&keep);
+        self.remove_unreferenced_vertices();
+        removed
+    }
+
+    /// Orient triangles consistently within each connected component
+    ///
+    /// Triangles are flipped to agree with the first triangle of their
+    /// component across manifold edges; closed components are then turned
+    /// outwards so that they enclose a positive volume.
+    ///
+    /// # Returns
+    /// Number of triangles flipped
+    pub fn fix_winding(&mut self) -> usize {
+        let edges = self.edge_triangles();
+        let mut flipped = vec![false; self.triangles.len()];
+        let mut visited = vec![false; self.triangles.len()];
+        let mut queue = VecDeque::new();
+        for seed in 0..self.triangles.len() {
+            if visited[seed] {
+                continue;
+            }
+            visited[seed] = true;
+            queue.push_back(seed);
+            let mut component = Vec::new();
+            let mut closed = true;
+            while let Some(t) = queue.pop_front() {
+                component.push(t);
+                let [a, b, c] = self.triangles[t].indices;
+                for (p, q) in [(a, b), (b, c), (c, a)] {
+                    let Some(neighbours) = edges.get(&edge_key(p, q)) else { continue };
+                    if neighbours.len() != 2 {
+                        closed = false;
+                        continue;
+                    }
+                    let other = if neighbours[0] == t { neighbours[1] } else { neighbours[0] };
+                    if visited[other] {
+                        continue;
+                    }
+                    visited[other] = true;
+                    // Once oriented, `t` runs p -> q and a consistent neighbour q -> p
+                    let t_forward = self.has_directed_edge(t, p, q) != flipped[t];
+                    let other_forward = self.has_directed_edge(other, p, q);
+                    flipped[other] = other_forward == t_forward;
+                    queue.push_back(other);
+                }
+            }
+
+            if closed {
+                let volume: f32 = component.iter()
+                    .map(|&t| {
+                        let mut indices = self.triangles[t].indices;
+                        if flipped[t] {
+                            indices.swap(1, 2);
+                        }
+                        let [p0, p1, p2] = indices.map(|i| self.vertices[i].position);
+                        p0.dot(&p1.cross(&p2))
+                    })
+                    .sum();
+                if volume < 0.0 {
+                    for &t in &component {
+                        flipped[t] = !flipped[t];
+                    }
+                }
+            }
+        }
+
+        for (triangle, &flip) in self.triangles.iter_mut().zip(&flipped) {
+            if flip {
+                triangle.indices.swap(1, 2);
+            }
+        }
+        if self.has_normals() {
+            self.compute_vertex_normals();
+        }
+        flipped.iter().filter(|&&f| f).count()
+    }
+
+    /// Apply the basic repairs and report on the result
+    ///
+    /// Removes degenerate and duplicate triangles and unreferenced vertices,
+    /// then fixes the winding.
+    pub fn repair(&mut self) -> MeshReport {
+        self.remove_degenerate_triangles();
+        self.remove_duplicate_triangles();
+        self.remove_unreferenced_vertices();
+        self.fix_winding();
+        self.analyze()
+    }
+
+    /// Keep the triangles flagged in `keep`, with their materials
+    fn retain_triangles(&mut self, keep: &[bool]) -> usize {
+        let before = self.triangles.len();
+        let mut flags = keep.iter();
+        self.triangles.retain(|_| *flags.next().unwrap());
+        if self.has_materials() {
+            let mut flags = keep.iter();
+            self.triangle_materials.retain(|_| *flags.next().unwrap());
+        }
+        before - self.triangles.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::sphere_mesh;
+    use crate::mesh_generation::Vertex;
+    use crate::math::Vec3f;
+    use test_log::test;
+
+    #[test]
+    fn test_sphere_is_printable() {
+        let mut mesh = sphere_mesh();
+        // Marching cubes may produce zero-area triangles at cell corners
+        mesh.weld_vertices(1e-6);
+        let report = mesh.analyze();
+        assert!(report.is_printable(), "{:?}", report);
+        assert_eq!(report.components, 1);
+    }
+
+    #[test]
+    fn test_fix_winding() {
+        let mut mesh = sphere_mesh();
+        mesh.weld_vertices(1e-6);
+        for triangle in mesh.triangles.iter_mut().step_by(7) {
+            triangle.indices.swap(0, 1);
+        }
+        let flipped = mesh.triangles.len().div_ceil(7);
+        assert!(mesh.analyze().inconsistent_edges > 0);
+        assert_eq!(mesh.fix_winding(), flipped);
+        assert!(mesh.analyze().is_printable());
+
+        // Fully inverted meshes are turned outwards
+        for triangle in &mut mesh.triangles {
+            triangle.indices.swap(0, 1);
+        }
+        assert_eq!(mesh.analyze().inconsistent_edges, 0);
+        assert_eq!(mesh.fix_winding(), mesh.triangle_count());
+    }
+
+    #[test]
+    fn test_defects_and_repairs() {
+        let mut mesh = sphere_mesh();
+        mesh.weld_vertices(1e-6);
+        let sphere_triangles = mesh.triangle_count();
+
+        // A separate shell with a duplicate, a non-manifold edge and a degenerate triangle
+        let base = mesh.vertex_count();
+        for p in [(3.0, 0.0, 0.0), (4.0, 0.0, 0.0), (3.0, 1.0, 0.0), (3.0, 0.0, 1.0)] {
+            mesh.add_vertex(Vertex { position: Vec3f::new(p.0, p.1, p.2) });
+        }
+        mesh.add_triangle(Triangle { indices: [base, base + 1, base + 2] });
+        mesh.add_triangle(Triangle { indices: [base, base + 1, base + 2] });
+        mesh.add_triangle(Triangle { indices: [base, base + 1, base + 3] });
+        mesh.add_triangle(Triangle { indices: [base, base, base + 3] });
+
+        let report = mesh.analyze();
+        assert_eq!(report.duplicate_triangles, 1);
+        assert_eq!(report.degenerate_triangles, 1);
+        assert_eq!(report.non_manifold_edges, 1);
+        // The degenerate triangle shares no edge with the others
+        assert_eq!(report.components, 3);
+        assert!(!report.is_printable());
+
+        assert_eq!(mesh.remove_degenerate_triangles(), 1);
+        assert_eq!(mesh.remove_duplicate_triangles(), 1);
+        assert_eq!(mesh.remove_small_components(10), 2);
+        assert_eq!(mesh.triangle_count(), sphere_triangles);
+        let report = mesh.repair();
+        assert!(report.is_printable());
+        assert_eq!(report.unreferenced_vertices, 0);
+    }
+
+    #[test]
+    fn test_isolated_degenerate_triangle() {
+        let mut mesh = Mesh::new();
+        for p in [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0)] {
+            mesh.add_vertex(Vertex { position: Vec3f::new(p.0, p.1, p.2) });
+        }
+        mesh.add_triangle(Triangle { indices: [0, 1, 2] });
+        mesh.add_triangle(Triangle { indices: [0, 0, 3] });
+
+        let report = mesh.analyze();
+        assert_eq!(report.degenerate_triangles, 1);
+        assert_eq!(report.non_manifold_vertices, 0);
+        assert_eq!(report.boundary_edges, 3);
+    }
+}
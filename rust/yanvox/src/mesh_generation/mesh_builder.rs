@@ -1,6 +1,9 @@
-use crate::voxel::{VoxelVolume, SignedDistance};
-use super::mesh::Mesh;
-use super::algorithm::MarchingCubesAlgorithm;
+use crate::voxel::{VoxelVolume, VoxelData, SignedDistance};
+use crate::math::{Bounds3f, Vec3f};
+use super::mesh::{Mesh, VertexAttribute};
+use super::algorithm::{AlgorithmError, MarchingCubesAlgorithm, Mesher, VolumeView};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use thiserror::Error;
 
 /// Error types for mesh building
@@ -11,23 +14,56 @@ pub enum MeshBuilderError {
     #[error("Invalid iso level: {0}")]
     InvalidIsoLevel(f32),
     #[error("Mesh generation failed: {0}")]
-    GenerationFailed(String),
+    GenerationFailed(#[from] AlgorithmError),
+    #[error("Attribute `{name}` has {found} components for some voxels instead of {expected}")]
+    InconsistentAttribute { name: String, expected: usize, found: usize },
 }
 
-/// Builder for creating meshes from voxel volumes using marching cubes
-pub struct MeshBuilder<'a, T: SignedDistance> {
+/// Function extracting a value from a voxel
+type VoxelFn<'a, T, V> = Box<dyn Fn(&T) -> V + 'a>;
+
+/// Builder for creating meshes from voxel volumes
+///
+/// Uses marching cubes by default; any `Mesher` can be supplied instead,
+/// such as [`MarchingCubesAlgorithm::parallel`] to mesh on several threads.
+///
+/// Voxel attributes such as colours can be carried onto the mesh: each
+/// vertex receives the trilinear blend of the values of the active voxels
+/// around it, which for vertices on iso-crossing edges is the interpolation
+/// between the two edge voxels. Vertices with no active voxel around them
+/// blend all of them.
+pub struct MeshBuilder<'a, T: VoxelData> {
     voxel_volume: &'a VoxelVolume<T>,
     iso_level: Option<f32>,
-    algorithm: MarchingCubesAlgorithm,
+    algorithm: Box<dyn Mesher<T> + 'a>,
+    cancel_flag: Option<Arc<AtomicBool>>,
+    bounds: Option<Bounds3f>,
+    vertex_normals: bool,
+    colors: Option<VoxelFn<'a, T, [f32; 4]>>,
+    attributes: Vec<(String, VoxelFn<'a, T, Vec<f32>>)>,
+    material: Option<VoxelFn<'a, T, u32>>,
 }
 
 impl<'a, T: SignedDistance + Clone + 'static> MeshBuilder<'a, T> {
-    /// Create a new mesh builder with a voxel volume
+    /// Create a new mesh builder with a voxel volume, using marching cubes
     pub fn new(voxel_volume: &'a VoxelVolume<T>) -> Self {
+        Self::from_mesher(voxel_volume, MarchingCubesAlgorithm::new())
+    }
+}
+
+impl<'a, T: VoxelData + Clone + 'static> MeshBuilder<'a, T> {
+    /// Create a new mesh builder with a voxel volume and a meshing algorithm
+    pub fn from_mesher(voxel_volume: &'a VoxelVolume<T>, mesher: impl Mesher<T> + 'a) -> Self {
         Self {
             voxel_volume,
             iso_level: None,
-            algorithm: MarchingCubesAlgorithm::new(),
+            algorithm: Box::new(mesher),
+            cancel_flag: None,
+            bounds: None,
+            vertex_normals: false,
+            colors: None,
+            attributes: Vec::new(),
+            material: None,
         }
     }
 
@@ -37,17 +73,430 @@ impl<'a, T: SignedDistance + Clone + 'static> MeshBuilder<'a, T> {
         self
     }
 
+    /// Replace the meshing algorithm
+    pub fn with_mesher(mut self, mesher: impl Mesher<T> + 'a) -> Self {
+        self.algorithm = Box::new(mesher);
+        self
+    }
+
+    /// Set a flag that cancels the build when set to `true` from another thread
+    pub fn with_cancel_flag(mut self, cancel_flag: Arc<AtomicBool>) -> Self {
+        self.cancel_flag = Some(cancel_flag);
+        self
+    }
+
+    /// Only mesh the part of the volume inside world-space bounds
+    pub fn with_bounds(mut self, bounds: Bounds3f) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// Ensure the mesh carries per-vertex normals
+    ///
+    /// Normals produced by the algorithm are kept; otherwise they are
+    /// computed as area-weighted averages of the face normals.
+    pub fn with_vertex_normals(mut self) -> Self {
+        self.vertex_normals = true;
+        self
+    }
+
+    /// Carry a voxel colour onto per-vertex colours
+    pub fn with_colors(mut self, color: impl Fn(&T) -> [f32; 4] + 'a) -> Self {
+        self.colors = Some(Box::new(color));
+        self
+    }
+
+    /// Carry a voxel attribute onto a named per-vertex attribute channel
+    ///
+    /// The function must return the same number of components for every
+    /// voxel, including the background, or the build fails.
+    pub fn with_attribute(mut self, name: &str, attribute: impl Fn(&T) -> Vec<f32> + 'a) -> Self {
+        self.attributes.push((name.to_string(), Box::new(attribute)));
+        self
+    }
+
+    /// Assign per-triangle materials from a voxel material ID
+    ///
+    /// Materials cannot be blended: each vertex takes the material of its
+    /// most heavily weighted active voxel and each triangle the material shared by
+    /// most of its vertices, or that of its first vertex if they all differ.
+    pub fn with_material(mut self, material: impl Fn(&T) -> u32 + 'a) -> Self {
+        self.material = Some(Box::new(material));
+        self
+    }
+
+    /// Voxels around a mesh vertex with their trilinear weights
+    ///
+    /// Weights are renormalised over the active voxels so that the
+    /// background does not bleed into the surface.
+    fn vertex_voxels(&self, position: Vec3f) -> Vec<(&T, f32)> {
+        let weights: Vec<_> = self.voxel_volume.trilinear_weights(position)
+            .into_iter()
+            .filter(|&(_, weight)| weight > 0.0)
+            .map(|(coord, weight)| (self.voxel_volume.get_voxel(coord), weight))
+            .collect();
+        let active: Vec<_> = weights.iter().filter(|(voxel, _)| voxel.is_active()).copied().collect();
+        let weights = if active.is_empty() { weights } else { active };
+        let total: f32 = weights.iter().map(|&(_, weight)| weight).sum();
+        weights.into_iter().map(|(voxel, weight)| (voxel, weight / total)).collect()
+    }
+
+    /// Blend a voxel function of `components` values over the voxels around a mesh vertex
+    ///
+    /// # Returns
+    /// The blended values, or the component count of a voxel not matching `components`
+    fn blend(&self, position: Vec3f, components: usize, f: impl Fn(&T) -> Vec<f32>) -> Result<Vec<f32>, usize> {
+        let mut blended = vec![0.0; components];
+        for (voxel, weight) in self.vertex_voxels(position) {
+            let values = f(voxel);
+            if values.len() != components {
+                return Err(values.len());
+            }
+            for (blended, value) in blended.iter_mut().zip(values) {
+                *blended += weight * value;
+            }
+        }
+        Ok(blended)
+    }
+
+    /// Transfer the configured voxel attributes onto the mesh
+    fn transfer_attributes(&self, mesh: &mut Mesh) -> Result<(), MeshBuilderError> {
+        let positions: Vec<Vec3f> = mesh.vertices.iter().map(|v| v.position).collect();
+        if let Some(color) = &self.colors {
+            mesh.colors = positions.iter()
+                .map(|&p| {
+                    let c = self.blend(p, 4, |voxel| color(voxel).to_vec()).unwrap();
+                    [c[0], c[1], c[2], c[3]]
+                })
+                .collect();
+        }
+        for (name, attribute) in &self.attributes {
+            // The background sets the component count, even for empty meshes
+            let components = attribute(&T::background()).len();
+            let mut values = Vec::with_capacity(positions.len() * components);
+            for &p in &positions {
+                let blended = self.blend(p, components, attribute)
+                    .map_err(|found| MeshBuilderError::InconsistentAttribute { name: name.clone(), expected: components, found })?;
+                values.extend(blended);
+            }
+            mesh.attributes.insert(name.clone(), VertexAttribute { components, values });
+        }
+        if let Some(material) = &self.material {
+            let vertex_materials: Vec<u32> = positions.iter()
+                .map(|&p| {
+                    let voxel = self.vertex_voxels(p)
+                        .into_iter()
+                        .fold(None, |best: Option<(&T, f32)>, candidate| match best {
+                            Some(best) if best.1 >= candidate.1 => Some(best),
+                            _ => Some(candidate),
+                        })
+                        .map_or_else(T::background, |(voxel, _)| voxel.clone());
+                    material(&voxel)
+                })
+                .collect();
+            mesh.triangle_materials = mesh.triangles.iter()
+                .map(|triangle| {
+                    let [a, b, c] = triangle.indices.map(|i| vertex_materials[i]);
+                    if b == c { b } else { a }
+                })
+                .collect();
+        }
+        Ok(())
+    }
+
     /// Build the mesh using the configured parameters
     pub fn build(self) -> Result<Mesh, MeshBuilderError> {
-        let iso_level = self.iso_level.ok_or(MeshBuilderError::NoIsoLevel)?;
+        let iso_level = match self.iso_level {
+            Some(iso_level) => iso_level,
+            None if !self.algorithm.uses_iso_level() => 0.0,
+            None => return Err(MeshBuilderError::NoIsoLevel),
+        };
 
         // Validate iso level
         if !iso_level.is_finite() {
             return Err(MeshBuilderError::InvalidIsoLevel(iso_level));
         }
 
+        let mut view = VolumeView::new(self.voxel_volume);
+        if let Some(bounds) = self.bounds {
+            view = view.with_region(self.voxel_volume.world_to_voxel_bounds(bounds));
+        }
+        if let Some(cancel_flag) = self.cancel_flag.clone() {
+            view = view.with_cancel_flag(cancel_flag);
+        }
+
         // Delegate to the algorithm
-        self.algorithm.generate_mesh(self.voxel_volume, iso_level)
-            .map_err(|e| MeshBuilderError::GenerationFailed(e.to_string()))
+        let mut mesh = self.algorithm.generate_mesh(&view, iso_level)?;
+        if self.vertex_normals && !mesh.has_normals() {
+            mesh.compute_vertex_normals();
+        }
+        self.transfer_attributes(&mut mesh)?;
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{VolumeConfig, VolumeConfigType};
+    use crate::voxel_data::tests::{SdfVoxel, config, sphere_volume};
+    use crate::mesh_generation::{Vertex, Triangle};
+    use crate::math::Vec3i;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+    use std::sync::atomic::Ordering;
+    use test_log::test;
+
+    /// Signed distance voxel painted with a material
+    #[derive(Debug, Clone, PartialEq)]
+    struct Painted(f32, u32);
+
+    impl VoxelData for Painted {
+        fn is_active(&self) -> bool { SdfVoxel(self.0).is_active() }
+        fn background() -> Self { Painted(SdfVoxel::BAND, 0) }
+    }
+
+    impl SignedDistance for Painted {
+        fn signed_distance(&self) -> f32 { self.0 }
+    }
+
+    /// Mesher returning a fixed mesh
+    struct FixedMesher(Mesh);
+
+    impl<T: VoxelData> Mesher<T> for FixedMesher {
+        fn generate_mesh(&self, _view: &VolumeView<'_, T>, _iso_level: f32) -> Result<Mesh, AlgorithmError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_marching_cubes_sphere() {
+        let volume = sphere_volume(Vec3f::zero(), 1.0);
+        let mesh = MeshBuilder::new(&volume).with_iso_level(0.0).build().unwrap();
+        assert!(mesh.triangle_count() > 0);
+        let direct = MarchingCubesAlgorithm::new().generate_mesh(&volume, 0.0).unwrap();
+        assert_eq!(direct.triangles, mesh.triangles);
+    }
+
+    #[test]
+    fn test_parallel_is_deterministic() {
+        let volume = sphere_volume(Vec3f::zero(), 1.0);
+        let serial = MeshBuilder::new(&volume).with_iso_level(0.0).build().unwrap();
+        let parallel = MeshBuilder::new(&volume)
+            .with_iso_level(0.0)
+            .with_mesher(MarchingCubesAlgorithm::new().parallel().with_threads(4))
+            .build()
+            .unwrap();
+        assert_eq!(serial.vertices, parallel.vertices);
+        assert_eq!(serial.triangles, parallel.triangles);
+        // Vertices on shared edges are merged
+        assert!(serial.vertex_count() < serial.triangle_count());
+    }
+
+    #[test]
+    fn test_parallel_uses_threads() {
+        /// Threads that read a `Traced` voxel's distance
+        static READERS: Mutex<Vec<std::thread::ThreadId>> = Mutex::new(Vec::new());
+
+        #[derive(Debug, Clone, PartialEq)]
+        struct Traced(f32);
+
+        impl VoxelData for Traced {
+            fn is_active(&self) -> bool { SdfVoxel(self.0).is_active() }
+            fn background() -> Self { Traced(SdfVoxel::BAND) }
+        }
+
+        impl SignedDistance for Traced {
+            fn signed_distance(&self) -> f32 {
+                READERS.lock().unwrap().push(std::thread::current().id());
+                self.0
+            }
+        }
+
+        let mut volume = VoxelVolume::with_config(config());
+        volume.fill_bounds(Vec3f::new(-1.5, -1.5, -1.5), Vec3f::new(1.5, 1.5, 1.5), |p| Some(Traced(p.length() - 1.0)));
+        MeshBuilder::new(&volume)
+            .with_iso_level(0.0)
+            .with_mesher(MarchingCubesAlgorithm::new().parallel().with_threads(3))
+            .build()
+            .unwrap();
+        let readers: HashSet<_> = READERS.lock().unwrap().drain(..).collect();
+        assert_eq!(readers.len(), 3);
+    }
+
+    #[test]
+    fn test_voxels_not_shared_between_threads() {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Shared(std::rc::Rc<f32>);
+
+        impl VoxelData for Shared {
+            fn is_active(&self) -> bool { SdfVoxel(*self.0).is_active() }
+            fn background() -> Self { Shared(std::rc::Rc::new(SdfVoxel::BAND)) }
+        }
+
+        impl SignedDistance for Shared {
+            fn signed_distance(&self) -> f32 { *self.0 }
+        }
+
+        let mut volume = VoxelVolume::with_config(config());
+        volume.fill_bounds(Vec3f::new(-1.5, -1.5, -1.5), Vec3f::new(1.5, 1.5, 1.5), |p| {
+            Some(Shared(std::rc::Rc::new(p.length() - 1.0)))
+        });
+        let mesh = MeshBuilder::new(&volume).with_iso_level(0.0).build().unwrap();
+        assert_eq!(mesh.triangle_count(), MeshBuilder::new(&sphere_volume(Vec3f::zero(), 1.0)).with_iso_level(0.0).build().unwrap().triangle_count());
+    }
+
+    #[test]
+    fn test_bounds() {
+        let volume = sphere_volume(Vec3f::zero(), 1.0);
+        let full = MeshBuilder::new(&volume).with_iso_level(0.0).build().unwrap();
+        let half = MeshBuilder::new(&volume)
+            .with_iso_level(0.0)
+            .with_bounds(Bounds3f::new(Vec3f::new(0.0, -1.5, -1.5), Vec3f::new(1.5, 1.5, 1.5)))
+            .build()
+            .unwrap();
+        assert!(half.triangle_count() > 0);
+        assert!(half.triangle_count() < full.triangle_count());
+        assert!(half.vertices.iter().all(|v| v.position.x >= -0.1));
+    }
+
+    #[test]
+    fn test_vertex_normals() {
+        let volume = sphere_volume(Vec3f::zero(), 1.0);
+        let gradient = MeshBuilder::new(&volume)
+            .with_iso_level(0.0)
+            .with_mesher(MarchingCubesAlgorithm::new().with_gradient_normals())
+            .build()
+            .unwrap();
+        let faces = MeshBuilder::new(&volume).with_iso_level(0.0).with_vertex_normals().build().unwrap();
+        assert_eq!(gradient.normals.len(), gradient.vertex_count());
+        assert_eq!(faces.normals.len(), faces.vertex_count());
+
+        // Both point outwards on a sphere centred at the origin
+        for mesh in [&gradient, &faces] {
+            for (vertex, normal) in mesh.vertices.iter().zip(&mesh.normals) {
+                assert!(normal.dot(&vertex.position.normalize()) > 0.9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_voxel_attributes() {
+        // Material 1 for x > 0, material 2 elsewhere
+        let mut volume = VoxelVolume::with_config(config());
+        volume.fill_bounds(Vec3f::new(-1.5, -1.5, -1.5), Vec3f::new(1.5, 1.5, 1.5), |p| {
+            Some(Painted(p.length() - 1.0, if p.x > 0.0 { 1 } else { 2 }))
+        });
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let blue = [0.0, 0.0, 1.0, 1.0];
+        let mesh = MeshBuilder::new(&volume)
+            .with_iso_level(0.0)
+            .with_colors(|v: &Painted| if v.1 == 1 { red } else { blue })
+            .with_attribute("distance", |v: &Painted| vec![v.0])
+            .with_material(|v: &Painted| v.1)
+            .build()
+            .unwrap();
+
+        assert_eq!(mesh.colors.len(), mesh.vertex_count());
+        assert_eq!(mesh.attribute("distance").unwrap().values.len(), mesh.vertex_count());
+        assert_eq!(mesh.triangle_materials.len(), mesh.triangle_count());
+        for (index, vertex) in mesh.vertices.iter().enumerate() {
+            // Interpolated distance is the iso level on crossing edges
+            assert!(mesh.attribute("distance").unwrap().get(index)[0].abs() < 1e-4);
+            if vertex.position.x > 0.15 {
+                assert_eq!(mesh.colors[index], red);
+            } else if vertex.position.x < -0.15 {
+                assert_eq!(mesh.colors[index], blue);
+            }
+        }
+        for (triangle, &material) in mesh.triangles.iter().zip(&mesh.triangle_materials) {
+            let x = mesh.vertices[triangle.indices[0]].position.x;
+            if x.abs() > 0.15 {
+                assert_eq!(material, if x > 0.0 { 1 } else { 2 });
+            }
+        }
+    }
+
+    #[test]
+    fn test_attributes_ignore_background() {
+        // A single active voxel of material 1 among background voxels of material 0
+        let mut volume = VoxelVolume::with_config(config());
+        volume.set_voxel(Vec3i::new(1, 1, 1), Painted(0.0, 1));
+        let mut triangle = Mesh::new();
+        for p in [(0.05, 0.05, 0.05), (0.06, 0.06, 0.04), (0.09, 0.01, 0.05)] {
+            triangle.add_vertex(Vertex { position: Vec3f::new(p.0, p.1, p.2) });
+        }
+        triangle.add_triangle(Triangle { indices: [0, 1, 2] });
+
+        let mesh = MeshBuilder::new(&volume)
+            .with_iso_level(0.0)
+            .with_mesher(FixedMesher(triangle))
+            .with_colors(|v: &Painted| [v.1 as f32, 0.0, 0.0, 1.0])
+            .with_attribute("material", |v: &Painted| vec![v.1 as f32])
+            .with_material(|v: &Painted| v.1)
+            .build()
+            .unwrap();
+        for index in 0..mesh.vertex_count() {
+            assert!((mesh.colors[index][0] - 1.0).abs() < 1e-5);
+            assert!((mesh.attribute("material").unwrap().get(index)[0] - 1.0).abs() < 1e-5);
+        }
+        assert_eq!(mesh.triangle_materials, vec![1]);
+    }
+
+    #[test]
+    fn test_attribute_components() {
+        let volume = sphere_volume(Vec3f::zero(), 1.0);
+        let result = MeshBuilder::new(&volume)
+            .with_iso_level(0.0)
+            .with_attribute("uneven", |v: &SdfVoxel| if v.0 < 0.0 { vec![1.0] } else { vec![1.0, 2.0] })
+            .build();
+        assert!(matches!(
+            result,
+            Err(MeshBuilderError::InconsistentAttribute { expected: 2, found: 1, .. })
+        ));
+
+        let empty = MeshBuilder::new(&volume)
+            .with_iso_level(0.0)
+            .with_mesher(FixedMesher(Mesh::new()))
+            .with_attribute("uv", |_: &SdfVoxel| vec![0.0, 0.0])
+            .build()
+            .unwrap();
+        assert_eq!(empty.attribute("uv").unwrap().components, 2);
+    }
+
+    #[test]
+    fn test_custom_mesher() {
+        let volume = sphere_volume(Vec3f::zero(), 1.0);
+        let mesh = MeshBuilder::new(&volume)
+            .with_iso_level(0.0)
+            .with_mesher(FixedMesher(Mesh::new()))
+            .build()
+            .unwrap();
+        assert!(mesh.is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let volume = VoxelVolume::<SdfVoxel>::with_config(VolumeConfig { volume_config_type: VolumeConfigType::Default, ..config() });
+        assert!(matches!(MeshBuilder::new(&volume).build(), Err(MeshBuilderError::NoIsoLevel)));
+        assert!(matches!(
+            MeshBuilder::new(&volume).with_iso_level(0.0).build(),
+            Err(MeshBuilderError::GenerationFailed(AlgorithmError::EmptyVolume))
+        ));
+
+        let volume = sphere_volume(Vec3f::zero(), 1.0);
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        cancel_flag.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            MeshBuilder::new(&volume).with_iso_level(0.0).with_cancel_flag(cancel_flag).build(),
+            Err(MeshBuilderError::GenerationFailed(AlgorithmError::Cancelled))
+        ));
+        assert!(matches!(
+            MeshBuilder::new(&volume)
+                .with_iso_level(0.0)
+                .with_mesher(MarchingCubesAlgorithm::new().with_max_region_voxels(8))
+                .build(),
+            Err(MeshBuilderError::GenerationFailed(AlgorithmError::RegionTooLarge { .. }))
+        ));
     }
 }
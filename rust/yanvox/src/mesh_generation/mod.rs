@@ -2,6 +2,13 @@ pub mod mesh;
 pub mod mesh_builder;
 pub mod algorithm;
 pub mod marching_cubes;
+pub mod greedy;
+pub mod transvoxel;
+pub mod chunked;
 
 pub use mesh::*;
 pub use mesh_builder::*;
+pub use algorithm::{AlgorithmError, MarchingCubesAlgorithm, ParallelMarchingCubes, Mesher, VolumeView};
+pub use greedy::GreedyMesher;
+pub use transvoxel::LodMesher;
+pub use chunked::ChunkedMesher;
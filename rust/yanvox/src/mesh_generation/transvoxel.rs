@@ -0,0 +1,578 @@
+use crate::voxel::SignedDistance;
+use crate::math::{Vec3i, Vec3f};
+use super::mesh::{Mesh, Vertex, Triangle};
+use super::algorithm::{AlgorithmError, Mesher, VolumeView};
+use super::marching_cubes::{CORNER_OFFSETS, EDGE_VERTEX_INDICES, TRIANGLE_TABLE};
+use std::collections::{HashMap, HashSet};
+
+/// Level-of-detail marching cubes mesher producing one mesh per chunk
+///
+/// The volume is split into cubic chunks of `2^chunk_log2` voxels per side,
+/// typically matching the leaf or internal node size. Each chunk is meshed
+/// with marching cubes at a stride of `2^lod` voxels, where the level of
+/// detail is chosen per chunk by a selector function.
+///
+/// Cells touching a finer chunk, whether across a face, along an edge or only
+/// at a corner, become transition cells: every edge and face they share with
+/// finer cells is sampled at the finer resolution, and the surface is rebuilt
+/// from its contours on the cell's faces. Whether an edge or face is split
+/// depends only on the chunks touching it, so the cells on both sides trace
+/// the same contour and no cracks appear between chunks.
+///
+/// Chunks touching each other may differ by at most one level of detail.
+pub struct LodMesher {
+    chunk_log2: u32,
+    lod: Box<dyn Fn(Vec3i) -> u32>,
+}
+
+/// Directed boundary segment of the surface on a cell face, as vertex indices
+type Segment = (usize, usize);
+
+impl LodMesher {
+    /// Create a mesher with chunks of `2^chunk_log2` voxels per side, all at full resolution
+    pub fn new(chunk_log2: u32) -> Self {
+        Self {
+            chunk_log2,
+            lod: Box::new(|_| 0),
+        }
+    }
+
+    /// Set the function selecting the level of detail of a chunk from its key
+    ///
+    /// Level `n` samples every `2^n` voxels. Levels are clamped so a chunk
+    /// holds at least two cells per side.
+    pub fn with_lod(mut self, lod: impl Fn(Vec3i) -> u32 + 'static) -> Self {
+        self.lod = Box::new(lod);
+        self
+    }
+
+    /// Get the key (lower corner) of the chunk containing a voxel
+    pub fn chunk_key(&self, coord: Vec3i) -> Vec3i {
+        chunk_key(coord, 1 << self.chunk_log2)
+    }
+
+    fn chunk_lod(&self, key: Vec3i) -> u32 {
+        (self.lod)(key).min(self.chunk_log2.saturating_sub(1))
+    }
+
+    /// Generate one mesh per chunk containing active voxels, keyed by chunk key
+    ///
+    /// Returns `AlgorithmError::EmptyVolume` if the view has no active voxels.
+    pub fn generate_chunks<T: SignedDistance + Clone + 'static>(
+        &self,
+        view: &VolumeView<'_, T>,
+        iso_level: f32,
+    ) -> Result<HashMap<Vec3i, Mesh>, AlgorithmError> {
+        let size = 1 << self.chunk_log2;
+        let chunks: HashSet<Vec3i> = view.active_voxels()
+            .map(|(coord, _)| self.chunk_key(coord))
+            .collect();
+        if chunks.is_empty() {
+            return Err(AlgorithmError::EmptyVolume);
+        }
+        let lods: HashMap<Vec3i, u32> = chunks.iter()
+            .map(|&key| (key, self.chunk_lod(key)))
+            .collect();
+
+        let mut meshes = HashMap::new();
+        for (&key, &lod) in &lods {
+            view.check_cancelled()?;
+
+            // Chunks sharing a face, an edge or a corner with a finer chunk need transition cells
+            let mut finer = false;
+            for offset in (0..27).filter(|&i| i != 13).map(|i| Vec3i::new(i % 3 - 1, i / 3 % 3 - 1, i / 9 - 1)) {
+                let neighbour = key + offset * size;
+                if let Some(&neighbour_lod) = lods.get(&neighbour) {
+                    if neighbour_lod + 1 < lod {
+                        return Err(AlgorithmError::UnsupportedLodTransition { chunk: key, neighbour });
+                    }
+                    finer |= neighbour_lod < lod;
+                }
+            }
+
+            let mut chunk = ChunkMesher {
+                view,
+                iso_level,
+                key,
+                size,
+                stride: 1 << lod,
+                lods: &lods,
+                finer,
+                leaf_size: view.volume().get_leaf_voxel_size(),
+                mesh: Mesh::new(),
+                vertices: HashMap::new(),
+            };
+            chunk.generate()?;
+            meshes.insert(key, chunk.mesh);
+        }
+        Ok(meshes)
+    }
+}
+
+impl<T: SignedDistance + Clone + 'static> Mesher<T> for LodMesher {
+    /// Generate all chunks and merge them into a single mesh
+    fn generate_mesh(&self, view: &VolumeView<'_, T>, iso_level: f32) -> Result<Mesh, AlgorithmError> {
+        let chunks = self.generate_chunks(view, iso_level)?;
+        let mut keys: Vec<Vec3i> = chunks.keys().copied().collect();
+        keys.sort_by_key(|k| (k.z, k.y, k.x));
+
+        let mut mesh = Mesh::new();
+        for key in keys {
+            mesh.append(&chunks[&key]);
+        }
+        Ok(mesh)
+    }
+}
+
+/// Meshing state for a single chunk
+struct ChunkMesher<'v, 'a, T: SignedDistance> {
+    view: &'v VolumeView<'a, T>,
+    iso_level: f32,
+    key: Vec3i,
+    size: i32,
+    stride: i32,
+    /// Level of detail of every chunk being meshed
+    lods: &'v HashMap<Vec3i, u32>,
+    /// Whether any chunk touching this one is finer
+    finer: bool,
+    leaf_size: f32,
+    mesh: Mesh,
+    /// Vertices keyed by the ordered endpoints of the edge they cross
+    vertices: HashMap<(Vec3i, Vec3i), usize>,
+}
+
+impl<T: SignedDistance + Clone + 'static> ChunkMesher<'_, '_, T> {
+    fn generate(&mut self) -> Result<(), AlgorithmError> {
+        let n = self.size / self.stride;
+        for k in 0..n {
+            for j in 0..n {
+                for i in 0..n {
+                    let origin = self.key + Vec3i::new(i, j, k) * self.stride;
+                    if self.view.contains(origin) {
+                        self.add_cell(origin)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sample the signed distance at a voxel, `None` if the voxel is inactive
+    fn sample(&self, coord: Vec3i) -> Result<Option<f32>, AlgorithmError> {
+        if !self.view.volume().get_voxel(coord).is_active() {
+            return Ok(None);
+        }
+        self.distance(coord).map(Some)
+    }
+
+    /// Read the signed distance at a voxel, active or not
+    ///
+    /// Inactive voxels give the distance stored for them, or the background
+    /// distance if none was stored.
+    fn distance(&self, coord: Vec3i) -> Result<f32, AlgorithmError> {
+        let value = self.view.volume().get_voxel(coord).signed_distance();
+        if !value.is_finite() {
+            return Err(AlgorithmError::NonFiniteValue { coord, value });
+        }
+        Ok(value)
+    }
+
+    /// Sample the 8 corners of a cell of the given stride
+    fn cell_values(&self, origin: Vec3i, stride: i32) -> Result<Option<[f32; 8]>, AlgorithmError> {
+        let mut values = [0.0; 8];
+        for (corner, value) in values.iter_mut().enumerate() {
+            match self.sample(origin + corner_offset(corner) * stride)? {
+                Some(sample) => *value = sample,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(values))
+    }
+
+    fn cube_index(&self, values: &[f32; 8]) -> usize {
+        values.iter().enumerate()
+            .filter(|(_, value)| **value < self.iso_level)
+            .fold(0, |index, (corner, _)| index | (1 << corner))
+    }
+
+    /// Check if a lattice point is shared with a finer chunk and must be sampled
+    ///
+    /// Only edge midpoints and face centres of cells are checked, and the
+    /// answer depends only on the chunks touching the point, so every cell
+    /// sharing that edge or face agrees on it.
+    fn is_split(&self, point: Vec3i) -> bool {
+        self.finer && (0..8).any(|corner| {
+            let key = chunk_key(point - corner_offset(corner), self.size);
+            key != self.key && self.lods.get(&key).is_some_and(|&lod| (1 << lod) < self.stride)
+        })
+    }
+
+    /// Check if any edge or face of a cell is split
+    fn has_split(&self, origin: Vec3i) -> bool {
+        let half = self.stride / 2;
+        self.finer && (0..27)
+            .map(|i| Vec3i::new(i % 3, i / 3 % 3, i / 9))
+            .filter(|offset| offset.x == 1 || offset.y == 1 || offset.z == 1)
+            .any(|offset| self.is_split(origin + offset * half))
+    }
+
+    /// Get or create the vertex for a crossing between two samples
+    fn vertex(&mut self, a: Vec3i, value_a: f32, b: Vec3i, value_b: f32) -> usize {
+        let (a, value_a, b, value_b) = if (a.x, a.y, a.z) <= (b.x, b.y, b.z) {
+            (a, value_a, b, value_b)
+        } else {
+            (b, value_b, a, value_a)
+        };
+        if let Some(&index) = self.vertices.get(&(a, b)) {
+            return index;
+        }
+
+        let t = if (value_b - value_a).abs() < 1e-6 {
+            0.5
+        } else {
+            ((self.iso_level - value_a) / (value_b - value_a)).clamp(0.0, 1.0)
+        };
+        let pa = a.as_vec3f();
+        let position = pa + (b.as_vec3f() - pa).scale(t);
+        let index = self.mesh.add_vertex(Vertex { position: position.scale(self.leaf_size) });
+        self.vertices.insert((a, b), index);
+        index
+    }
+
+    /// Add the triangles of a cell
+    ///
+    /// Cells with a split edge or face, or with a face the surface crosses
+    /// twice, are rebuilt from their face contours so they match their
+    /// neighbours; all others use the marching cubes tables.
+    fn add_cell(&mut self, origin: Vec3i) -> Result<(), AlgorithmError> {
+        let Some(values) = self.cell_values(origin, self.stride)? else {
+            return Ok(());
+        };
+        let cube_index = self.cube_index(&values);
+        if has_ambiguous_face(cube_index) || self.has_split(origin) {
+            return self.add_transition_cell(origin);
+        }
+        for triangle in TRIANGLE_TABLE[cube_index].chunks(3).take_while(|t| t[0] != -1) {
+            let indices = [0, 1, 2].map(|i| {
+                let [c1, c2] = EDGE_VERTEX_INDICES[triangle[i] as usize].map(|c| c as usize);
+                self.vertex(
+                    origin + corner_offset(c1) * self.stride, values[c1],
+                    origin + corner_offset(c2) * self.stride, values[c2],
+                )
+            });
+            self.mesh.add_triangle(Triangle { indices });
+        }
+        Ok(())
+    }
+
+    /// Rebuild the surface in a cell from its contours on the cell's faces
+    ///
+    /// Each face is traced through its corners and split edge midpoints, or
+    /// as four quarter faces when its centre is split.
+    fn add_transition_cell(&mut self, origin: Vec3i) -> Result<(), AlgorithmError> {
+        let stride = self.stride;
+        let mut segments = Vec::new();
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            for side in 0..2 {
+                // Points on the face in half cells; only corners are used at a stride of one
+                let face = origin + unit(axis, side * stride);
+                let point = |(i, j): (i32, i32)| face + unit(u, i * stride / 2) + unit(v, j * stride / 2);
+
+                let mut polygons = Vec::with_capacity(4);
+                if self.is_split(point((1, 1))) {
+                    for (i, j) in [(0, 0), (1, 0), (1, 1), (0, 1)] {
+                        polygons.push([(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)].map(point).to_vec());
+                    }
+                } else {
+                    let corners = [(0, 0), (2, 0), (2, 2), (0, 2)];
+                    let mut polygon = Vec::with_capacity(8);
+                    for (k, &(i, j)) in corners.iter().enumerate() {
+                        let (next_i, next_j) = corners[(k + 1) % 4];
+                        let midpoint = point(((i + next_i) / 2, (j + next_j) / 2));
+                        polygon.push(point((i, j)));
+                        if self.is_split(midpoint) {
+                            polygon.push(midpoint);
+                        }
+                    }
+                    polygons.push(polygon);
+                }
+
+                for mut polygon in polygons {
+                    // Wind counter-clockwise seen from outside the cell
+                    if side == 0 {
+                        polygon.reverse();
+                    }
+                    self.add_face_contour(&mut segments, &polygon)?;
+                }
+            }
+        }
+
+        for polygon in assemble_loops(&segments) {
+            self.add_polygon(&polygon);
+        }
+        Ok(())
+    }
+
+    /// Add the contour of the surface on a face polygon wound counter-clockwise from outside
+    ///
+    /// Segments keep the inside of the surface on their right. A face crossed
+    /// more than twice is resolved by the mean of its samples, which both
+    /// cells sharing it compute alike. The corners are active, but split
+    /// points may lie where the band ends and are read as stored.
+    fn add_face_contour(&mut self, segments: &mut Vec<Segment>, polygon: &[Vec3i]) -> Result<(), AlgorithmError> {
+        let values = polygon.iter()
+            .map(|&point| self.distance(point))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut crossings = Vec::new();
+        for i in 0..polygon.len() {
+            let j = (i + 1) % polygon.len();
+            let entering = values[j] < self.iso_level;
+            if (values[i] < self.iso_level) != entering {
+                crossings.push((self.vertex(polygon[i], values[i], polygon[j], values[j]), entering));
+            }
+        }
+
+        // Sum in sorted order so both windings of the face agree
+        let mut sorted = values.clone();
+        sorted.sort_by(f32::total_cmp);
+        let connect_inside = sorted.iter().sum::<f32>() / (sorted.len() as f32) < self.iso_level;
+        for (k, &(vertex, entering)) in crossings.iter().enumerate() {
+            let (next, _) = crossings[(k + 1) % crossings.len()];
+            if connect_inside && !entering {
+                segments.push((next, vertex));
+            } else if !connect_inside && entering {
+                segments.push((vertex, next));
+            }
+        }
+        Ok(())
+    }
+
+    /// Triangulate a closed polygon, as a fan around its centroid if it has more than three vertices
+    fn add_polygon(&mut self, polygon: &[usize]) {
+        if let [a, b, c] = *polygon {
+            self.mesh.add_triangle(Triangle { indices: [a, b, c] });
+            return;
+        }
+        let sum = polygon.iter()
+            .fold(Vec3f::zero(), |sum, &i| sum + self.mesh.vertices[i].position);
+        let centre = self.mesh.add_vertex(Vertex { position: sum.scale(1.0 / polygon.len() as f32) });
+        for (i, &a) in polygon.iter().enumerate() {
+            let b = polygon[(i + 1) % polygon.len()];
+            self.mesh.add_triangle(Triangle { indices: [centre, a, b] });
+        }
+    }
+}
+
+/// Chain directed boundary segments into closed polygons
+///
+/// Chains that do not close are dropped.
+fn assemble_loops(segments: &[Segment]) -> Vec<Vec<usize>> {
+    let next: HashMap<usize, usize> = segments.iter().copied().collect();
+    let mut used = HashSet::new();
+    let mut polygons = Vec::new();
+    for &(first, mut current) in segments {
+        if !used.insert(first) {
+            continue;
+        }
+        let mut polygon = vec![first];
+        let closed = loop {
+            if current == first {
+                break true;
+            }
+            if !used.insert(current) {
+                break false;
+            }
+            polygon.push(current);
+            match next.get(&current) {
+                Some(&vertex) => current = vertex,
+                None => break false,
+            }
+        };
+        if closed && polygon.len() >= 3 {
+            polygons.push(polygon);
+        }
+    }
+    polygons
+}
+
+/// Check if a face of a marching cubes case has only its diagonally opposite corners inside
+fn has_ambiguous_face(cube_index: usize) -> bool {
+    (0..3).any(|axis| (0..2).any(|side| {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let inside = [(0, 0), (1, 0), (1, 1), (0, 1)]
+            .map(|(i, j)| (cube_index >> ((side << axis) | (i << u) | (j << v))) & 1 == 1);
+        inside[0] == inside[2] && inside[1] == inside[3] && inside[0] != inside[1]
+    }))
+}
+
+/// Key (lower corner) of the chunk of the given size containing a voxel
+fn chunk_key(coord: Vec3i, size: i32) -> Vec3i {
+    Vec3i::new(coord.x & !(size - 1), coord.y & !(size - 1), coord.z & !(size - 1))
+}
+
+/// Offset of a cube corner, following the corner numbering of the lookup tables
+fn corner_offset(corner: usize) -> Vec3i {
+    CORNER_OFFSETS[corner].into()
+}
+
+fn unit(axis: usize, value: i32) -> Vec3i {
+    match axis {
+        0 => Vec3i::new(value, 0, 0),
+        1 => Vec3i::new(0, value, 0),
+        _ => Vec3i::new(0, 0, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{VoxelData, VoxelVolume};
+    use crate::voxel_data::tests::{SdfVoxel, config_with_size};
+    use test_log::test;
+
+    /// Volume of unit voxels from the origin to `max`, with distances in voxels scaled so the band spans four voxels
+    fn field_volume(max: Vec3f, distance: impl Fn(Vec3f) -> f32) -> VoxelVolume<SdfVoxel> {
+        let mut volume = VoxelVolume::with_config(config_with_size(1.0));
+        volume.fill_bounds(Vec3f::zero(), max, |p| Some(SdfVoxel(distance(p) * SdfVoxel::BAND / 4.0)).filter(|v| v.is_active()));
+        volume
+    }
+
+    /// Sphere straddling the face between chunks (0, 0, 0) and (16, 0, 0)
+    fn straddling_sphere() -> VoxelVolume<SdfVoxel> {
+        sphere_field(Vec3f::new(32.0, 16.0, 16.0), Vec3f::new(16.1, 8.2, 7.9), 3.7)
+    }
+
+    fn sphere_field(max: Vec3f, centre: Vec3f, radius: f32) -> VoxelVolume<SdfVoxel> {
+        field_volume(max, |p| (p - centre).length() - radius)
+    }
+
+    /// Sphere intersected with a gyroid, so many faces are crossed more than twice
+    fn gyroid_volume(centre: Vec3f, radius: f32) -> VoxelVolume<SdfVoxel> {
+        field_volume(Vec3f::new(32.0, 32.0, 32.0), |p| {
+            let q = p.scale(std::f32::consts::TAU / 7.0);
+            let gyroid = q.x.sin() * q.y.cos() + q.y.sin() * q.z.cos() + q.z.sin() * q.x.cos();
+            ((p - centre).length() - radius).max(gyroid)
+        })
+    }
+
+    /// Mesh with the chunks in `fine` at level 0 and all others at level 1
+    fn mesh_with_fine(volume: &VoxelVolume<SdfVoxel>, fine: &[Vec3i]) -> HashMap<Vec3i, Mesh> {
+        let fine = fine.to_vec();
+        LodMesher::new(4)
+            .with_lod(move |key| if fine.contains(&key) { 0 } else { 1 })
+            .generate_chunks(&VolumeView::new(volume), 0.0)
+            .unwrap()
+    }
+
+    /// Count edges not shared by exactly one opposite triangle after welding positions
+    fn open_edges(meshes: &HashMap<Vec3i, Mesh>) -> usize {
+        let mut welded: HashMap<(i64, i64, i64), usize> = HashMap::new();
+        let mut edges: HashMap<(usize, usize), i32> = HashMap::new();
+        for mesh in meshes.values() {
+            let ids: Vec<usize> = mesh.vertices.iter().map(|v| {
+                let key = ((v.position.x * 1e4).round() as i64, (v.position.y * 1e4).round() as i64, (v.position.z * 1e4).round() as i64);
+                let next = welded.len();
+                *welded.entry(key).or_insert(next)
+            }).collect();
+            for triangle in &mesh.triangles {
+                let [a, b, c] = triangle.indices.map(|i| ids[i]);
+                if a == b || b == c || c == a {
+                    continue;
+                }
+                for (p, q) in [(a, b), (b, c), (c, a)] {
+                    *edges.entry((p, q)).or_default() += 1;
+                }
+            }
+        }
+        edges.iter()
+            .filter(|&(&(p, q), &count)| count != 1 || edges.get(&(q, p)) != Some(&1))
+            .count()
+    }
+
+    #[test]
+    fn test_single_resolution_is_closed() {
+        let volume = straddling_sphere();
+        let meshes = LodMesher::new(4).generate_chunks(&VolumeView::new(&volume), 0.0).unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert!(meshes.values().all(|mesh| mesh.triangle_count() > 0));
+        assert_eq!(open_edges(&meshes), 0);
+    }
+
+    #[test]
+    fn test_transition_is_closed() {
+        let volume = straddling_sphere();
+        for coarse_x in [0, 16] {
+            let mesher = LodMesher::new(4).with_lod(move |key| if key.x == coarse_x { 1 } else { 0 });
+            let meshes = mesher.generate_chunks(&VolumeView::new(&volume), 0.0).unwrap();
+            assert_eq!(open_edges(&meshes), 0);
+        }
+    }
+
+    #[test]
+    fn test_lod_jump_is_rejected() {
+        let volume = straddling_sphere();
+        let mesher = LodMesher::new(4).with_lod(|key| if key.x == 16 { 2 } else { 0 });
+        assert!(matches!(
+            mesher.generate_chunks(&VolumeView::new(&volume), 0.0),
+            Err(AlgorithmError::UnsupportedLodTransition { .. })
+        ));
+    }
+
+    #[test]
+    fn test_edge_transition_is_closed() {
+        // Four chunks around the edge x = y = 16, each finer or coarser than only its diagonal
+        let volume = sphere_field(Vec3f::new(32.0, 32.0, 32.0), Vec3f::new(16.2, 15.9, 8.1), 5.3);
+        let keys = [(0, 0), (16, 0), (0, 16), (16, 16)].map(|(x, y)| Vec3i::new(x, y, 0));
+        for diagonal in [[keys[0], keys[3]], [keys[1], keys[2]]] {
+            assert_eq!(open_edges(&mesh_with_fine(&volume, &diagonal)), 0);
+        }
+        for key in keys {
+            assert_eq!(open_edges(&mesh_with_fine(&volume, &[key])), 0);
+            let others: Vec<Vec3i> = keys.iter().copied().filter(|&k| k != key).collect();
+            assert_eq!(open_edges(&mesh_with_fine(&volume, &others)), 0);
+        }
+    }
+
+    #[test]
+    fn test_band_end_in_transition_cell() {
+        // Plane x = 15 with the fine chunk at x >= 16 inside
+        let mut volume = field_volume(Vec3f::new(32.0, 16.0, 16.0), |p| 15.0 - p.x);
+        let fine = [Vec3i::new(16, 0, 0)];
+        let full = mesh_with_fine(&volume, &fine);
+
+        // The fine side's band ends at a split edge midpoint of a transition cell
+        volume.set_voxel(Vec3i::new(16, 9, 8), SdfVoxel(-SdfVoxel::BAND));
+        let meshes = mesh_with_fine(&volume, &fine);
+        assert_eq!(meshes[&Vec3i::zero()].triangle_count(), full[&Vec3i::zero()].triangle_count());
+        assert_eq!(open_edges(&meshes), open_edges(&full));
+    }
+
+    #[test]
+    fn test_corner_transition_is_closed() {
+        // Eight chunks around the corner (16, 16, 16), each alone finer or coarser than the rest
+        let volume = sphere_field(Vec3f::new(32.0, 32.0, 32.0), Vec3f::new(16.3, 15.8, 16.2), 5.6);
+        let keys: Vec<Vec3i> = (0..8).map(|i| corner_offset(i) * 16).collect();
+        for &key in &keys {
+            let meshes = mesh_with_fine(&volume, &[key]);
+            assert_eq!(meshes.len(), 8);
+            assert_eq!(open_edges(&meshes), 0);
+            let others: Vec<Vec3i> = keys.iter().copied().filter(|&k| k != key).collect();
+            assert_eq!(open_edges(&mesh_with_fine(&volume, &others)), 0);
+        }
+        let checkerboard: Vec<Vec3i> = keys.iter().copied().filter(|k| (k.x + k.y + k.z) / 16 % 2 == 0).collect();
+        assert_eq!(open_edges(&mesh_with_fine(&volume, &checkerboard)), 0);
+    }
+
+    #[test]
+    fn test_ambiguous_faces_are_closed() {
+        let volume = gyroid_volume(Vec3f::new(16.3, 15.8, 16.2), 11.0);
+        let keys: Vec<Vec3i> = (0..8).map(|i| corner_offset(i) * 16).collect();
+        for fine in [&keys[..], &[], &keys[..1], &keys[..3]] {
+            let meshes = mesh_with_fine(&volume, fine);
+            assert!(meshes.values().all(|mesh| mesh.triangle_count() > 0));
+            assert_eq!(open_edges(&meshes), 0);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Signed distance primitives and their rasterization into volumes
+//!
+//! Primitives give the exact signed distance to a shape, negative inside,
+//! and can be transformed rigidly and scaled uniformly while staying exact.
+
+mod shapes; pub use shapes::{Sphere, Cuboid, RoundedCuboid, Cylinder, Capsule, Torus, Cone, Plane, Ellipsoid};
+mod rasterizer; pub use rasterizer::SdfRasterizer;
+
+use crate::conversion::ConversionError;
+use crate::math::{Vec3f, Bounds3f};
+use crate::voxel::{VoxelVolume, VolumeConfig, FromSignedDistance};
+
+/// Shape described by its signed distance function
+pub trait Primitive {
+    /// Get the signed distance from a point to the surface, negative inside
+    fn distance(&self, point: Vec3f) -> f32;
+
+    /// Get bounds enclosing the inside of the shape
+    ///
+    /// Unbounded shapes return infinite bounds.
+    fn bounds(&self) -> Bounds3f;
+
+    /// Move the shape by an offset
+    fn translated(self, offset: Vec3f) -> Transformed<Self> where Self: Sized {
+        Transformed::new(self, Transform::translation(offset))
+    }
+
+    /// Rotate the shape about an axis through the origin, by an angle in radians
+    fn rotated(self, axis: Vec3f, angle: f32) -> Transformed<Self> where Self: Sized {
+        Transformed::new(self, Transform::rotation(axis, angle))
+    }
+
+    /// Scale the shape about the origin
+    fn scaled(self, scale: f32) -> Transformed<Self> where Self: Sized {
+        Transformed::new(self, Transform::scale(scale))
+    }
+}
+
+/// Similarity transform: rotation, uniform scale and translation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    /// Rotation matrix, by rows
+    pub rotation: [[f32; 3]; 3],
+    pub scale: f32,
+    pub translation: Vec3f,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            rotation: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            scale: 1.0,
+            translation: Vec3f::zero(),
+        }
+    }
+}
+
+impl Transform {
+    /// Create a translation
+    pub fn translation(offset: Vec3f) -> Self {
+        Self { translation: offset, ..Self::default() }
+    }
+
+    /// Create a rotation about an axis through the origin, by an angle in radians
+    pub fn rotation(axis: Vec3f, angle: f32) -> Self {
+        let Vec3f { x, y, z } = axis.normalize();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+        let rotation = [
+            [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y],
+            [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x],
+            [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos],
+        ];
+        Self { rotation, ..Self::default() }
+    }
+
+    /// Create a uniform scale about the origin
+    ///
+    /// # Panics
+    /// Panics if the scale is not positive
+    pub fn scale(scale: f32) -> Self {
+        assert!(scale > 0.0, "scale must be positive");
+        Self { scale, ..Self::default() }
+    }
+
+    /// Map a point from shape space to world space
+    pub fn apply(&self, point: Vec3f) -> Vec3f {
+        let r = &self.rotation;
+        Vec3f::new(
+            r[0][0] * point.x + r[0][1] * point.y + r[0][2] * point.z,
+            r[1][0] * point.x + r[1][1] * point.y + r[1][2] * point.z,
+            r[2][0] * point.x + r[2][1] * point.y + r[2][2] * point.z,
+        ).scale(self.scale) + self.translation
+    }
+
+    /// Map a point from world space to shape space
+    pub fn apply_inverse(&self, point: Vec3f) -> Vec3f {
+        let p = (point - self.translation).scale(1.0 / self.scale);
+        let r = &self.rotation;
+        Vec3f::new(
+            r[0][0] * p.x + r[1][0] * p.y + r[2][0] * p.z,
+            r[0][1] * p.x + r[1][1] * p.y + r[2][1] * p.z,
+            r[0][2] * p.x + r[1][2] * p.y + r[2][2] * p.z,
+        )
+    }
+}
+
+/// Primitive placed by a transform
+#[derive(Debug, Clone)]
+pub struct Transformed<S: Primitive> {
+    pub shape: S,
+    pub transform: Transform,
+}
+
+impl<S: Primitive> Transformed<S> {
+    pub fn new(shape: S, transform: Transform) -> Self {
+        Self { shape, transform }
+    }
+}
+
+impl<S: Primitive> Primitive for Transformed<S> {
+    fn distance(&self, point: Vec3f) -> f32 {
+        self.shape.distance(self.transform.apply_inverse(point)) * self.transform.scale
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        let bounds = self.shape.bounds();
+        if !bounds.volume().is_finite() {
+            let infinity = Vec3f::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+            return Bounds3f::new(infinity.scale(-1.0), infinity);
+        }
+        (0..8).fold(Bounds3f::empty(), |result, corner| {
+            let pick = |bit: usize, min: f32, max: f32| if corner & bit == 0 { min } else { max };
+            result.expand(self.transform.apply(Vec3f::new(
+                pick(1, bounds.min.x, bounds.max.x),
+                pick(2, bounds.min.y, bounds.max.y),
+                pick(4, bounds.min.z, bounds.max.z),
+            )))
+        })
+    }
+}
+
+/// Rasterizer writing the narrow band of a primitive into a volume
+///
+/// Uses an [`SdfRasterizer`] over the bounds of the primitive, so only
+/// blocks that the band can reach are evaluated voxel by voxel.
+pub struct PrimitiveRasterizer<'a, S: Primitive + ?Sized> {
+    shape: &'a S,
+    band_width: f32,
+    bounds: Option<Bounds3f>,
+    fill_interior: bool,
+}
+
+impl<'a, S: Primitive + ?Sized> PrimitiveRasterizer<'a, S> {
+    /// Create a rasterizer with a band of 3 voxels
+    pub fn new(shape: &'a S) -> Self {
+        Self { shape, band_width: 3.0, bounds: None, fill_interior: true }
+    }
+
+    /// Set the half-width of the narrow band, in voxels
+    pub fn with_band_width(mut self, band_width: f32) -> Self {
+        self.band_width = band_width;
+        self
+    }
+
+    /// Restrict rasterization to world-space bounds, required for unbounded shapes
+    pub fn with_bounds(mut self, bounds: Bounds3f) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// Set whether the inside of the shape beyond the band is filled, see [`SdfRasterizer::with_interior_fill`]
+    pub fn with_interior_fill(mut self, fill_interior: bool) -> Self {
+        self.fill_interior = fill_interior;
+        self
+    }
+
+    /// Rasterize the primitive into a new volume
+    pub fn build<T: FromSignedDistance + Clone + 'static>(&self, config: VolumeConfig) -> Result<VoxelVolume<T>, ConversionError> {
+        let mut volume = VoxelVolume::with_config(config);
+        self.write_into(&mut volume)?;
+        Ok(volume)
+    }
+
+    /// Write the narrow band of the primitive into an existing volume
+    ///
+    /// # Returns
+    /// Number of voxels written
+    pub fn write_into<T: FromSignedDistance + Clone + 'static>(&self, volume: &mut VoxelVolume<T>) -> Result<usize, ConversionError> {
+        // Pad by a voxel so rounding cannot clip the band at the shape bounds
+        let margin = (self.band_width + 1.0) * volume.get_leaf_voxel_size();
+        SdfRasterizer::new(|point| self.shape.distance(point), self.region(margin))
+            .with_band_width(self.band_width)
+            .with_interior_fill(self.fill_interior)
+            .write_into(volume)
+    }
+
+    /// Get the world-space region within a margin of the shape
+    fn region(&self, margin: f32) -> Bounds3f {
+        let margin = Vec3f::new(margin, margin, margin);
+        let shape = self.shape.bounds();
+        let shape = Bounds3f::new(shape.min - margin, shape.max + margin);
+        match self.bounds {
+            Some(bounds) => Bounds3f::new(
+                Vec3f::new(shape.min.x.max(bounds.min.x), shape.min.y.max(bounds.min.y), shape.min.z.max(bounds.min.z)),
+                Vec3f::new(shape.max.x.min(bounds.max.x), shape.max.y.min(bounds.max.y), shape.max.z.min(bounds.max.z)),
+            ),
+            None => shape,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::{SdfVoxel, config};
+    use test_log::test;
+
+    #[test]
+    fn test_transformed() {
+        let shape = Cuboid::new(Vec3f::new(1.0, 0.5, 0.5))
+            .rotated(Vec3f::new(0.0, 0.0, 1.0), std::f32::consts::FRAC_PI_2)
+            .scaled(2.0)
+            .translated(Vec3f::new(0.0, 0.0, 5.0));
+        assert!((shape.distance(Vec3f::new(0.0, 3.0, 5.0)) - 1.0).abs() < 1e-5);
+        assert!((shape.distance(Vec3f::new(2.0, 0.0, 5.0)) - 1.0).abs() < 1e-5);
+        assert!((shape.distance(Vec3f::new(0.0, 0.0, 5.0)) + 1.0).abs() < 1e-5);
+        let bounds = shape.bounds();
+        assert!((bounds.min - Vec3f::new(-1.0, -2.0, 4.0)).length() < 1e-5);
+        assert!((bounds.max - Vec3f::new(1.0, 2.0, 6.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn test_rasterize_band() {
+        let shape = Torus::new(1.0, 0.3).translated(Vec3f::new(0.2, 0.0, -0.1));
+        let volume: VoxelVolume<SdfVoxel> = PrimitiveRasterizer::new(&shape).with_band_width(2.0).build(config()).unwrap();
+
+        // Every voxel within the band is written, and only those
+        let region = volume.world_to_voxel_bounds(Bounds3f::new(Vec3f::new(-2.0, -1.0, -2.0), Vec3f::new(2.0, 1.0, 2.0)));
+        let mut expected = 0;
+        for coord in region.coords() {
+            let distance = shape.distance(coord.as_vec3f().scale(0.1));
+            if distance.abs() < 0.2 {
+                expected += 1;
+                assert_eq!(volume.get_voxel(coord).0, distance);
+            }
+        }
+        assert_eq!(volume.active_count(), expected);
+
+        let plane = Plane::new(Vec3f::new(0.0, 1.0, 0.0), 0.0);
+        assert!(matches!(
+            PrimitiveRasterizer::new(&plane).build::<SdfVoxel>(config()),
+            Err(ConversionError::UnboundedShape)
+        ));
+        let clipped: VoxelVolume<SdfVoxel> = PrimitiveRasterizer::new(&plane)
+            .with_bounds(Bounds3f::new(Vec3f::new(-1.0, -1.0, -1.0), Vec3f::new(1.0, 1.0, 1.0)))
+            .build(config())
+            .unwrap();
+        assert_eq!(clipped.active_count(), 20 * 5 * 20);
+    }
+}
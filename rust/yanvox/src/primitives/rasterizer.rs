@@ -0,0 +1,241 @@
+use crate::conversion::ConversionError;
+use crate::math::{Vec3i, Vec3f, Bounds3f, Bounds3i};
+use crate::voxel::{VoxelVolume, VolumeConfig, FromSignedDistance};
+use std::collections::HashMap;
+
+/// Voxels per side of the coarsest blocks
+const ROOT_BLOCK_SIZE: i32 = 32;
+/// Blocks at most this size are evaluated voxel by voxel
+const DENSE_BLOCK_SIZE: i32 = 4;
+
+/// Hierarchical rasterizer writing the narrow band of a signed distance function
+///
+/// The region is covered by blocks of voxels that are subdivided as an
+/// octree. The function is evaluated at block corners, and a block whose
+/// corners all lie on the same side of the surface, further than the band
+/// plus the distance the function can change within the block, is skipped
+/// without visiting its voxels. The change is bounded by the Lipschitz
+/// constant of the function: 1 for exact distances, more for functions that
+/// overestimate them.
+///
+/// Skipped blocks outside the surface are left at the background, and those
+/// inside are filled with tiles at the negated background distance (or band
+/// width if larger), see [`VoxelVolume::fill`]. This keeps them inactive
+/// while letting later operations tell inside from outside.
+pub struct SdfRasterizer<F: Fn(Vec3f) -> f32> {
+    function: F,
+    bounds: Bounds3f,
+    band_width: f32,
+    lipschitz: f32,
+    fill_interior: bool,
+}
+
+impl<F: Fn(Vec3f) -> f32> SdfRasterizer<F> {
+    /// Create a rasterizer over world-space bounds, with a band of 3 voxels and a Lipschitz constant of 1
+    pub fn new(function: F, bounds: Bounds3f) -> Self {
+        Self {
+            function,
+            bounds,
+            band_width: 3.0,
+            lipschitz: 1.0,
+            fill_interior: true,
+        }
+    }
+
+    /// Set the half-width of the narrow band, in voxels
+    pub fn with_band_width(mut self, band_width: f32) -> Self {
+        self.band_width = band_width;
+        self
+    }
+
+    /// Set the Lipschitz constant bounding how fast the function changes
+    ///
+    /// Must be positive, as smaller values would skip blocks the band reaches.
+    pub fn with_lipschitz(mut self, lipschitz: f32) -> Self {
+        self.lipschitz = lipschitz;
+        self
+    }
+
+    /// Set whether the inside of the surface beyond the band is filled, on by default
+    pub fn with_interior_fill(mut self, fill_interior: bool) -> Self {
+        self.fill_interior = fill_interior;
+        self
+    }
+
+    /// Rasterize the function into a new volume
+    pub fn build<T: FromSignedDistance + Clone + 'static>(&self, config: VolumeConfig) -> Result<VoxelVolume<T>, ConversionError> {
+        let mut volume = VoxelVolume::with_config(config);
+        self.write_into(&mut volume)?;
+        Ok(volume)
+    }
+
+    /// Write the narrow band of the function into an existing volume
+    ///
+    /// # Returns
+    /// Number of voxels written, including those covered by interior tiles
+    pub fn write_into<T: FromSignedDistance + Clone + 'static>(&self, volume: &mut VoxelVolume<T>) -> Result<usize, ConversionError> {
+        if !(self.band_width > 0.0 && self.band_width.is_finite()) {
+            return Err(ConversionError::InvalidBandWidth(self.band_width));
+        }
+        if !(self.lipschitz > 0.0 && self.lipschitz.is_finite()) {
+            return Err(ConversionError::InvalidLipschitz(self.lipschitz));
+        }
+        if !self.bounds.volume().is_finite() {
+            return Err(ConversionError::UnboundedShape);
+        }
+        let region = volume.world_to_voxel_bounds(self.bounds);
+        let roots = Bounds3i::new(
+            Vec3i::new(region.min.x.div_euclid(ROOT_BLOCK_SIZE), region.min.y.div_euclid(ROOT_BLOCK_SIZE), region.min.z.div_euclid(ROOT_BLOCK_SIZE)),
+            Vec3i::new(
+                (region.max.x + ROOT_BLOCK_SIZE - 1).div_euclid(ROOT_BLOCK_SIZE),
+                (region.max.y + ROOT_BLOCK_SIZE - 1).div_euclid(ROOT_BLOCK_SIZE),
+                (region.max.z + ROOT_BLOCK_SIZE - 1).div_euclid(ROOT_BLOCK_SIZE),
+            ),
+        );
+        let mut pass = RasterPass {
+            rasterizer: self,
+            volume,
+            region,
+            voxel_size: 0.0,
+            band: 0.0,
+            corners: HashMap::new(),
+            count: 0,
+            interior: T::background(),
+        };
+        pass.voxel_size = pass.volume.get_leaf_voxel_size();
+        pass.band = self.band_width * pass.voxel_size;
+        let far = T::background().signed_distance().abs().max(pass.band);
+        pass.interior = T::from_signed_distance(-far);
+        for root in roots.coords() {
+            pass.rasterize_block(root * ROOT_BLOCK_SIZE, ROOT_BLOCK_SIZE);
+        }
+        Ok(pass.count)
+    }
+}
+
+/// State of one rasterization into a volume
+struct RasterPass<'a, F: Fn(Vec3f) -> f32, T: FromSignedDistance + Clone + 'static> {
+    rasterizer: &'a SdfRasterizer<F>,
+    volume: &'a mut VoxelVolume<T>,
+    region: Bounds3i,
+    voxel_size: f32,
+    band: f32,
+    /// Function values at the block corners evaluated so far, shared by neighbouring blocks
+    corners: HashMap<Vec3i, f32>,
+    count: usize,
+    /// Value written inside the surface beyond the band
+    interior: T,
+}
+
+impl<F: Fn(Vec3f) -> f32, T: FromSignedDistance + Clone + 'static> RasterPass<'_, F, T> {
+    fn evaluate(&self, coord: Vec3i) -> f32 {
+        (self.rasterizer.function)(coord.as_vec3f().scale(self.voxel_size))
+    }
+
+    /// Rasterize the voxels of the block `[min, min + size)` inside the region
+    fn rasterize_block(&mut self, min: Vec3i, size: i32) {
+        let block = Bounds3i::new(min, min + Vec3i::one() * size);
+        if !block.intersects(self.region) {
+            return;
+        }
+        let clipped = Bounds3i::new(min.max(self.region.min), block.max.min(self.region.max));
+
+        if size <= DENSE_BLOCK_SIZE {
+            for coord in clipped.coords() {
+                let distance = self.evaluate(coord);
+                if distance.abs() < self.band {
+                    self.volume.set_voxel(coord, T::from_signed_distance(distance));
+                    self.count += 1;
+                } else if distance < 0.0 && self.rasterizer.fill_interior {
+                    self.volume.set_voxel(coord, self.interior.clone());
+                    self.count += 1;
+                }
+            }
+            return;
+        }
+
+        // Every point of the block is within half its diagonal of a corner
+        let reach = self.rasterizer.lipschitz * 0.5 * 3f32.sqrt() * size as f32 * self.voxel_size;
+        let mut nearest = f32::INFINITY;
+        let mut signs = [false; 2];
+        for corner in 0..8 {
+            let offset = Vec3i::new(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1) * size;
+            let coord = min + offset;
+            let distance = match self.corners.get(&coord) {
+                Some(&distance) => distance,
+                None => {
+                    let distance = self.evaluate(coord);
+                    self.corners.insert(coord, distance);
+                    distance
+                }
+            };
+            nearest = nearest.min(distance.abs());
+            signs[(distance < 0.0) as usize] = true;
+        }
+        if nearest >= self.band + reach && !(signs[0] && signs[1]) {
+            if signs[1] && self.rasterizer.fill_interior {
+                self.volume.fill(clipped, self.interior.clone());
+                self.count += clipped.volume() as usize;
+            }
+            return;
+        }
+
+        let half = size / 2;
+        for child in 0..8 {
+            let offset = Vec3i::new(child & 1, (child >> 1) & 1, (child >> 2) & 1) * half;
+            self.rasterize_block(min + offset, half);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::{SdfVoxel, config_with_size};
+    use std::cell::Cell;
+    use test_log::test;
+
+    #[test]
+    fn test_rasterize_function() {
+        let config = config_with_size(0.05);
+        let bounds = Bounds3f::new(Vec3f::new(-2.0, -2.0, -2.0), Vec3f::new(2.0, 2.0, 2.0));
+        let evaluations = Cell::new(0);
+        let sphere = |p: Vec3f| {
+            evaluations.set(evaluations.get() + 1);
+            p.length() - 1.0
+        };
+        let volume: VoxelVolume<SdfVoxel> = SdfRasterizer::new(sphere, bounds).with_band_width(2.0).build(config.clone()).unwrap();
+
+        let mut expected = 0;
+        for coord in volume.world_to_voxel_bounds(bounds).coords() {
+            let distance = coord.as_vec3f().scale(0.05).length() - 1.0;
+            if distance.abs() < 0.1 {
+                expected += 1;
+                assert_eq!(volume.get_voxel(coord).0, distance);
+            }
+        }
+        assert_eq!(volume.active_count(), expected);
+        // Far fewer evaluations than the 80^3 voxels of the region
+        assert!(evaluations.get() < 80 * 80 * 80 / 2, "{} evaluations", evaluations.get());
+
+        // The inside is filled with inactive tiles
+        assert_eq!(volume.get_voxel(Vec3i::zero()).0, -SdfVoxel::BAND);
+        assert_eq!(volume.get_voxel(Vec3i::new(30, 30, 30)).0, SdfVoxel::BAND);
+        assert!(volume.total_count() > volume.active_count());
+
+        let unfilled: VoxelVolume<SdfVoxel> = SdfRasterizer::new(|p: Vec3f| p.length() - 1.0, bounds)
+            .with_band_width(2.0)
+            .with_interior_fill(false)
+            .build(config.clone())
+            .unwrap();
+        assert_eq!(unfilled.active_count(), expected);
+        assert_eq!(unfilled.get_voxel(Vec3i::zero()).0, SdfVoxel::BAND);
+
+        for lipschitz in [0.0, -1.0, f32::NAN] {
+            assert!(matches!(
+                SdfRasterizer::new(|p: Vec3f| p.length() - 1.0, bounds).with_lipschitz(lipschitz).build::<SdfVoxel>(config.clone()),
+                Err(ConversionError::InvalidLipschitz(_))
+            ));
+        }
+    }
+}
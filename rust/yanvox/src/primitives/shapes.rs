@@ -0,0 +1,326 @@
+use super::Primitive;
+use crate::math::{Vec3f, Bounds3f};
+
+fn max_component(v: Vec3f) -> f32 {
+    v.x.max(v.y).max(v.z)
+}
+
+fn positive_part(v: Vec3f) -> Vec3f {
+    Vec3f::new(v.x.max(0.0), v.y.max(0.0), v.z.max(0.0))
+}
+
+fn abs(v: Vec3f) -> Vec3f {
+    Vec3f::new(v.x.abs(), v.y.abs(), v.z.abs())
+}
+
+/// Length of a 2D vector
+fn length2(x: f32, y: f32) -> f32 {
+    (x * x + y * y).sqrt()
+}
+
+/// Bounds symmetric about the origin
+fn centered_bounds(half_extents: Vec3f) -> Bounds3f {
+    Bounds3f::new(half_extents.scale(-1.0), half_extents)
+}
+
+/// Sphere centred on the origin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Primitive for Sphere {
+    fn distance(&self, point: Vec3f) -> f32 {
+        point.length() - self.radius
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        centered_bounds(Vec3f::one().scale(self.radius))
+    }
+}
+
+/// Axis-aligned box centred on the origin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cuboid {
+    pub half_extents: Vec3f,
+}
+
+impl Cuboid {
+    pub fn new(half_extents: Vec3f) -> Self {
+        Self { half_extents }
+    }
+}
+
+impl Primitive for Cuboid {
+    fn distance(&self, point: Vec3f) -> f32 {
+        let q = abs(point) - self.half_extents;
+        positive_part(q).length() + max_component(q).min(0.0)
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        centered_bounds(self.half_extents)
+    }
+}
+
+/// Axis-aligned box centred on the origin with edges and corners rounded by a radius
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundedCuboid {
+    /// Half extents including the rounding
+    pub half_extents: Vec3f,
+    pub radius: f32,
+}
+
+impl RoundedCuboid {
+    pub fn new(half_extents: Vec3f, radius: f32) -> Self {
+        Self { half_extents, radius }
+    }
+}
+
+impl Primitive for RoundedCuboid {
+    fn distance(&self, point: Vec3f) -> f32 {
+        let q = abs(point) - self.half_extents + Vec3f::one().scale(self.radius);
+        positive_part(q).length() + max_component(q).min(0.0) - self.radius
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        centered_bounds(self.half_extents)
+    }
+}
+
+/// Capped cylinder centred on the origin, along the y axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cylinder {
+    pub radius: f32,
+    pub half_height: f32,
+}
+
+impl Cylinder {
+    pub fn new(radius: f32, half_height: f32) -> Self {
+        Self { radius, half_height }
+    }
+}
+
+impl Primitive for Cylinder {
+    fn distance(&self, point: Vec3f) -> f32 {
+        let dx = length2(point.x, point.z) - self.radius;
+        let dy = point.y.abs() - self.half_height;
+        dx.max(dy).min(0.0) + length2(dx.max(0.0), dy.max(0.0))
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        centered_bounds(Vec3f::new(self.radius, self.half_height, self.radius))
+    }
+}
+
+/// Points within a radius of a segment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub start: Vec3f,
+    pub end: Vec3f,
+    pub radius: f32,
+}
+
+impl Capsule {
+    pub fn new(start: Vec3f, end: Vec3f, radius: f32) -> Self {
+        Self { start, end, radius }
+    }
+}
+
+impl Primitive for Capsule {
+    fn distance(&self, point: Vec3f) -> f32 {
+        let axis = self.end - self.start;
+        let offset = point - self.start;
+        let length_squared = axis.dot(&axis);
+        let t = if length_squared > 0.0 { (offset.dot(&axis) / length_squared).clamp(0.0, 1.0) } else { 0.0 };
+        (offset - axis.scale(t)).length() - self.radius
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        let margin = Vec3f::one().scale(self.radius);
+        let bounds = Bounds3f::from_point(self.start).expand(self.end);
+        Bounds3f::new(bounds.min - margin, bounds.max + margin)
+    }
+}
+
+/// Torus centred on the origin, in the xz plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Torus {
+    /// Distance from the centre to the middle of the tube
+    pub major_radius: f32,
+    /// Radius of the tube
+    pub minor_radius: f32,
+}
+
+impl Torus {
+    pub fn new(major_radius: f32, minor_radius: f32) -> Self {
+        Self { major_radius, minor_radius }
+    }
+}
+
+impl Primitive for Torus {
+    fn distance(&self, point: Vec3f) -> f32 {
+        length2(length2(point.x, point.z) - self.major_radius, point.y) - self.minor_radius
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        let outer = self.major_radius + self.minor_radius;
+        centered_bounds(Vec3f::new(outer, self.minor_radius, outer))
+    }
+}
+
+/// Capped cone centred on the origin, along the y axis
+///
+/// The base at `-half_height` has `bottom_radius` and the top at
+/// `half_height` has `top_radius`, zero for a pointed cone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cone {
+    pub bottom_radius: f32,
+    pub top_radius: f32,
+    pub half_height: f32,
+}
+
+impl Cone {
+    /// Create a pointed cone with its apex on +y
+    pub fn new(radius: f32, half_height: f32) -> Self {
+        Self { bottom_radius: radius, top_radius: 0.0, half_height }
+    }
+
+    /// Create a truncated cone
+    pub fn frustum(bottom_radius: f32, top_radius: f32, half_height: f32) -> Self {
+        Self { bottom_radius, top_radius, half_height }
+    }
+}
+
+impl Primitive for Cone {
+    fn distance(&self, point: Vec3f) -> f32 {
+        let (r1, r2, h) = (self.bottom_radius, self.top_radius, self.half_height);
+        let (qx, qy) = (length2(point.x, point.z), point.y);
+        // Closest points on the caps and on the slanted side, in the (radial, y) plane
+        let cap_radius = if qy < 0.0 { r1 } else { r2 };
+        let (ax, ay) = (qx - qx.min(cap_radius), qy.abs() - h);
+        let (kx, ky) = (r2 - r1, 2.0 * h);
+        let t = (((r2 - qx) * kx + (h - qy) * ky) / (kx * kx + ky * ky)).clamp(0.0, 1.0);
+        let (bx, by) = (qx - r2 + kx * t, qy - h + ky * t);
+        let sign = if bx < 0.0 && ay < 0.0 { -1.0 } else { 1.0 };
+        sign * (ax * ax + ay * ay).min(bx * bx + by * by).sqrt()
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        let radius = self.bottom_radius.max(self.top_radius);
+        centered_bounds(Vec3f::new(radius, self.half_height, radius))
+    }
+}
+
+/// Half-space below a plane, unbounded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    /// Unit normal pointing out of the half-space
+    pub normal: Vec3f,
+    /// Signed distance of the plane from the origin along the normal
+    pub offset: f32,
+}
+
+impl Plane {
+    /// Create a plane, normalizing its normal
+    pub fn new(normal: Vec3f, offset: f32) -> Self {
+        Self { normal: normal.normalize(), offset }
+    }
+}
+
+impl Primitive for Plane {
+    fn distance(&self, point: Vec3f) -> f32 {
+        point.dot(&self.normal) - self.offset
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        centered_bounds(Vec3f::one().scale(f32::INFINITY))
+    }
+}
+
+/// Axis-aligned ellipsoid centred on the origin
+///
+/// Exact distances to an ellipsoid have no closed form, so this uses a
+/// first-order approximation that is exact on the surface and for spheres,
+/// with errors growing with the eccentricity away from the surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ellipsoid {
+    pub radii: Vec3f,
+}
+
+impl Ellipsoid {
+    pub fn new(radii: Vec3f) -> Self {
+        Self { radii }
+    }
+}
+
+impl Primitive for Ellipsoid {
+    fn distance(&self, point: Vec3f) -> f32 {
+        let r = self.radii;
+        let k0 = Vec3f::new(point.x / r.x, point.y / r.y, point.z / r.z).length();
+        let k1 = Vec3f::new(point.x / (r.x * r.x), point.y / (r.y * r.y), point.z / (r.z * r.z)).length();
+        if k1 > 0.0 {
+            k0 * (k0 - 1.0) / k1
+        } else {
+            -r.x.min(r.y).min(r.z)
+        }
+    }
+
+    fn bounds(&self) -> Bounds3f {
+        centered_bounds(self.radii)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_shape_distances() {
+        let cuboid = Cuboid::new(Vec3f::new(1.0, 2.0, 3.0));
+        assert_eq!(cuboid.distance(Vec3f::new(2.0, 0.0, 0.0)), 1.0);
+        assert_eq!(cuboid.distance(Vec3f::new(0.0, 0.0, 0.0)), -1.0);
+        assert!((cuboid.distance(Vec3f::new(2.0, 3.0, 0.0)) - 2f32.sqrt()).abs() < 1e-6);
+
+        let rounded = RoundedCuboid::new(Vec3f::one(), 0.25);
+        assert_eq!(rounded.distance(Vec3f::new(2.0, 0.0, 0.0)), 1.0);
+        let corner = Vec3f::one().scale(0.75 + 0.25 / 3f32.sqrt());
+        assert!(rounded.distance(corner).abs() < 1e-6);
+
+        let cylinder = Cylinder::new(1.0, 2.0);
+        assert_eq!(cylinder.distance(Vec3f::new(0.0, 3.0, 0.0)), 1.0);
+        assert_eq!(cylinder.distance(Vec3f::new(0.0, 0.0, -3.0)), 2.0);
+        assert_eq!(cylinder.distance(Vec3f::new(0.5, 0.0, 0.0)), -0.5);
+
+        let capsule = Capsule::new(Vec3f::zero(), Vec3f::new(0.0, 0.0, 2.0), 0.5);
+        assert_eq!(capsule.distance(Vec3f::new(1.0, 0.0, 1.0)), 0.5);
+        assert_eq!(capsule.distance(Vec3f::new(0.0, 0.0, 3.0)), 0.5);
+
+        let torus = Torus::new(2.0, 0.5);
+        assert_eq!(torus.distance(Vec3f::new(2.0, 0.0, 0.0)), -0.5);
+        assert_eq!(torus.distance(Vec3f::zero()), 1.5);
+
+        let cone = Cone::new(1.0, 1.0);
+        assert_eq!(cone.distance(Vec3f::new(0.0, -2.0, 0.0)), 1.0);
+        assert!((cone.distance(Vec3f::new(0.0, 2.0, 0.0)) - 1.0).abs() < 1e-6);
+        // Perpendicular to the slanted side through its middle
+        let slant = Vec3f::new(2.0, 1.0, 0.0).normalize();
+        assert!((cone.distance(Vec3f::new(0.5, 0.0, 0.0) + slant.scale(0.3)) - 0.3).abs() < 1e-6);
+        assert!(cone.distance(Vec3f::new(0.0, -0.5, 0.0)) < 0.0);
+
+        let plane = Plane::new(Vec3f::new(0.0, 0.0, 2.0), 1.0);
+        assert_eq!(plane.distance(Vec3f::new(5.0, 5.0, 3.0)), 2.0);
+
+        let ellipsoid = Ellipsoid::new(Vec3f::new(1.0, 2.0, 3.0));
+        assert!(ellipsoid.distance(Vec3f::new(0.0, 2.0, 0.0)).abs() < 1e-6);
+        assert!(ellipsoid.distance(Vec3f::new(0.0, 0.0, 4.0)) > 0.0);
+        let sphere = Ellipsoid::new(Vec3f::one());
+        assert!((sphere.distance(Vec3f::new(0.0, 3.0, 0.0)) - Sphere::new(1.0).distance(Vec3f::new(0.0, 3.0, 0.0))).abs() < 1e-6);
+    }
+}
@@ -1,10 +1,18 @@
 use super::*;
-use math::{Vec3i, Vec3f, Bounds3i, Bounds3f};
+use math::{Vec3i, Vec3f, Bounds3i, Bounds3f, MassProperties};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 mod root_node; use root_node::RootNode;
 mod internal_node; use internal_node::InternalNode;
 mod leaf_node; use leaf_node::LeafNode;
+mod csg;
+mod reinitialize;
+mod morphology;
+mod curvature;
+mod topology; pub use topology::{ActiveMask, Connectivity};
+mod components; pub use components::{Component, Components};
+mod advection; pub use advection::{VelocityField, AdvectionScheme};
 
 pub trait VoxelData: Clone + std::cmp::PartialEq {
     /// Check if this voxel is "active" (non-empty)
@@ -21,12 +29,26 @@ pub trait SignedDistance: VoxelData {
     fn signed_distance(&self) -> f32;
 }
 
+/// Trait for signed distance voxel data that can be created from a distance value
+///
+/// Used by algorithms that write distances into a volume, such as voxelization
+pub trait FromSignedDistance: SignedDistance {
+    /// Create a voxel holding a signed distance
+    fn from_signed_distance(distance: f32) -> Self;
+
+    /// Copy this voxel with a new signed distance, keeping any other attributes
+    fn with_signed_distance(&self, distance: f32) -> Self {
+        Self::from_signed_distance(distance)
+    }
+}
+
 /// Unified trait that all nodes implement
 /// This allows seamless traversal from root to leaf
 pub trait NodeTrait<T: VoxelData> {
     // Basic node information
     fn level(&self) -> u32;
     fn log2_cum(&self) -> u32;
+    fn leaf_log2(&self) -> u32;
     fn bounds(&self) -> Bounds3i;
     fn is_active(&self, coord: Vec3i) -> bool;
     fn active_count(&self) -> usize;
@@ -40,11 +62,29 @@ pub trait NodeTrait<T: VoxelData> {
     // Iterator operations
     fn active_voxels(&self) -> Box<dyn Iterator<Item = (Vec3i, &T)> + '_>;
     fn all_voxels(&self) -> Box<dyn Iterator<Item = (Vec3i, &T)> + '_>;    
+
+    /// Iterate over the active voxels within bounds (max exclusive)
+    ///
+    /// Nodes with children skip those that do not overlap the bounds.
+    fn active_voxels_in(&self, bounds: Bounds3i) -> Box<dyn Iterator<Item = (Vec3i, &T)> + '_> {
+        Box::new(self.active_voxels().filter(move |(coord, _)| bounds.contains(*coord)))
+    }
+
+    /// Set every voxel of the node within bounds (max exclusive) to a value
+    ///
+    /// Internal nodes store the child blocks covered by the bounds as tiles.
+    fn fill(&mut self, bounds: Bounds3i, value: T) {
+        for coord in bounds.intersection(self.bounds()).coords() {
+            self.set_voxel(coord, value.clone());
+        }
+    }
 }
 
 trait ChildNodeTrait<T: VoxelData>: NodeTrait<T> {
     fn log2() -> u32;
     fn log2_cum() -> u32;
+    /// Returns the log2 of the leaf node size at the bottom of the hierarchy
+    fn leaf_log2() -> u32;
 
     /// Calculate the key (lower left corner)
     /// 
@@ -67,6 +107,11 @@ trait ChildNodeTrait<T: VoxelData>: NodeTrait<T> {
     
     // Add factory method
     fn create(coord: Vec3i, level: u32, background_value: T) -> Self;
+
+    /// Replace the leaf block containing a coordinate with the same block of another node
+    ///
+    /// Tiles of `other` covering the block are copied as tiles.
+    fn copy_leaf(&mut self, other: &Self, coord: Vec3i);
 }
 
 // Separate trait for advanced users
@@ -85,9 +130,60 @@ pub enum NodeType {
 }
 
 
+/// Root node of a volume, one variant per configuration type
+///
+/// Keeping the concrete node types makes volumes `Send` and `Sync`
+/// whenever their voxel type is.
+#[derive(Clone)]
+enum Root<T: VoxelData> {
+    Default(RootNode<T, LeafNode<T, 2>>),
+    Hashx5x4(RootNode<T, InternalNode<T, LeafNode<T, 4>, 5>>),
+    Hashx2x1(RootNode<T, InternalNode<T, LeafNode<T, 1>, 2>>),
+}
+
+impl<T: VoxelData + 'static> std::ops::Deref for Root<T> {
+    type Target = dyn NodeTrait<T>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Root::Default(root) => root,
+            Root::Hashx5x4(root) => root,
+            Root::Hashx2x1(root) => root,
+        }
+    }
+}
+
+impl<T: VoxelData + 'static> std::ops::DerefMut for Root<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Root::Default(root) => root,
+            Root::Hashx5x4(root) => root,
+            Root::Hashx2x1(root) => root,
+        }
+    }
+}
+
+impl<T: VoxelData + 'static> Root<T> {
+    /// Copy the leaf block containing a coordinate from a root of the same configuration
+    ///
+    /// Returns false, copying nothing, if the configurations differ.
+    fn copy_leaf(&mut self, other: &Self, coord: Vec3i) -> bool {
+        match (self, other) {
+            (Root::Default(root), Root::Default(other)) => root.copy_leaf(other, coord),
+            (Root::Hashx5x4(root), Root::Hashx5x4(other)) => root.copy_leaf(other, coord),
+            (Root::Hashx2x1(root), Root::Hashx2x1(other)) => root.copy_leaf(other, coord),
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[derive(Clone)]
 pub struct VoxelVolume<T: VoxelData> {
-    root: Box<dyn NodeTrait<T>>,
+    root: Root<T>,
     config: VolumeConfig,
+    /// Keys of the leaves modified since the last checkpoint
+    dirty_leaves: HashSet<Vec3i>,
 }
 
 /// Summary information for a VoxelVolume
@@ -105,14 +201,15 @@ pub struct VoxelVolumeSummary {
 impl<T: VoxelData + Clone + 'static> VoxelVolume<T> {
     /// Create a new voxel volume with a configuration
     pub fn with_config(config: VolumeConfig) -> Self {
-        let root: Box<dyn NodeTrait<T>> = match config.volume_config_type {
-            VolumeConfigType::Default => Box::new(RootNode::<T, LeafNode<T, 2>>::default()),
-            VolumeConfigType::Hashx5x4 => Box::new(RootNode::<T, InternalNode<T, LeafNode<T, 4>, 5>>::default()),
-            VolumeConfigType::Hashx2x1 => Box::new(RootNode::<T, InternalNode<T, LeafNode<T, 1>, 2>>::default()),
+        let root = match config.volume_config_type {
+            VolumeConfigType::Default => Root::Default(RootNode::default()),
+            VolumeConfigType::Hashx5x4 => Root::Hashx5x4(RootNode::default()),
+            VolumeConfigType::Hashx2x1 => Root::Hashx2x1(RootNode::default()),
         };
         Self {
             root,
             config,
+            dirty_leaves: HashSet::new(),
         }
     }
     
@@ -133,6 +230,7 @@ impl<T: VoxelData + Clone + 'static> VoxelVolume<T> {
 
     /// Set a voxel at a given coordinate
     pub fn set_voxel(&mut self, coord: Vec3i, value: T) -> Option<T> {
+        self.dirty_leaves.insert(self.leaf_key(coord));
         self.root.set_voxel(coord, value)
     }
 
@@ -140,8 +238,44 @@ impl<T: VoxelData + Clone + 'static> VoxelVolume<T> {
       self.set_voxel(self.cv_coord(coord), value)
     }
 
+    /// Set every voxel within bounds (max exclusive) to a value
+    ///
+    /// Whole leaves inside the bounds are stored as single tiles when the
+    /// configuration has internal nodes, so large constant regions such as
+    /// the inside of a narrow band stay cheap.
+    pub fn fill(&mut self, bounds: Bounds3i, value: T) {
+        let size = bounds.size();
+        if size.x <= 0 || size.y <= 0 || size.z <= 0 {
+            return;
+        }
+        let step = 1i32 << self.leaf_log2();
+        let keys = Bounds3i::new(self.leaf_key(bounds.min), bounds.max);
+        for z in (keys.min.z..keys.max.z).step_by(step as usize) {
+            for y in (keys.min.y..keys.max.y).step_by(step as usize) {
+                for x in (keys.min.x..keys.max.x).step_by(step as usize) {
+                    self.dirty_leaves.insert(Vec3i::new(x, y, z));
+                }
+            }
+        }
+        self.root.fill(bounds, value);
+    }
+
+    /// Replace the leaf containing a coordinate with the same leaf of another volume
+    ///
+    /// The leaf's nodes and tiles are cloned rather than written voxel by
+    /// voxel. Returns false, copying nothing, if the volumes have different
+    /// configuration types.
+    fn copy_leaf_from(&mut self, other: &Self, coord: Vec3i) -> bool {
+        if !self.root.copy_leaf(&other.root, coord) {
+            return false;
+        }
+        self.dirty_leaves.insert(self.leaf_key(coord));
+        true
+    }
+
     /// Remove a voxel at a given coordinate
     pub fn remove_voxel(&mut self, coord: Vec3i) -> Option<T> {
+        self.dirty_leaves.insert(self.leaf_key(coord));
         self.root.remove_voxel(coord)
     }
 
@@ -173,6 +307,48 @@ impl<T: VoxelData + Clone + 'static> VoxelVolume<T> {
         self.root.active_voxels()
     }
 
+    /// Get an iterator over the active voxels within bounds (max exclusive)
+    ///
+    /// Only the leaves overlapping the bounds are visited.
+    pub fn active_voxels_in(&self, bounds: Bounds3i) -> Box<dyn Iterator<Item = (Vec3i, &T)> + '_> {
+        self.root.active_voxels_in(bounds)
+    }
+
+    // Change tracking
+
+    /// Get the log2 of the leaf node size, in voxels per side
+    pub fn leaf_log2(&self) -> u32 {
+        self.root.leaf_log2()
+    }
+
+    /// Get the key (lower corner) of the leaf node containing a voxel coordinate
+    pub fn leaf_key(&self, coord: Vec3i) -> Vec3i {
+        let size = 1 << self.leaf_log2();
+        Vec3i::new(coord.x & !(size - 1), coord.y & !(size - 1), coord.z & !(size - 1))
+    }
+
+    /// Get the keys of the leaves modified since the last checkpoint
+    pub fn dirty_leaves(&self) -> &HashSet<Vec3i> {
+        &self.dirty_leaves
+    }
+
+    /// Get the voxel-space bounds of the leaves modified since the last checkpoint
+    pub fn dirty_bounds(&self) -> impl Iterator<Item = Bounds3i> + '_ {
+        let size = 1 << self.leaf_log2();
+        self.dirty_leaves.iter()
+            .map(move |&key| Bounds3i::new(key, key + Vec3i::new(size, size, size)))
+    }
+
+    /// Check if any voxel was modified since the last checkpoint
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty_leaves.is_empty()
+    }
+
+    /// Mark the current state as clean, returning the leaves modified since the last checkpoint
+    pub fn checkpoint(&mut self) -> HashSet<Vec3i> {
+        std::mem::take(&mut self.dirty_leaves)
+    }
+
     // Batch operations
 
     /*
@@ -252,6 +428,39 @@ impl<T: VoxelData + Clone + 'static> VoxelVolume<T> {
 
     // ===== UTILITY FUNCTIONS =====
 
+    /// Get the voxels surrounding a world-space position with their trilinear weights
+    ///
+    /// Corners are ordered with x varying fastest; the weights sum to one.
+    pub fn trilinear_weights(&self, position: Vec3f) -> [(Vec3i, f32); 8] {
+        let grid = position.scale(1.0 / self.config.leaf_voxel_size);
+        let base = Vec3i::new(grid.x.floor() as i32, grid.y.floor() as i32, grid.z.floor() as i32);
+        let t = grid - base.as_vec3f();
+        std::array::from_fn(|corner| {
+            let (dx, dy, dz) = (corner as i32 & 1, (corner as i32 >> 1) & 1, (corner as i32 >> 2) & 1);
+            let weight = if dx == 1 { t.x } else { 1.0 - t.x }
+                * if dy == 1 { t.y } else { 1.0 - t.y }
+                * if dz == 1 { t.z } else { 1.0 - t.z };
+            (base + Vec3i::new(dx, dy, dz), weight)
+        })
+    }
+
+    /// Convert world-space bounds to the smallest voxel-space bounds covering them
+    /// 
+    /// # Arguments
+    /// * `bounds` - Bounds in world space
+    /// 
+    /// # Returns
+    /// Voxel-space bounds, with the maximum corner exclusive
+    pub fn world_to_voxel_bounds(&self, bounds: Bounds3f) -> Bounds3i {
+        let leaf_voxel_size = self.config.leaf_voxel_size;
+        let floor = |v: f32| (v / leaf_voxel_size).floor() as i32;
+        let ceil = |v: f32| (v / leaf_voxel_size).ceil() as i32;
+        Bounds3i::new(
+            Vec3i::new(floor(bounds.min.x), floor(bounds.min.y), floor(bounds.min.z)),
+            Vec3i::new(ceil(bounds.max.x), ceil(bounds.max.y), ceil(bounds.max.z)),
+        )
+    }
+
     /// Convert a world-space coordinate to the corresponding voxel coordinate
     /// 
     /// # Arguments
@@ -332,6 +541,78 @@ impl<T: VoxelData + Clone + 'static> VoxelVolume<T> {
     }
 }
 
+impl<T: SignedDistance + Clone + 'static> VoxelVolume<T> {
+    /// Sample the signed distance at a world-space position
+    ///
+    /// Values are trilinearly interpolated between the surrounding voxels;
+    /// voxels that are not stored contribute the background value.
+    pub fn sample_signed_distance(&self, position: Vec3f) -> f32 {
+        self.trilinear_weights(position)
+            .iter()
+            .filter(|(_, weight)| *weight > 0.0)
+            .map(|&(coord, weight)| weight * self.get_voxel(coord).signed_distance())
+            .sum()
+    }
+
+    /// Estimate the signed distance gradient at a world-space position
+    ///
+    /// Uses central differences of the interpolated signed distance over one leaf voxel
+    pub fn signed_distance_gradient(&self, position: Vec3f) -> Vec3f {
+        let h = self.config.leaf_voxel_size;
+        let difference = |offset: Vec3f| {
+            self.sample_signed_distance(position + offset) - self.sample_signed_distance(position - offset)
+        };
+        Vec3f::new(
+            difference(Vec3f::new(h, 0.0, 0.0)),
+            difference(Vec3f::new(0.0, h, 0.0)),
+            difference(Vec3f::new(0.0, 0.0, h)),
+        ).scale(0.5 / h)
+    }
+
+    /// Estimate the surface area and mass properties of the iso-surface from the field
+    ///
+    /// Surface integrals are approximated by summing over voxels within 1.5
+    /// voxels of the iso-surface with a smeared delta function, and volume
+    /// integrals are obtained from them by the divergence theorem, so only
+    /// the narrow band needs to be stored. The band must hold valid
+    /// distances at least 2 voxels either side of the surface. Errors are of
+    /// the order of a voxel size relative to the feature size.
+    pub fn estimate_mass_properties(&self, iso_level: f32) -> MassProperties {
+        let h = self.config.leaf_voxel_size as f64;
+        let epsilon = 1.5 * h;
+        let cell_volume = h * h * h;
+        let distance = |coord: Vec3i| (self.get_voxel(coord).signed_distance() - iso_level) as f64;
+
+        let mut area = 0.0;
+        let mut volume = 0.0;
+        let mut first = [0.0; 3];
+        let mut second = [[0.0; 3]; 3];
+        for (coord, voxel) in self.active_voxels() {
+            let phi = (voxel.signed_distance() - iso_level) as f64;
+            if phi.abs() >= epsilon {
+                continue;
+            }
+            let delta = (1.0 + (std::f64::consts::PI * phi / epsilon).cos()) / (2.0 * epsilon);
+            let gradient = [Vec3i::new(1, 0, 0), Vec3i::new(0, 1, 0), Vec3i::new(0, 0, 1)]
+                .map(|step| (distance(coord + step) - distance(coord - step)) / (2.0 * h));
+            let x = [coord.x as f64 * h, coord.y as f64 * h, coord.z as f64 * h];
+            let weight = delta * cell_volume;
+            // Surface element x.n dA
+            let flux = weight * (x[0] * gradient[0] + x[1] * gradient[1] + x[2] * gradient[2]);
+
+            area += weight * gradient.iter().map(|g| g * g).sum::<f64>().sqrt();
+            volume += flux / 3.0;
+            for i in 0..3 {
+                first[i] += x[i] * flux / 4.0;
+                for j in 0..3 {
+                    second[i][j] += x[i] * x[j] * flux / 5.0;
+                }
+            }
+        }
+        MassProperties::from_moments(area, volume, first, second)
+    }
+}
+
 impl std::fmt::Display for VoxelVolumeSummary {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "VoxelVolume Summary:\n")?;
@@ -457,7 +738,7 @@ pub struct VolumeConfig {
     pub leaf_voxel_size: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VolumeConfigType {
     Default,
     Hashx5x4,
@@ -497,4 +778,47 @@ mod tests {
         assert_eq!(summary.root_length, 10.0);
         assert_eq!(summary.leaf_length, 2.5);
     }
+
+    #[test]
+    fn test_dirty_leaf_tracking() {
+        let config = VolumeConfig {
+          compression: CompressionType::None,
+          leaf_voxel_size: 1.0,
+          volume_config_type: VolumeConfigType::Hashx2x1,
+        };
+        let mut voxel_volume = VoxelVolume::<u32>::with_config(config);
+        assert_eq!(voxel_volume.leaf_log2(), 1);
+        assert!(!voxel_volume.is_dirty());
+
+        voxel_volume.set_voxel(Vec3i::new(3, 0, -1), 1);
+        voxel_volume.set_voxel(Vec3i::new(2, 1, -2), 1);
+        assert_eq!(voxel_volume.dirty_leaves().len(), 1);
+        assert!(voxel_volume.dirty_leaves().contains(&Vec3i::new(2, 0, -2)));
+
+        let dirty = voxel_volume.checkpoint();
+        assert_eq!(dirty.len(), 1);
+        assert!(!voxel_volume.is_dirty());
+
+        voxel_volume.remove_voxel(Vec3i::new(3, 0, -1));
+        assert_eq!(voxel_volume.dirty_bounds().collect::<Vec<_>>(), vec![Bounds3i::new(Vec3i::new(2, 0, -2), Vec3i::new(4, 2, 0))]);
+    }
+
+    #[test]
+    fn test_active_voxels_in() {
+        let config = VolumeConfig {
+          compression: CompressionType::None,
+          leaf_voxel_size: 1.0,
+          volume_config_type: VolumeConfigType::Hashx2x1,
+        };
+        let mut voxel_volume = VoxelVolume::<u32>::with_config(config);
+        for coord in Bounds3i::new(Vec3i::new(-6, -6, -6), Vec3i::new(6, 6, 6)).coords() {
+            voxel_volume.set_voxel(coord, 1);
+        }
+        let bounds = Bounds3i::new(Vec3i::new(-1, 0, 2), Vec3i::new(3, 5, 4));
+        let mut inside: Vec<Vec3i> = voxel_volume.active_voxels_in(bounds).map(|(coord, _)| coord).collect();
+        inside.sort_by_key(|c| (c.z, c.y, c.x));
+        let mut expected: Vec<Vec3i> = bounds.coords().collect();
+        expected.sort_by_key(|c| (c.z, c.y, c.x));
+        assert_eq!(inside, expected);
+    }
 }
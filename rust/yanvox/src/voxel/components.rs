@@ -0,0 +1,122 @@
+//! Connected-component labeling of active voxels
+use super::*;
+use crate::disjoint_set::DisjointSet;
+use std::collections::HashMap;
+
+/// Connected set of active voxels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    /// Label of the component, from 1
+    pub label: u32,
+    pub voxel_count: usize,
+    /// Voxel-space bounds, with the maximum corner exclusive
+    pub bounds: Bounds3i,
+}
+
+/// Result of connected-component labeling
+pub struct Components {
+    /// Components in order of their first voxel, leaf by leaf
+    pub components: Vec<Component>,
+    /// Label of every active voxel, if requested
+    pub labels: Option<VoxelVolume<u32>>,
+}
+
+impl<T: VoxelData + Clone + 'static> VoxelVolume<T> {
+    /// Split the active voxels into connected components
+    ///
+    /// Voxels are visited leaf by leaf, each joined with its neighbours
+    /// already visited by union-find, so components spanning many leaves
+    /// are merged in a single pass. With `with_labels`, a volume with the
+    /// same configuration holding the label of every active voxel is also
+    /// returned.
+    pub fn connected_components(&self, connectivity: Connectivity, with_labels: bool) -> Components {
+        let mask = self.active_mask();
+        let coords: Vec<Vec3i> = mask.coords().collect();
+        let indices: HashMap<Vec3i, usize> = coords.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+
+        let offsets = connectivity.offsets();
+        let mut sets = DisjointSet::new(coords.len());
+        for (index, &coord) in coords.iter().enumerate() {
+            for &offset in &offsets {
+                if let Some(&neighbour) = indices.get(&(coord + offset))
+                    && neighbour < index
+                {
+                    sets.union(index, neighbour);
+                }
+            }
+        }
+
+        let mut components: Vec<Component> = Vec::new();
+        let mut labels_by_root: HashMap<usize, u32> = HashMap::new();
+        let mut voxel_labels = Vec::with_capacity(coords.len());
+        for (index, &coord) in coords.iter().enumerate() {
+            let root = sets.find(index);
+            let label = *labels_by_root.entry(root).or_insert_with(|| {
+                components.push(Component {
+                    label: components.len() as u32 + 1,
+                    voxel_count: 0,
+                    bounds: Bounds3i::empty(),
+                });
+                components.len() as u32
+            });
+            let component = &mut components[label as usize - 1];
+            component.voxel_count += 1;
+            component.bounds = component.bounds.expand(coord);
+            voxel_labels.push(label);
+        }
+        for component in &mut components {
+            component.bounds.max = component.bounds.max + Vec3i::one();
+        }
+
+        let labels = with_labels.then(|| {
+            let mut labels = VoxelVolume::with_config(self.config.clone());
+            for (&coord, &label) in coords.iter().zip(&voxel_labels) {
+                labels.set_voxel(coord, label);
+            }
+            labels.checkpoint();
+            labels
+        });
+        Components { components, labels }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::config_with_size;
+    use test_log::test;
+
+    #[test]
+    fn test_connected_components() {
+        let mut volume = VoxelVolume::<u32>::with_config(config_with_size(1.0));
+        // A bar crossing many leaves, a diagonal pair and a lone voxel
+        for x in -5..6 {
+            volume.set_voxel(Vec3i::new(x, 0, 0), 1);
+        }
+        volume.set_voxel(Vec3i::new(0, 3, 0), 1);
+        volume.set_voxel(Vec3i::new(1, 4, 0), 1);
+        volume.set_voxel(Vec3i::new(1, 5, 1), 1);
+        volume.set_voxel(Vec3i::new(10, 10, 10), 1);
+
+        let six = volume.connected_components(Connectivity::Six, false);
+        assert_eq!(six.components.len(), 5);
+        assert!(six.labels.is_none());
+        let bar = six.components.iter().find(|c| c.voxel_count == 11).unwrap();
+        assert_eq!(bar.bounds, Bounds3i::new(Vec3i::new(-5, 0, 0), Vec3i::new(6, 1, 1)));
+
+        assert_eq!(volume.connected_components(Connectivity::Eighteen, false).components.len(), 3);
+        let corners = volume.connected_components(Connectivity::TwentySix, true);
+        assert_eq!(corners.components.len(), 3);
+        let mut counts: Vec<usize> = corners.components.iter().map(|c| c.voxel_count).collect();
+        counts.sort();
+        assert_eq!(counts, vec![1, 3, 11]);
+
+        let labels = corners.labels.unwrap();
+        assert_eq!(labels.active_count(), volume.active_count());
+        assert_eq!(labels.get_voxel(Vec3i::new(-5, 0, 0)), labels.get_voxel(Vec3i::new(5, 0, 0)));
+        assert_eq!(labels.get_voxel(Vec3i::new(0, 3, 0)), labels.get_voxel(Vec3i::new(1, 5, 1)));
+        assert_ne!(labels.get_voxel(Vec3i::new(0, 3, 0)), labels.get_voxel(Vec3i::new(10, 10, 10)));
+        let lone = corners.components.iter().find(|c| c.voxel_count == 1).unwrap();
+        assert_eq!(*labels.get_voxel(Vec3i::new(10, 10, 10)), lone.label);
+    }
+}
@@ -0,0 +1,494 @@
+//! Constructive solid geometry between signed distance volumes
+use super::*;
+use std::collections::HashMap;
+
+/// Boolean operation combining two signed distances
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    /// Combine two voxels with a blend radius, keeping the attributes of the
+    /// input that contributes most to the result
+    fn combine<T: FromSignedDistance>(self, a: &T, b: &T, radius: f32) -> T {
+        let (da, db) = (a.signed_distance(), b.signed_distance());
+        let (distance, from_b) = match self {
+            Self::Union => (smooth_min(da, db, radius), db < da),
+            Self::Intersection => (-smooth_min(-da, -db, radius), db > da),
+            Self::Difference => (-smooth_min(-da, db, radius), -db > da),
+        };
+        if from_b { b } else { a }.with_signed_distance(distance)
+    }
+}
+
+/// Polynomial smooth minimum, blending over distances within `radius` of each other
+fn smooth_min(a: f32, b: f32, radius: f32) -> f32 {
+    if radius <= 0.0 {
+        return a.min(b);
+    }
+    let h = (radius - (a - b).abs()).max(0.0) / radius;
+    a.min(b) - h * h * radius * 0.25
+}
+
+impl<T: FromSignedDistance + Clone + 'static> VoxelVolume<T> {
+    /// Replace this volume by its union with another
+    ///
+    /// Returns the number of voxels changed. See [`VoxelVolume::union`].
+    pub fn union_with(&mut self, other: &Self) -> usize {
+        self.combine_with(other, CsgOperation::Union, 0.0)
+    }
+
+    /// Replace this volume by its intersection with another
+    ///
+    /// Returns the number of voxels changed.
+    pub fn intersect_with(&mut self, other: &Self) -> usize {
+        self.combine_with(other, CsgOperation::Intersection, 0.0)
+    }
+
+    /// Subtract another volume from this one
+    ///
+    /// Returns the number of voxels changed.
+    pub fn subtract_with(&mut self, other: &Self) -> usize {
+        self.combine_with(other, CsgOperation::Difference, 0.0)
+    }
+
+    /// Replace this volume by its union with another, rounding the seams over `radius`
+    ///
+    /// Returns the number of voxels changed.
+    pub fn smooth_union_with(&mut self, other: &Self, radius: f32) -> usize {
+        self.combine_with(other, CsgOperation::Union, radius)
+    }
+
+    /// Replace this volume by its intersection with another, rounding the seams over `radius`
+    ///
+    /// Returns the number of voxels changed.
+    pub fn smooth_intersect_with(&mut self, other: &Self, radius: f32) -> usize {
+        self.combine_with(other, CsgOperation::Intersection, radius)
+    }
+
+    /// Subtract another volume from this one, rounding the seams over `radius`
+    ///
+    /// Returns the number of voxels changed.
+    pub fn smooth_subtract_with(&mut self, other: &Self, radius: f32) -> usize {
+        self.combine_with(other, CsgOperation::Difference, radius)
+    }
+
+    /// Get the union of two volumes, the minimum of their distances
+    ///
+    /// Both volumes are treated as narrow bands: inactive voxels lie at the
+    /// background distance, on the side of the surface given by their stored
+    /// sign, or else by a ray cast to the band. The result keeps the voxels
+    /// inside the band, with the attributes of whichever input gives the
+    /// resulting distance.
+    ///
+    /// The result starts from a clone of this volume's tree. Its leaves
+    /// that the operation leaves unchanged, such as those outside the other
+    /// surface in a union, are not touched. Leaves of the other volume that
+    /// the result takes as they are are copied whole when both volumes share
+    /// a configuration; other voxels are written one by one.
+    ///
+    /// # Panics
+    /// Panics if the volumes have different voxel sizes
+    pub fn union(&self, other: &Self) -> Self {
+        let mut result = self.clone();
+        result.union_with(other);
+        result
+    }
+
+    /// Get the intersection of two volumes, the maximum of their distances
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut result = self.clone();
+        result.intersect_with(other);
+        result
+    }
+
+    /// Get this volume with another subtracted, the maximum of the first and negated second distance
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut result = self.clone();
+        result.subtract_with(other);
+        result
+    }
+
+    /// Get the union of two volumes with seams rounded over `radius`
+    ///
+    /// Uses a polynomial smooth minimum, which moves the surface by up to a
+    /// quarter of the radius where the inputs meet and leaves it unchanged
+    /// where their distances differ by more than the radius. The radius
+    /// should be smaller than the band width, as distances beyond the band
+    /// are clamped to the background.
+    pub fn smooth_union(&self, other: &Self, radius: f32) -> Self {
+        let mut result = self.clone();
+        result.smooth_union_with(other, radius);
+        result
+    }
+
+    /// Get the intersection of two volumes with seams rounded over `radius`
+    pub fn smooth_intersection(&self, other: &Self, radius: f32) -> Self {
+        let mut result = self.clone();
+        result.smooth_intersect_with(other, radius);
+        result
+    }
+
+    /// Get this volume with another subtracted, with seams rounded over `radius`
+    pub fn smooth_difference(&self, other: &Self, radius: f32) -> Self {
+        let mut result = self.clone();
+        result.smooth_subtract_with(other, radius);
+        result
+    }
+
+    fn combine_with(&mut self, other: &Self, operation: CsgOperation, radius: f32) -> usize {
+        assert_eq!(
+            self.config.leaf_voxel_size, other.config.leaf_voxel_size,
+            "CSG operations need volumes with the same voxel size"
+        );
+        let mut self_leaves: HashMap<Vec3i, Vec<Vec3i>> = HashMap::new();
+        for (coord, _) in self.active_voxels() {
+            self_leaves.entry(self.leaf_key(coord)).or_default().push(coord);
+        }
+        // Both volumes are split along the leaves of this one, whatever the other's configuration
+        let mut other_leaves: HashMap<Vec3i, Vec<Vec3i>> = HashMap::new();
+        for (coord, _) in other.active_voxels() {
+            other_leaves.entry(self.leaf_key(coord)).or_default().push(coord);
+        }
+        let self_band = NarrowBand::new(self);
+        let other_band = NarrowBand::new(other);
+
+        let mut changes = Vec::new();
+        let mut copies = Vec::new();
+        for (key, coords) in &self_leaves {
+            if other_leaves.contains_key(key) {
+                continue;
+            }
+            // Without active voxels the leaf lies entirely on one side of the other surface
+            let inside = other_band.is_inside(*key);
+            let unchanged = match operation {
+                CsgOperation::Union | CsgOperation::Difference => !inside,
+                CsgOperation::Intersection => inside,
+            };
+            if !(unchanged && radius <= 0.0) {
+                let b = other_band.far_value(inside);
+                for &coord in coords {
+                    changes.push((coord, operation.combine(self.get_voxel(coord), &b, radius)));
+                }
+            }
+        }
+        for (key, coords) in &other_leaves {
+            match self_leaves.get(key) {
+                Some(self_coords) => {
+                    let mut coords: Vec<Vec3i> = coords.iter().chain(self_coords).copied().collect();
+                    coords.sort_by_key(|c| (c.z, c.y, c.x));
+                    coords.dedup();
+                    for coord in coords {
+                        let a = self_band.value(coord);
+                        let b = other_band.value(coord);
+                        changes.push((coord, operation.combine(&a, &b, radius)));
+                    }
+                }
+                None => {
+                    let inside = self_band.is_inside(*key);
+                    let (unchanged, copied) = match operation {
+                        CsgOperation::Union => (inside, !inside),
+                        CsgOperation::Intersection => (!inside, inside),
+                        CsgOperation::Difference => (!inside, false),
+                    };
+                    if copied && radius <= 0.0 && self.config.volume_config_type == other.config.volume_config_type {
+                        // The result is the other volume's leaf as it is
+                        copies.push(*key);
+                    } else if !(unchanged && radius <= 0.0) {
+                        let a = self_band.far_value(inside);
+                        for &coord in coords {
+                            changes.push((coord, operation.combine(&a, other.get_voxel(coord), radius)));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Inactive voxels stored with a sign the operation flips no longer tell their side
+        let copied: HashSet<Vec3i> = copies.iter().copied().collect();
+        let written: HashSet<Vec3i> = changes.iter().map(|(coord, _)| *coord).collect();
+        let mut other_sides: HashMap<Vec3i, bool> = HashMap::new();
+        let mut cleared = Vec::new();
+        for (coord, voxel) in self.root.all_voxels() {
+            let key = self.leaf_key(coord);
+            if self_band.side(voxel).is_none() || voxel.is_active() || copied.contains(&key) || written.contains(&coord) {
+                continue;
+            }
+            let inside = voxel.signed_distance() < 0.0;
+            let other_inside = if other_leaves.contains_key(&key) {
+                other_band.is_inside(coord)
+            } else {
+                *other_sides.entry(key).or_insert_with(|| other_band.is_inside(key))
+            };
+            let result_inside = match operation {
+                CsgOperation::Union => inside || other_inside,
+                CsgOperation::Intersection => inside && other_inside,
+                CsgOperation::Difference => inside && !other_inside,
+            };
+            if result_inside != inside {
+                cleared.push(coord);
+            }
+        }
+
+        // An inactive result only replaces a stored voxel whose sign it contradicts
+        let background = T::background();
+        let flips_side = |stored: &T, value: &T| {
+            *stored != background && (stored.signed_distance() < 0.0) != (value.signed_distance() < 0.0)
+        };
+        let mut changed = 0;
+        for key in copies {
+            changed += other_leaves[&key].iter()
+                .filter(|&&coord| self.get_voxel(coord) != other.get_voxel(coord))
+                .count();
+            self.copy_leaf_from(other, key);
+        }
+        for (coord, value) in changes {
+            if value.is_active() {
+                if value != *self.get_voxel(coord) {
+                    self.set_voxel(coord, value);
+                    changed += 1;
+                }
+            } else if self.is_active(coord) || flips_side(self.get_voxel(coord), &value) {
+                self.remove_voxel(coord);
+                changed += 1;
+            }
+        }
+        for coord in cleared {
+            self.remove_voxel(coord);
+        }
+        changed
+    }
+}
+
+/// Narrow band of a volume, with the side of the surface of inactive voxels
+///
+/// Inactive voxels stored with a distance other than the background, such
+/// as the interior written by the rasterizer or by reinitialization, carry
+/// their side in their sign. For the others it is found from the first
+/// active or signed voxel along a ray in -x: no surface is crossed before
+/// reaching it since the band encloses the surface.
+struct NarrowBand<'a, T: VoxelData> {
+    volume: &'a VoxelVolume<T>,
+    /// Keys of the leaves holding active voxels, in the volume's own leaf size
+    leaves: HashSet<Vec3i>,
+    min_x: i32,
+    background: T,
+}
+
+impl<'a, T: FromSignedDistance + Clone + 'static> NarrowBand<'a, T> {
+    fn new(volume: &'a VoxelVolume<T>) -> Self {
+        let leaves: HashSet<Vec3i> = volume.active_voxels()
+            .map(|(coord, _)| volume.leaf_key(coord))
+            .collect();
+        let min_x = leaves.iter().map(|key| key.x).min().unwrap_or(0);
+        Self { volume, leaves, min_x, background: T::background() }
+    }
+
+    /// Side of the surface a voxel's own value tells, `None` for the unsigned background
+    fn side(&self, voxel: &T) -> Option<bool> {
+        (voxel.is_active() || *voxel != self.background).then(|| voxel.signed_distance() < 0.0)
+    }
+
+    /// Check if a voxel lies inside the surface
+    fn is_inside(&self, coord: Vec3i) -> bool {
+        let mut coord = coord;
+        while coord.x >= self.min_x {
+            if let Some(inside) = self.side(self.volume.get_voxel(coord)) {
+                return inside;
+            }
+            let key = self.volume.leaf_key(coord);
+            if self.leaves.contains(&key) {
+                coord.x -= 1;
+            } else {
+                coord.x = key.x - 1;
+            }
+        }
+        false
+    }
+
+    /// Value of an inactive voxel on the given side of the surface
+    fn far_value(&self, inside: bool) -> T {
+        let far = self.background.signed_distance().abs();
+        self.background.with_signed_distance(if inside { -far } else { far })
+    }
+
+    /// Value of a voxel, with inactive voxels at the background distance on their side of the surface
+    fn value(&self, coord: Vec3i) -> T {
+        if self.volume.is_active(coord) {
+            self.volume.get_voxel(coord).clone()
+        } else {
+            self.far_value(self.is_inside(coord))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::{SdfVoxel, config};
+    use test_log::test;
+
+    /// Signed distance voxel painted with a material, kept through operations
+    #[derive(Debug, Clone, PartialEq)]
+    struct Painted(f32, u32);
+
+    impl VoxelData for Painted {
+        fn is_active(&self) -> bool { SdfVoxel(self.0).is_active() }
+        fn background() -> Self { Painted(SdfVoxel::BAND, 0) }
+    }
+
+    impl SignedDistance for Painted {
+        fn signed_distance(&self) -> f32 { self.0 }
+    }
+
+    impl FromSignedDistance for Painted {
+        fn from_signed_distance(distance: f32) -> Self { Painted(distance, 0) }
+        fn with_signed_distance(&self, distance: f32) -> Self { Painted(distance, self.1) }
+    }
+
+    fn sphere(center: Vec3f, radius: f32, material: u32) -> VoxelVolume<Painted> {
+        sphere_in(VolumeConfigType::Hashx2x1, center, radius, material)
+    }
+
+    fn sphere_in(volume_config_type: VolumeConfigType, center: Vec3f, radius: f32, material: u32) -> VoxelVolume<Painted> {
+        let mut volume = VoxelVolume::with_config(VolumeConfig { volume_config_type, ..config() });
+        volume.fill_bounds(Vec3f::new(-2.0, -2.0, -2.0), Vec3f::new(2.0, 2.0, 2.0), |p| {
+            Some(Painted((p - center).length() - radius, material)).filter(|v| v.is_active())
+        });
+        volume
+    }
+
+    /// Sphere with its inside stored at `-SdfVoxel::BAND`, as the rasterizer writes it
+    fn filled_sphere(center: Vec3f, radius: f32, material: u32) -> VoxelVolume<Painted> {
+        let mut volume = VoxelVolume::with_config(config());
+        volume.fill_bounds(Vec3f::new(-2.0, -2.0, -2.0), Vec3f::new(2.0, 2.0, 2.0), |p| {
+            let d = (p - center).length() - radius;
+            (d < SdfVoxel::BAND).then(|| Painted(d.max(-SdfVoxel::BAND), material))
+        });
+        volume
+    }
+
+    /// Check the band, and that inactive voxels stored with a sign are on the expected side
+    fn assert_sides(volume: &VoxelVolume<Painted>, expected: impl Fn(Vec3f) -> f32) {
+        for coord in Bounds3i::new(Vec3i::new(-20, -20, -20), Vec3i::new(20, 20, 20)).coords() {
+            let d = expected(coord.as_vec3f().scale(0.1));
+            let value = volume.get_voxel(coord);
+            if d.abs() < SdfVoxel::BAND {
+                assert!((value.0 - d).abs() < 1e-5, "{:?}: {:?} != {}", coord, value, d);
+            } else {
+                assert!(*value == Painted::background() || (value.0 < 0.0) == (d < 0.0), "{:?}: {:?} != {}", coord, value, d);
+            }
+        }
+    }
+
+    fn assert_matches(volume: &VoxelVolume<Painted>, expected: impl Fn(Vec3f) -> f32) {
+        let reference = VoxelVolume::<Painted>::with_config(volume.config.clone());
+        for coord in Bounds3i::new(Vec3i::new(-20, -20, -20), Vec3i::new(20, 20, 20)).coords() {
+            let d = expected(coord.as_vec3f().scale(0.1));
+            let value = volume.get_voxel(coord);
+            if d.abs() < SdfVoxel::BAND {
+                assert!((value.0 - d).abs() < 1e-5, "{:?}: {:?} != {}", coord, value, d);
+            } else {
+                assert!(!volume.is_active(coord) && reference.get_voxel(coord) == value, "{:?}: {:?}", coord, value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sharp_operations() {
+        let a_center = Vec3f::new(-0.4, 0.0, 0.0);
+        let b_center = Vec3f::new(0.5, 0.0, 0.0);
+        let a = sphere(a_center, 1.0, 1);
+        let b = sphere(b_center, 0.8, 2);
+        let da = move |p: Vec3f| (p - a_center).length() - 1.0;
+        let db = move |p: Vec3f| (p - b_center).length() - 0.8;
+
+        let union = a.union(&b);
+        assert_matches(&union, |p| da(p).min(db(p)));
+        assert_eq!(union.get_voxel(Vec3i::new(-14, 0, 0)).1, 1);
+        assert_eq!(union.get_voxel(Vec3i::new(13, 0, 0)).1, 2);
+        assert_matches(&a.intersection(&b), |p| da(p).max(db(p)));
+        assert_matches(&a.difference(&b), |p| da(p).max(-db(p)));
+        // The inputs are left untouched
+        assert_matches(&a, da);
+
+        let mut in_place = a.clone();
+        in_place.checkpoint();
+        assert!(in_place.union_with(&b) > 0);
+        assert!(in_place.is_dirty());
+        assert_eq!(in_place.union_with(&b), 0);
+    }
+
+    #[test]
+    fn test_mixed_configurations() {
+        let a_center = Vec3f::new(-0.4, 0.0, 0.0);
+        let b_center = Vec3f::new(0.5, 0.0, 0.0);
+        let a = sphere_in(VolumeConfigType::Hashx2x1, a_center, 1.0, 1);
+        let b = sphere_in(VolumeConfigType::Hashx5x4, b_center, 0.8, 2);
+        let da = move |p: Vec3f| (p - a_center).length() - 1.0;
+        let db = move |p: Vec3f| (p - b_center).length() - 0.8;
+
+        assert_matches(&a.intersection(&b), |p| da(p).max(db(p)));
+        assert_matches(&a.union(&b), |p| da(p).min(db(p)));
+        assert_matches(&b.difference(&a), |p| db(p).max(-da(p)));
+    }
+
+    #[test]
+    fn test_smooth_union() {
+        let a = sphere(Vec3f::new(-0.6, 0.0, 0.0), 0.7, 1);
+        let b = sphere(Vec3f::new(0.6, 0.0, 0.0), 0.7, 2);
+        let sharp = a.union(&b);
+        let smooth = a.smooth_union(&b, 0.2);
+        // The blend fills the neck between the spheres and leaves the far sides alone
+        let neck = Vec3i::new(0, 4, 0);
+        assert!(smooth.get_voxel(neck).0 < sharp.get_voxel(neck).0 - 0.04);
+        let far_side = Vec3i::new(-13, 0, 0);
+        assert_eq!(smooth.get_voxel(far_side), sharp.get_voxel(far_side));
+        // Sharp results are reproduced with a zero radius
+        assert_matches(&a.smooth_union(&b, 0.0), |p| {
+            ((p - Vec3f::new(-0.6, 0.0, 0.0)).length() - 0.7).min((p - Vec3f::new(0.6, 0.0, 0.0)).length() - 0.7)
+        });
+        assert!(a.smooth_difference(&b, 0.2).active_count() > 0);
+        assert!(a.smooth_intersection(&b, 0.2).get_voxel(Vec3i::zero()).0 < 0.3);
+    }
+
+    #[test]
+    fn test_copies_untouched_leaves() {
+        let a = sphere(Vec3f::new(-1.0, 0.0, 0.0), 0.5, 1);
+        let b = filled_sphere(Vec3f::new(1.0, 0.0, 0.0), 0.5, 2);
+        let union = a.union(&b);
+        // Leaves of the other volume come whole, with their stored inside
+        let leaves: HashSet<Vec3i> = b.active_voxels().map(|(coord, _)| b.leaf_key(coord)).collect();
+        let mut stored_inside = 0;
+        for (coord, value) in b.root.all_voxels() {
+            if leaves.contains(&b.leaf_key(coord)) {
+                assert_eq!(union.get_voxel(coord), value, "{:?}", coord);
+                stored_inside += usize::from(!value.is_active() && value.0 < 0.0);
+            }
+        }
+        assert!(stored_inside > 0);
+        assert_eq!(a.intersection(&b).active_count(), 0);
+    }
+
+    #[test]
+    fn test_stored_signs() {
+        let a_center = Vec3f::new(-0.2, 0.0, 0.0);
+        let b_center = Vec3f::new(0.3, 0.0, 0.0);
+        let a = filled_sphere(a_center, 1.2, 1);
+        let b = filled_sphere(b_center, 0.5, 2);
+        let da = move |p: Vec3f| (p - a_center).length() - 1.2;
+        let db = move |p: Vec3f| (p - b_center).length() - 0.5;
+        assert_sides(&a.union(&b), |p| da(p).min(db(p)));
+        assert_sides(&a.intersection(&b), |p| da(p).max(db(p)));
+        // The inside of the hole no longer reads as inside the result
+        let hollow = a.difference(&b);
+        assert_sides(&hollow, |p| da(p).max(-db(p)));
+        assert_sides(&hollow.union(&sphere(b_center, 0.2, 3)), |p| {
+            da(p).max(-db(p)).min((p - b_center).length() - 0.2)
+        });
+        assert_sides(&b.difference(&a), |p| db(p).max(-da(p)));
+    }
+}
@@ -0,0 +1,192 @@
+//! Curvature of signed distance volumes and mean curvature flow
+use super::*;
+
+/// Gradient and Hessian of the distance field at a voxel, by central differences
+fn derivatives<T: SignedDistance + Clone + 'static>(volume: &VoxelVolume<T>, coord: Vec3i) -> ([f32; 3], [[f32; 3]; 3]) {
+    let h = volume.config.leaf_voxel_size;
+    let phi = |offset: [i32; 3]| {
+        volume.get_voxel(coord + Vec3i::new(offset[0], offset[1], offset[2])).signed_distance()
+    };
+    let unit = |axis: usize, step: i32| {
+        let mut offset = [0; 3];
+        offset[axis] = step;
+        offset
+    };
+    let center = phi([0; 3]);
+    let mut gradient = [0.0; 3];
+    let mut hessian = [[0.0; 3]; 3];
+    for i in 0..3 {
+        let (below, above) = (phi(unit(i, -1)), phi(unit(i, 1)));
+        gradient[i] = (above - below) / (2.0 * h);
+        hessian[i][i] = (above - 2.0 * center + below) / (h * h);
+        for j in i + 1..3 {
+            let corner = |si: i32, sj: i32| {
+                let mut offset = unit(i, si);
+                offset[j] = sj;
+                phi(offset)
+            };
+            let mixed = (corner(1, 1) - corner(1, -1) - corner(-1, 1) + corner(-1, -1)) / (4.0 * h * h);
+            hessian[i][j] = mixed;
+            hessian[j][i] = mixed;
+        }
+    }
+    (gradient, hessian)
+}
+
+/// Mean and Gaussian curvature of the level set through a voxel
+fn curvatures(gradient: [f32; 3], hessian: [[f32; 3]; 3]) -> (f32, f32) {
+    let g = gradient;
+    let norm_squared = g[0] * g[0] + g[1] * g[1] + g[2] * g[2];
+    if norm_squared <= f32::EPSILON {
+        return (0.0, 0.0);
+    }
+    let m = hessian;
+    let quadratic = (0..3).map(|i| (0..3).map(|j| g[i] * m[i][j] * g[j]).sum::<f32>()).sum::<f32>();
+    let trace = m[0][0] + m[1][1] + m[2][2];
+    let mean = (norm_squared * trace - quadratic) / (2.0 * norm_squared * norm_squared.sqrt());
+
+    // g^T adj(H) g, with adj(H) the adjugate of the Hessian
+    let adjugate = [
+        [m[1][1] * m[2][2] - m[1][2] * m[2][1], m[0][2] * m[2][1] - m[0][1] * m[2][2], m[0][1] * m[1][2] - m[0][2] * m[1][1]],
+        [m[1][2] * m[2][0] - m[1][0] * m[2][2], m[0][0] * m[2][2] - m[0][2] * m[2][0], m[0][2] * m[1][0] - m[0][0] * m[1][2]],
+        [m[1][0] * m[2][1] - m[1][1] * m[2][0], m[0][1] * m[2][0] - m[0][0] * m[2][1], m[0][0] * m[1][1] - m[0][1] * m[1][0]],
+    ];
+    let gaussian = (0..3).map(|i| (0..3).map(|j| g[i] * adjugate[i][j] * g[j]).sum::<f32>()).sum::<f32>()
+        / (norm_squared * norm_squared);
+    (mean, gaussian)
+}
+
+impl<T: SignedDistance + Clone + 'static> VoxelVolume<T> {
+    /// Sample the mean curvature of the level sets at a world-space position
+    ///
+    /// The mean of the principal curvatures, positive where the surface is
+    /// convex: `1 / r` on a sphere of radius `r`. Computed at the voxels by
+    /// central differences and interpolated trilinearly, so the band must
+    /// extend two voxels past the position.
+    pub fn mean_curvature(&self, position: Vec3f) -> f32 {
+        self.trilinear_weights(position).iter()
+            .map(|&(coord, weight)| {
+                let (gradient, hessian) = derivatives(self, coord);
+                curvatures(gradient, hessian).0 * weight
+            })
+            .sum()
+    }
+
+    /// Sample the Gaussian curvature of the level sets at a world-space position
+    ///
+    /// The product of the principal curvatures: `1 / r^2` on a sphere of
+    /// radius `r`, negative at saddles. See [`VoxelVolume::mean_curvature`].
+    pub fn gaussian_curvature(&self, position: Vec3f) -> f32 {
+        self.trilinear_weights(position).iter()
+            .map(|&(coord, weight)| {
+                let (gradient, hessian) = derivatives(self, coord);
+                curvatures(gradient, hessian).1 * weight
+            })
+            .sum()
+    }
+}
+
+impl<T: FromSignedDistance + Clone + 'static> VoxelVolume<T> {
+    /// Smooth the surface by mean curvature flow
+    ///
+    /// Each iteration moves the level set along its normal by its mean
+    /// curvature for the largest stable explicit time step, a sixth of the
+    /// voxel size squared, which removes bumps at the scale of a few voxels
+    /// quickly and larger features slowly, then rebuilds the narrow band.
+    /// Curvatures are clamped to what a voxel-sized feature can have.
+    ///
+    /// The flow shrinks convex shapes, so with `preserve_volume` the surface
+    /// is moved back along its normal after each iteration by the volume
+    /// lost divided by the area, both estimated from the field.
+    ///
+    /// Returns the number of active voxels afterwards.
+    pub fn mean_curvature_flow(&mut self, iterations: usize, preserve_volume: bool) -> usize {
+        let h = self.config.leaf_voxel_size;
+        let band = T::background().signed_distance().abs().max(h);
+        let dt = h * h / 6.0;
+        for _ in 0..iterations {
+            let before = preserve_volume.then(|| self.estimate_mass_properties(0.0));
+
+            let updates: Vec<(Vec3i, T)> = self.active_voxels()
+                .map(|(coord, value)| {
+                    let (gradient, hessian) = derivatives(self, coord);
+                    // Twice the mean curvature is the divergence of the normal
+                    let speed = (2.0 * curvatures(gradient, hessian).0).clamp(-2.0 / h, 2.0 / h);
+                    (coord, value.with_signed_distance(value.signed_distance() + dt * speed))
+                })
+                .collect();
+            for (coord, value) in updates {
+                self.set_voxel(coord, value);
+            }
+            self.reinitialize(band / h);
+
+            if let Some(before) = before {
+                let after = self.estimate_mass_properties(0.0);
+                if after.surface_area > 0.0 {
+                    let shift = (before.volume - after.volume) / after.surface_area;
+                    let shifted: Vec<(Vec3i, T)> = self.active_voxels()
+                        .map(|(coord, value)| (coord, value.with_signed_distance(value.signed_distance() - shift)))
+                        .collect();
+                    for (coord, value) in shifted {
+                        self.set_voxel(coord, value);
+                    }
+                }
+            }
+        }
+        self.reinitialize(band / h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::{SdfVoxel, config};
+    use crate::primitives::{PrimitiveRasterizer, Sphere, Torus};
+    use test_log::test;
+
+    #[test]
+    fn test_curvatures() {
+        let sphere: VoxelVolume<SdfVoxel> = PrimitiveRasterizer::new(&Sphere::new(0.8)).build(config()).unwrap();
+        let point = Vec3f::new(0.3, -0.4, 0.5).normalize().scale(0.8);
+        assert!((sphere.mean_curvature(point) - 1.25).abs() < 0.05, "{}", sphere.mean_curvature(point));
+        assert!((sphere.gaussian_curvature(point) - 1.5625).abs() < 0.15, "{}", sphere.gaussian_curvature(point));
+
+        // Saddle on the inner side of a torus: principal curvatures 1 / 0.4 and -1 / 1.2
+        let torus: VoxelVolume<SdfVoxel> = PrimitiveRasterizer::new(&Torus::new(1.6, 0.4)).build(config()).unwrap();
+        let inner = Vec3f::new(1.2, 0.0, 0.0);
+        assert!((torus.mean_curvature(inner) - 0.5 * (2.5 - 1.0 / 1.2)).abs() < 0.1, "{}", torus.mean_curvature(inner));
+        assert!(torus.gaussian_curvature(inner) < -1.5, "{}", torus.gaussian_curvature(inner));
+    }
+
+    #[test]
+    fn test_mean_curvature_flow() {
+        let noisy = || {
+            let mut volume = VoxelVolume::<SdfVoxel>::with_config(config());
+            volume.fill_bounds(Vec3f::new(-1.5, -1.5, -1.5), Vec3f::new(1.5, 1.5, 1.5), |p| {
+                let bumps = 0.04 * (11.0 * p.x).sin() * (13.0 * p.y).sin() * (7.0 * p.z).cos();
+                Some(SdfVoxel(p.length() - 1.0 + bumps)).filter(|v| v.is_active())
+            });
+            volume.reinitialize(3.0);
+            volume
+        };
+        let roughness = |volume: &VoxelVolume<SdfVoxel>| {
+            (0..200).map(|i| {
+                let (theta, phi) = (i as f32 * 0.7, i as f32 * 0.31);
+                let point = Vec3f::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                volume.sample_signed_distance(point)
+            }).map(|d| d * d).sum::<f32>()
+        };
+
+        let mut preserved = noisy();
+        let volume_before = preserved.estimate_mass_properties(0.0).volume;
+        let roughness_before = roughness(&preserved);
+        preserved.mean_curvature_flow(5, true);
+        assert!(roughness(&preserved) < 0.5 * roughness_before, "{} -> {}", roughness_before, roughness(&preserved));
+        let volume_preserved = preserved.estimate_mass_properties(0.0).volume;
+        assert!((volume_preserved - volume_before).abs() < 0.02 * volume_before, "{} -> {}", volume_before, volume_preserved);
+
+        let mut shrunk = noisy();
+        shrunk.mean_curvature_flow(5, false);
+        assert!(shrunk.estimate_mass_properties(0.0).volume < volume_preserved);
+    }
+}
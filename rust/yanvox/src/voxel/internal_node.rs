@@ -3,6 +3,7 @@
 use crate::math::{Vec3i, Bounds3i};
 use crate::voxel::{VoxelData, NodeTrait, ChildNodeTrait, NodeDiagnostics, NodeType};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Internal node that stores child nodes
 /// The const generic LOG2 specifies the power of 2 for the number of children in each direction
@@ -15,6 +16,8 @@ pub struct InternalNode<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> {
     /// Index corresponds to local coordinate within this internal node's bounds
     /// None represents no child node (background value)
     data: Vec<Option<N>>,
+    /// Constant values of child blocks stored without a child node, by index
+    tiles: BTreeMap<usize, T>,
     /// Origin of this internal node
     pub origin: Vec3i,
 
@@ -30,6 +33,7 @@ impl<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> InternalNode<T, N, L
         Self {
             background_value,
             data: (0..total_size as usize).map(|_| None).collect(),
+            tiles: BTreeMap::new(),
             origin,
             level,
         }
@@ -137,12 +141,53 @@ impl<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> InternalNode<T, N, L
         for child in &mut self.data {
             *child = None;
         }
+        self.tiles.clear();
     }
 
     /// Get memory usage in bytes
     pub fn memory_usage(&self) -> usize {
         std::mem::size_of::<Self>() + 
-        self.data.capacity() * std::mem::size_of::<Option<N>>()
+        self.data.capacity() * std::mem::size_of::<Option<N>>() +
+        self.tiles.len() * std::mem::size_of::<(usize, T)>()
+    }
+
+    /// Get the number of voxels in a child block
+    fn child_volume() -> usize {
+        1 << (3 * <N as ChildNodeTrait::<_>>::log2_cum())
+    }
+
+    /// Get the bounds of the child block at an index
+    fn child_bounds(&self, index: usize) -> Bounds3i {
+        let origin = self.index_to_coord(index);
+        let size = 1 << <N as ChildNodeTrait::<_>>::log2_cum();
+        Bounds3i::new(origin, origin + Vec3i::new(size, size, size))
+    }
+
+    /// Get the tile covering a coordinate, if any
+    fn find_tile(&self, coord: Vec3i) -> Option<&T> {
+        self.tiles.get(&self.coord_to_index(coord)?)
+    }
+
+    /// Iterate over the voxels of the tiles whose value passes a filter
+    fn tile_voxels<'a>(&'a self, filter: impl Fn(&T) -> bool + 'a) -> impl Iterator<Item = (Vec3i, &'a T)> + 'a {
+        self.tiles.iter()
+            .filter(move |(_, value)| filter(value))
+            .flat_map(move |(&index, value)| self.child_bounds(index).coords().map(move |coord| (coord, value)))
+    }
+
+    /// Internal method to get the child node for a coordinate, creating it
+    /// from the tile or background covering the coordinate
+    fn child_or_create(&mut self, coord: Vec3i) -> &mut N {
+        let index = self.coord_to_index(coord).expect("Child key should be within bounds");
+        if self.data[index].is_none() {
+            let tile = self.tiles.remove(&index);
+            let bounds = self.child_bounds(index);
+            let child = self.create_child(coord);
+            if let Some(tile) = tile {
+                child.fill(bounds, tile);
+            }
+        }
+        self.data[index].as_mut().unwrap()
     }
 
     /// Internal method to create a new child node for a coordinate
@@ -181,6 +226,10 @@ impl<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> NodeTrait<T> for Int
         <Self as ChildNodeTrait::<T>>::log2_cum()
     }
 
+    fn leaf_log2(&self) -> u32 {
+        <N as ChildNodeTrait::<T>>::leaf_log2()
+    }
+
     fn bounds(&self) -> Bounds3i {
         Bounds3i::new(self.origin, self.origin + Self::calculate_dimensions())
     }
@@ -194,22 +243,25 @@ impl<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> NodeTrait<T> for Int
     }
 
     fn active_count(&self) -> usize {
+        let tiles = self.tiles.values().filter(|value| value.is_active()).count();
         self.data.iter()
             .filter_map(|child| child.as_ref())
             .map(|child| child.active_count())
-            .sum()
+            .sum::<usize>() + tiles * Self::child_volume()
     }
 
     fn total_count(&self) -> usize {
         self.data.iter()
             .filter_map(|child| child.as_ref())
             .map(|child| child.total_count())
-            .sum()
+            .sum::<usize>() + self.tiles.len() * Self::child_volume()
     }
 
     fn get_voxel(&self, coord: Vec3i) -> &T {
         if let Some(child) = self.find_child(coord) {
             child.get_voxel(coord)
+        } else if let Some(tile) = self.find_tile(coord) {
+            tile
         } else {
             &self.background_value
         }
@@ -219,6 +271,14 @@ impl<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> NodeTrait<T> for Int
         if let Some(child) = self.find_child_mut(coord) {
             // Internal nodes delegate to children
             child.set_voxel(coord, value)
+        } else if let Some(tile) = self.find_tile(coord) {
+            if *tile == value {
+                // Do nothing if the tile already holds the value
+                Some(value)
+            } else {
+                // Expand the tile into a child holding its value
+                self.child_or_create(coord).set_voxel(coord, value)
+            }
         } else if self.background_value != value {
             // Create a new child if the background value is different
             let child = self.create_child(coord);
@@ -230,8 +290,8 @@ impl<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> NodeTrait<T> for Int
     }
 
     fn remove_voxel(&mut self, coord: Vec3i) -> Option<T> {
-        if let Some(child) = self.find_child_mut(coord) {
-            child.remove_voxel(coord)
+        if self.find_child(coord).is_some() || self.find_tile(coord).is_some() {
+            self.child_or_create(coord).remove_voxel(coord)
         } else {
             None
         }
@@ -243,6 +303,7 @@ impl<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> NodeTrait<T> for Int
             self.data.iter()
                 .filter_map(|child| child.as_ref())
                 .flat_map(|child| child.active_voxels())
+                .chain(self.tile_voxels(|value| value.is_active()))
         )
     }
 
@@ -251,9 +312,59 @@ impl<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> NodeTrait<T> for Int
             self.data.iter()
                 .filter_map(|child| child.as_ref())
                 .flat_map(|child| child.all_voxels())
+                .chain(self.tile_voxels(|_| true))
         )
     }
 
+    fn active_voxels_in(&self, bounds: Bounds3i) -> Box<dyn Iterator<Item = (Vec3i, &T)> + '_> {
+        let tiles = self.tiles.iter()
+            .filter(move |&(&index, value)| value.is_active() && self.child_bounds(index).intersects(bounds))
+            .flat_map(move |(&index, value)| {
+                self.child_bounds(index).intersection(bounds).coords().map(move |coord| (coord, value))
+            });
+        Box::new(
+            self.data.iter()
+                .filter_map(|child| child.as_ref())
+                .filter(move |child| child.bounds().intersects(bounds))
+                .flat_map(move |child| child.active_voxels_in(bounds))
+                .chain(tiles)
+        )
+    }
+
+    fn fill(&mut self, bounds: Bounds3i, value: T) {
+        let clipped = bounds.intersection(self.bounds());
+        if clipped.is_empty() {
+            return;
+        }
+        let step = 1i32 << <N as ChildNodeTrait::<_>>::log2_cum();
+        let first = <N as ChildNodeTrait::<_>>::key(clipped.min);
+        for z in (first.z..clipped.max.z).step_by(step as usize) {
+            for y in (first.y..clipped.max.y).step_by(step as usize) {
+                for x in (first.x..clipped.max.x).step_by(step as usize) {
+                    let origin = Vec3i::new(x, y, z);
+                    let index = self.coord_to_index(origin).expect("Child key should be within bounds");
+                    if bounds.contains_bounds(self.child_bounds(index)) {
+                        // Blocks covered by the bounds become a tile, or background
+                        self.data[index] = None;
+                        if value == self.background_value {
+                            self.tiles.remove(&index);
+                        } else {
+                            self.tiles.insert(index, value.clone());
+                        }
+                        continue;
+                    }
+                    let constant = match self.data[index] {
+                        Some(_) => None,
+                        None => Some(self.tiles.get(&index).unwrap_or(&self.background_value)),
+                    };
+                    if constant != Some(&value) {
+                        self.child_or_create(origin).fill(bounds, value.clone());
+                    }
+                }
+            }
+        }
+    }
+
     /*
     // Background value operations
     fn background_value(&self) -> &T {
@@ -274,9 +385,27 @@ impl<T: VoxelData, N: ChildNodeTrait<T>, const LOG2: usize> ChildNodeTrait<T> fo
         LOG2 as u32 + <N as ChildNodeTrait::<_>>::log2_cum()
     }
 
+    /// Returns the log2 of the leaf node size below this internal node
+    fn leaf_log2() -> u32 {
+        <N as ChildNodeTrait::<_>>::leaf_log2()
+    }
+
     fn create(coord: Vec3i, level: u32, background_value: T) -> Self {
         Self::from_level_and_coord(level, coord, background_value)
     }
+
+    fn copy_leaf(&mut self, other: &Self, coord: Vec3i) {
+        let index = self.coord_to_index(coord).expect("Leaf should be within bounds");
+        match &other.data[index] {
+            Some(child) => self.child_or_create(coord).copy_leaf(child, coord),
+            None => {
+                let value = other.tiles.get(&index).unwrap_or(&other.background_value).clone();
+                let size = 1 << <N as ChildNodeTrait::<T>>::leaf_log2();
+                let key = Vec3i::new(coord.x & !(size - 1), coord.y & !(size - 1), coord.z & !(size - 1));
+                self.fill(Bounds3i::new(key, key + Vec3i::new(size, size, size)), value);
+            }
+        }
+    }
 }
 
 // Implementation of NodeDiagnostics for InternalNode
@@ -365,6 +494,37 @@ mod tests {
         assert_eq!(internal.coord_to_index(Vec3i::new(100, 120, 150)), None);
     }
 
+    #[test]
+    fn test_tiles() {
+        let mut internal = InternalNode::<f32, LeafNode<f32, 2>, 3>::from_level_and_coord(5, Vec3i::zero(), 0.0);
+
+        // Whole leaves become tiles, partially covered leaves are written voxel by voxel
+        internal.fill(Bounds3i::new(Vec3i::new(2, 0, 0), Vec3i::new(12, 4, 4)), -1.0);
+        assert_eq!(internal.child_count(), 1);
+        assert_eq!(internal.tiles.len(), 2);
+        assert_eq!(internal.active_count(), 10 * 4 * 4);
+        assert_eq!(internal.active_voxels().count(), 10 * 4 * 4);
+        assert_eq!(internal.active_voxels_in(Bounds3i::new(Vec3i::new(5, 1, 1), Vec3i::new(7, 2, 2))).count(), 2);
+        assert_eq!(*internal.get_voxel(Vec3i::new(9, 3, 3)), -1.0);
+        assert_eq!(*internal.get_voxel(Vec3i::new(1, 3, 3)), 0.0);
+
+        // Writing a different value expands the tile into a leaf
+        assert_eq!(internal.set_voxel(Vec3i::new(9, 3, 3), 2.0), Some(-1.0));
+        assert_eq!(internal.set_voxel(Vec3i::new(5, 0, 0), -1.0), Some(-1.0));
+        assert_eq!(internal.child_count(), 2);
+        assert_eq!(internal.tiles.len(), 1);
+        assert_eq!(*internal.get_voxel(Vec3i::new(9, 3, 3)), 2.0);
+        assert_eq!(*internal.get_voxel(Vec3i::new(8, 3, 3)), -1.0);
+        assert_eq!(internal.remove_voxel(Vec3i::new(4, 0, 0)), Some(-1.0));
+        assert_eq!(internal.tiles.len(), 0);
+        assert_eq!(internal.active_count(), 10 * 4 * 4 - 1);
+
+        // Filling with the background clears the blocks it covers
+        internal.fill(Bounds3i::new(Vec3i::zero(), Vec3i::new(32, 32, 32)), 0.0);
+        assert_eq!(internal.child_count(), 0);
+        assert_eq!(internal.active_count(), 0);
+    }
+
     #[test]
     fn convert_index_to_coord() {
         let internal = InternalNode::<f32, LeafNode<f32, 2>, 3>::from_level_and_coord(5, Vec3i::zero(), 0.0);
@@ -164,6 +164,10 @@ impl<T: VoxelData, const LOG2: usize> NodeTrait<T> for LeafNode<T, LOG2> {
       <Self as ChildNodeTrait::<T>>::log2_cum()
     }
 
+    fn leaf_log2(&self) -> u32 {
+        LOG2 as u32
+    }
+
     fn bounds(&self) -> Bounds3i {
         Bounds3i::new(self.origin, self.origin + Self::calculate_dimensions())
     }
@@ -278,9 +282,18 @@ impl<T: VoxelData, const LOG2: usize> ChildNodeTrait<T> for LeafNode<T, LOG2> {
       LOG2 as u32
     }
 
+    /// Returns the log2 of this leaf's size, as leaves are the bottom of the hierarchy
+    fn leaf_log2() -> u32 {
+        LOG2 as u32
+    }
+
     fn create(coord: Vec3i, level: u32, background_value: T) -> Self {
         Self::from_level_and_coord(level, coord, background_value)
     }
+
+    fn copy_leaf(&mut self, other: &Self, _coord: Vec3i) {
+        self.data.clone_from(&other.data);
+    }
 }
 
 // Implementation of NodeDiagnostics for LeafNode
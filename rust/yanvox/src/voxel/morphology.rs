@@ -0,0 +1,105 @@
+//! Offsetting and morphological operations on signed distance volumes
+use super::*;
+use super::reinitialize::Seeding;
+
+impl<T: FromSignedDistance + Clone + 'static> VoxelVolume<T> {
+    /// Move the surface outwards by a distance, inwards if negative
+    ///
+    /// Distances are first propagated far enough from the current surface
+    /// to cover the offset one, then shifted and written back over the band
+    /// of the volume, the background distance, activating voxels the
+    /// surface moved towards and clearing those it left. Fast marching is
+    /// first order, so for offsets of many voxels the surface may move by up
+    /// to about a voxel more than asked on curved parts.
+    ///
+    /// Returns the number of active voxels after the offset.
+    pub fn offset(&mut self, distance: f32) -> usize {
+        let h = self.config.leaf_voxel_size;
+        let band = T::background().signed_distance().abs().max(h);
+        let mut field = self.march_distances(band + distance.abs() + h, Seeding::Crossings);
+        for value in field.distances.values_mut() {
+            *value -= distance;
+        }
+        self.write_distance_band(&field, band);
+        self.active_count()
+    }
+
+    /// Grow the shape by a distance, see [`VoxelVolume::offset`]
+    pub fn dilate(&mut self, distance: f32) -> usize {
+        self.offset(distance.abs())
+    }
+
+    /// Shrink the shape by a distance, see [`VoxelVolume::offset`]
+    pub fn erode(&mut self, distance: f32) -> usize {
+        self.offset(-distance.abs())
+    }
+
+    /// Morphological opening: erode then dilate by a radius
+    ///
+    /// Removes features thinner than twice the radius and rounds convex
+    /// edges, leaving the rest of the shape in place.
+    pub fn open(&mut self, radius: f32) -> usize {
+        self.erode(radius);
+        self.dilate(radius)
+    }
+
+    /// Morphological closing: dilate then erode by a radius
+    ///
+    /// Fills gaps and holes narrower than twice the radius and rounds
+    /// concave edges, leaving the rest of the shape in place.
+    pub fn close(&mut self, radius: f32) -> usize {
+        self.dilate(radius);
+        self.erode(radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::{SdfVoxel, config};
+    use crate::primitives::{Primitive, PrimitiveRasterizer, Sphere, Capsule};
+    use test_log::test;
+
+    fn rasterize(shape: &impl Primitive) -> VoxelVolume<SdfVoxel> {
+        PrimitiveRasterizer::new(shape).build(config()).unwrap()
+    }
+
+    #[test]
+    fn test_offset_sphere() {
+        let mut volume = rasterize(&Sphere::new(1.0));
+        volume.offset(0.5);
+        assert!((volume.sample_signed_distance(Vec3f::new(1.5, 0.0, 0.0))).abs() < 0.02);
+        assert!((volume.sample_signed_distance(Vec3f::new(0.0, -1.4, 0.0)) + 0.1).abs() < 0.03);
+        assert!(!volume.is_active(Vec3i::new(10, 0, 0)));
+
+        volume.offset(-0.8);
+        assert!((volume.sample_signed_distance(Vec3f::new(0.0, 0.0, 0.7))).abs() < 0.03);
+        assert!(!volume.is_active(Vec3i::new(0, 0, 15)));
+        // Voxels left behind inside are kept inactive with a negative distance
+        assert!(volume.get_voxel(Vec3i::new(0, 0, 3)).0 < 0.0);
+        let mut worst: f32 = 0.0;
+        for (coord, value) in volume.active_voxels() {
+            worst = worst.max((value.0 - (coord.as_vec3f().scale(0.1).length() - 0.7)).abs());
+        }
+        assert!(worst < 0.1, "worst error {}", worst);
+    }
+
+    #[test]
+    fn test_open_and_close() {
+        // A sphere with a thin rod sticking out
+        let mut rod = rasterize(&Sphere::new(0.8));
+        rod.union_with(&rasterize(&Capsule::new(Vec3f::zero(), Vec3f::new(1.6, 0.0, 0.0), 0.15)));
+        assert!(rod.sample_signed_distance(Vec3f::new(1.3, 0.0, 0.0)) < 0.0);
+        rod.open(0.3);
+        assert!(rod.sample_signed_distance(Vec3f::new(1.3, 0.0, 0.0)) > 0.0);
+        assert!(rod.sample_signed_distance(Vec3f::new(0.0, 0.8, 0.0)).abs() < 0.05);
+
+        // Two spheres with a narrow gap between them
+        let mut pair = rasterize(&Sphere::new(0.6).translated(Vec3f::new(-0.7, 0.0, 0.0)));
+        pair.union_with(&rasterize(&Sphere::new(0.6).translated(Vec3f::new(0.7, 0.0, 0.0))));
+        assert!(pair.sample_signed_distance(Vec3f::zero()) > 0.0);
+        pair.close(0.3);
+        assert!(pair.sample_signed_distance(Vec3f::zero()) < 0.0);
+        assert!(pair.sample_signed_distance(Vec3f::new(-1.3, 0.0, 0.0)).abs() < 0.05);
+    }
+}
@@ -0,0 +1,325 @@
+//! Redistancing of signed distance volumes by fast marching
+use super::*;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Offsets to the six face neighbours, by axis and direction
+const NEIGHBOURS: [Vec3i; 6] = [
+    Vec3i { x: -1, y: 0, z: 0 }, Vec3i { x: 1, y: 0, z: 0 },
+    Vec3i { x: 0, y: -1, z: 0 }, Vec3i { x: 0, y: 1, z: 0 },
+    Vec3i { x: 0, y: 0, z: -1 }, Vec3i { x: 0, y: 0, z: 1 },
+];
+
+/// Tentative distance of a voxel on the marching front, ordered by increasing distance
+struct Trial {
+    distance: f32,
+    coord: Vec3i,
+}
+
+impl PartialEq for Trial {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Trial {}
+
+impl PartialOrd for Trial {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Trial {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed for a min-heap, ties broken by coordinate for determinism
+        other.distance.total_cmp(&self.distance)
+            .then_with(|| (other.coord.z, other.coord.y, other.coord.x).cmp(&(self.coord.z, self.coord.y, self.coord.x)))
+    }
+}
+
+/// Solve the upwind Eikonal equation |grad u| = 1 from the smallest known
+/// neighbour distance along each axis
+fn solve_eikonal(mut axes: [f32; 3], h: f32) -> f32 {
+    axes.sort_by(f32::total_cmp);
+    let mut sum = 0.0;
+    let mut sum_squares = 0.0;
+    let mut solution = f32::INFINITY;
+    for (count, &u) in axes.iter().enumerate() {
+        if u >= solution {
+            break;
+        }
+        sum += u;
+        sum_squares += u * u;
+        // n u^2 - 2 sum u + sum_squares - h^2 = 0
+        let n = (count + 1) as f32;
+        let discriminant = sum * sum - n * (sum_squares - h * h);
+        if discriminant < 0.0 {
+            break;
+        }
+        solution = (sum + discriminant.sqrt()) / n;
+    }
+    solution
+}
+
+/// How the distances of the voxels next to the surface are estimated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Seeding {
+    /// Linear interpolation of the crossings along each axis, which keeps
+    /// the zero crossings in place
+    Crossings,
+    /// Value over the central difference gradient, smoother for fields
+    /// that are resampled afterwards but moving the crossings slightly
+    Gradient,
+}
+
+/// Distance to the surface from the axis crossings around a voxel, if any
+fn crossing_distance<T: SignedDistance>(active: &HashMap<Vec3i, T>, coord: Vec3i, phi: f32, h: f32) -> Option<f32> {
+    let mut inverse_squares = 0.0;
+    let mut on_surface = phi == 0.0;
+    for axis in 0..3 {
+        let nearest = NEIGHBOURS[2 * axis..2 * axis + 2].iter()
+            .filter_map(|&offset| active.get(&(coord + offset)))
+            .map(|n| n.signed_distance())
+            .filter(|&n| (n < 0.0) != (phi < 0.0))
+            .map(|n| h * phi / (phi - n))
+            .fold(f32::INFINITY, f32::min);
+        if nearest.is_finite() {
+            on_surface = true;
+            inverse_squares += 1.0 / (nearest * nearest).max(f32::MIN_POSITIVE);
+        }
+    }
+    if !on_surface {
+        return None;
+    }
+    Some(if phi == 0.0 { 0.0 } else { inverse_squares.sqrt().recip() })
+}
+
+/// Distance to the surface from the local gradient, if the voxel is next to a sign change
+fn gradient_distance<T: SignedDistance>(active: &HashMap<Vec3i, T>, coord: Vec3i, phi: f32, h: f32) -> Option<f32> {
+    let mut on_surface = phi == 0.0;
+    let mut gradient_squared = 0.0;
+    for axis in 0..3 {
+        let [below, above] = [0, 1].map(|side| {
+            active.get(&(coord + NEIGHBOURS[2 * axis + side])).map(|n| n.signed_distance())
+        });
+        on_surface |= [below, above].iter().flatten().any(|&n| (n < 0.0) != (phi < 0.0));
+        let slope = match (below, above) {
+            (Some(below), Some(above)) => (above - below) / (2.0 * h),
+            (Some(below), None) => (phi - below) / h,
+            (None, Some(above)) => (above - phi) / h,
+            (None, None) => 0.0,
+        };
+        gradient_squared += slope * slope;
+    }
+    if !on_surface {
+        return None;
+    }
+    Some(if gradient_squared > 0.0 { phi.abs() / gradient_squared.sqrt() } else { 0.0 })
+}
+
+/// Signed distances propagated from the zero level set of a volume
+pub(super) struct DistanceField<T> {
+    /// Active voxels of the volume before redistancing
+    active: HashMap<Vec3i, T>,
+    /// Signed distance of every voxel reached
+    pub(super) distances: HashMap<Vec3i, f32>,
+    /// Voxel each newly reached voxel was propagated from
+    sources: HashMap<Vec3i, Vec3i>,
+}
+
+impl<T: FromSignedDistance + Clone> DistanceField<T> {
+    /// Value whose attributes a voxel inherits: its own if it was active,
+    /// otherwise those of the active voxel it was propagated from
+    fn template(&self, coord: Vec3i) -> T {
+        let mut coord = coord;
+        loop {
+            if let Some(value) = self.active.get(&coord) {
+                return value.clone();
+            }
+            match self.sources.get(&coord) {
+                Some(&source) => coord = source,
+                None => return T::background(),
+            }
+        }
+    }
+}
+
+impl<T: FromSignedDistance + Clone + 'static> VoxelVolume<T> {
+    /// Rebuild a true signed distance field around the zero level set
+    ///
+    /// Voxels next to a sign change between active voxels get their
+    /// distance to the surface found by linear interpolation along each
+    /// axis, which keeps the zero crossings in place. Distances are then
+    /// propagated outwards on both sides by fast marching until they reach
+    /// `band_width` voxels. Voxels within the band are written with their new
+    /// distance, keeping their other attributes or copying those of the
+    /// voxel they were reached from, and voxels beyond it are removed if
+    /// outside and set inactive at the negated background distance if inside.
+    ///
+    /// Returns the number of active voxels after redistancing.
+    pub fn reinitialize(&mut self, band_width: f32) -> usize {
+        let band = band_width * self.config.leaf_voxel_size;
+        let field = self.march_distances(band, Seeding::Crossings);
+        self.write_distance_band(&field, band);
+        self.active_count()
+    }
+
+    /// Compute signed distances from the zero level set out to `band` in world units
+    ///
+    /// The voxels next to the surface are seeded as chosen by `seeding`.
+    pub(super) fn march_distances(&self, band: f32, seeding: Seeding) -> DistanceField<T> {
+        let h = self.config.leaf_voxel_size;
+        let active: HashMap<Vec3i, T> = self.active_voxels().map(|(c, v)| (c, v.clone())).collect();
+
+        // Distances of the voxels next to a sign change
+        let mut accepted: HashMap<Vec3i, (f32, bool)> = HashMap::new();
+        for (&coord, value) in &active {
+            let phi = value.signed_distance();
+            let distance = match seeding {
+                Seeding::Crossings => crossing_distance(&active, coord, phi, h),
+                Seeding::Gradient => gradient_distance(&active, coord, phi, h),
+            };
+            if let Some(distance) = distance {
+                accepted.insert(coord, (distance.min(h), phi < 0.0));
+            }
+        }
+
+        // March outwards from the surface voxels
+        let mut sources: HashMap<Vec3i, Vec3i> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        let mut frontier: Vec<Vec3i> = accepted.keys().copied().collect();
+        frontier.sort_by_key(|c| (c.z, c.y, c.x));
+        let mut done: HashSet<Vec3i> = HashSet::new();
+        for coord in frontier {
+            heap.push(Trial { distance: accepted[&coord].0, coord });
+        }
+        while let Some(Trial { distance, coord }) = heap.pop() {
+            if !done.insert(coord) || distance >= band {
+                continue;
+            }
+            let inside = accepted[&coord].1;
+            for offset in NEIGHBOURS {
+                let neighbour = coord + offset;
+                if done.contains(&neighbour) {
+                    continue;
+                }
+                // Keep to this side of the surface where the sign is known
+                let neighbour_inside = active.get(&neighbour).map_or(inside, |v| v.signed_distance() < 0.0);
+                if neighbour_inside != inside {
+                    continue;
+                }
+                let axes = [0, 1, 2].map(|axis| {
+                    NEIGHBOURS[2 * axis..2 * axis + 2].iter()
+                        .filter(|&&o| done.contains(&(neighbour + o)))
+                        .filter_map(|&o| accepted.get(&(neighbour + o)))
+                        .filter(|&&(_, side)| side == inside)
+                        .map(|&(d, _)| d)
+                        .fold(f32::INFINITY, f32::min)
+                });
+                let candidate = solve_eikonal(axes, h);
+                let improved = match accepted.get(&neighbour) {
+                    Some(&(current, _)) => candidate < current,
+                    None => true,
+                };
+                if improved {
+                    accepted.insert(neighbour, (candidate, inside));
+                    sources.insert(neighbour, coord);
+                    heap.push(Trial { distance: candidate, coord: neighbour });
+                }
+            }
+        }
+
+        let distances = accepted.into_iter()
+            .map(|(coord, (distance, inside))| (coord, if inside { -distance } else { distance }))
+            .collect();
+        DistanceField { active, distances, sources }
+    }
+
+    /// Write the distances within `band` of the surface, clearing the previous band beyond it
+    pub(super) fn write_distance_band(&mut self, field: &DistanceField<T>, band: f32) {
+        let far = T::background().signed_distance().abs().max(band);
+        let mut coords: Vec<Vec3i> = field.distances.keys().chain(field.active.keys()).copied().collect();
+        coords.sort_by_key(|c| (c.z, c.y, c.x));
+        coords.dedup();
+        for coord in coords {
+            let distance = match field.distances.get(&coord) {
+                Some(&distance) => distance,
+                None => field.active[&coord].signed_distance().signum() * far,
+            };
+            if distance.abs() < band {
+                self.set_voxel(coord, field.template(coord).with_signed_distance(distance));
+            } else if distance < 0.0 {
+                self.set_voxel(coord, field.template(coord).with_signed_distance(-far));
+            } else {
+                self.remove_voxel(coord);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::{SdfVoxel, config};
+    use test_log::test;
+
+    #[test]
+    fn test_solve_eikonal() {
+        assert_eq!(solve_eikonal([0.0, f32::INFINITY, f32::INFINITY], 1.0), 1.0);
+        assert!((solve_eikonal([0.0, 0.0, f32::INFINITY], 1.0) - 0.5f32.sqrt()).abs() < 1e-6);
+        assert!((solve_eikonal([0.0, 0.0, 0.0], 1.0) - (1.0f32 / 3.0).sqrt()).abs() < 1e-6);
+        // A far neighbour does not contribute
+        assert_eq!(solve_eikonal([0.0, 5.0, f32::INFINITY], 1.0), 1.0);
+    }
+
+    #[test]
+    fn test_reinitialize_distorted_sphere() {
+        let mut volume = VoxelVolume::<SdfVoxel>::with_config(config());
+        // Same zero level set as the unit sphere, but far from a distance field
+        volume.fill_bounds(Vec3f::new(-1.5, -1.5, -1.5), Vec3f::new(1.5, 1.5, 1.5), |p| {
+            let phi = (p.length() - 1.0) * (0.3 + p.x * p.x);
+            Some(SdfVoxel(phi)).filter(|v| v.is_active())
+        });
+        let crossings: Vec<(Vec3i, bool)> = volume.active_voxels().map(|(c, v)| (c, v.0 < 0.0)).collect();
+
+        let active = volume.reinitialize(3.0);
+        assert_eq!(active, volume.active_count());
+        let mut worst: f32 = 0.0;
+        for coord in Bounds3i::new(Vec3i::new(-16, -16, -16), Vec3i::new(16, 16, 16)).coords() {
+            let exact = coord.as_vec3f().scale(0.1).length() - 1.0;
+            if exact.abs() < 0.25 {
+                assert!(volume.is_active(coord), "{:?}", coord);
+                worst = worst.max((volume.get_voxel(coord).0 - exact).abs());
+            } else if exact.abs() > 0.35 {
+                assert!(!volume.is_active(coord), "{:?}", coord);
+            }
+        }
+        assert!(worst < 0.04, "worst error {}", worst);
+        for (coord, inside) in crossings {
+            assert_eq!(volume.get_voxel(coord).0 < 0.0, inside);
+        }
+        assert!(volume.get_voxel(Vec3i::new(0, 0, 7)).0 < 0.0);
+    }
+
+    #[test]
+    fn test_reinitialize_keeps_crossings() {
+        let mut volume = VoxelVolume::<SdfVoxel>::with_config(config());
+        // Plane x = 0.043 with a distance scaled nonlinearly along x
+        volume.fill_bounds(Vec3f::new(-1.0, -0.5, -0.5), Vec3f::new(1.0, 0.5, 0.5), |p| {
+            Some(SdfVoxel((p.x - 0.043) * (0.5 + 4.0 * p.x * p.x))).filter(|v| v.is_active())
+        });
+        let crossing = |volume: &VoxelVolume<SdfVoxel>, coord: Vec3i| {
+            let (a, b) = (volume.get_voxel(coord).0, volume.get_voxel(coord + Vec3i::new(1, 0, 0)).0);
+            a / (a - b)
+        };
+        let surface: Vec<(Vec3i, f32)> = Bounds3i::new(Vec3i::new(0, -3, -3), Vec3i::new(1, 3, 3)).coords()
+            .map(|coord| (coord, crossing(&volume, coord)))
+            .collect();
+
+        volume.reinitialize(3.0);
+        for (coord, before) in surface {
+            assert!((crossing(&volume, coord) - before).abs() < 1e-4, "{:?}: {} -> {}", coord, before, crossing(&volume, coord));
+        }
+    }
+}
@@ -164,6 +164,31 @@ impl<T: VoxelData, N: ChildNodeTrait<T>> RootNode<T, N> {
         let child_key = <N as ChildNodeTrait::<T>>::key(coord);
         self.children.get_mut(&child_key)
     }
+
+    /// Replaces the leaf block containing a coordinate with the same block of another root.
+    /// 
+    /// Nodes and tiles are copied as they are stored in `other`, so the block
+    /// is not written voxel by voxel.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `other` - The root node to copy from
+    /// * `coord` - A coordinate within the leaf block to copy
+    pub(crate) fn copy_leaf(&mut self, other: &Self, coord: Vec3i) {
+        match other.find_child(coord) {
+            Some(child) => match self.find_child_mut(coord) {
+                Some(own) => own.copy_leaf(child, coord),
+                None => self.create_child(coord).copy_leaf(child, coord),
+            },
+            None => {
+                if let Some(own) = self.find_child_mut(coord) {
+                    let size = 1 << <N as ChildNodeTrait::<T>>::leaf_log2();
+                    let key = Vec3i::new(coord.x & !(size - 1), coord.y & !(size - 1), coord.z & !(size - 1));
+                    own.fill(Bounds3i::new(key, key + Vec3i::new(size, size, size)), other.background_value.clone());
+                }
+            }
+        }
+    }
 }
 
 impl<T: VoxelData, N: ChildNodeTrait<T>> NodeTrait<T> for RootNode<T, N> {
@@ -190,6 +215,15 @@ impl<T: VoxelData, N: ChildNodeTrait<T>> NodeTrait<T> for RootNode<T, N> {
       <N as ChildNodeTrait::<T>>::log2_cum()
     }
 
+    /// Returns the log2 size of the leaf nodes at the bottom of the hierarchy.
+    /// 
+    /// # Returns
+    /// 
+    /// The log2 size of leaf nodes (e.g., 2 means 2^2 = 4 voxels per side).
+    fn leaf_log2(&self) -> u32 {
+      <N as ChildNodeTrait::<T>>::leaf_log2()
+    }
+
     /// Returns the bounding box of all active voxels in this node and its children.
     /// 
     /// The bounds represent the spatial extent of all voxel data stored in the hierarchy.
@@ -357,6 +391,38 @@ impl<T: VoxelData, N: ChildNodeTrait<T>> NodeTrait<T> for RootNode<T, N> {
         )
     }
 
+    /// Returns an iterator over the active voxels within bounds.
+    /// 
+    /// Children that do not overlap the bounds are skipped without being visited.
+    fn active_voxels_in(&self, bounds: Bounds3i) -> Box<dyn Iterator<Item = (Vec3i, &T)> + '_> {
+        Box::new(
+            self.children.values()
+                .filter(move |child| child.bounds().intersects(bounds))
+                .flat_map(move |child| child.active_voxels_in(bounds))
+        )
+    }
+
+    /// Sets every voxel within bounds to a value.
+    /// 
+    /// Child nodes overlapping the bounds are created where the value differs
+    /// from the background, and each fills its own part of the bounds.
+    fn fill(&mut self, bounds: Bounds3i, value: T) {
+        let step = 1i32 << <N as ChildNodeTrait::<T>>::log2_cum();
+        let first = <N as ChildNodeTrait::<T>>::key(bounds.min);
+        for z in (first.z..bounds.max.z).step_by(step as usize) {
+            for y in (first.y..bounds.max.y).step_by(step as usize) {
+                for x in (first.x..bounds.max.x).step_by(step as usize) {
+                    let key = Vec3i::new(x, y, z);
+                    if let Some(child) = self.children.get_mut(&key) {
+                        child.fill(bounds, value.clone());
+                    } else if self.background_value != value {
+                        self.create_child(key).fill(bounds, value.clone());
+                    }
+                }
+            }
+        }
+    }
+
     /// Returns an iterator over all voxels (active and inactive) in this node and its children.
     /// 
     /// This includes both active voxels (containing data) and inactive voxels
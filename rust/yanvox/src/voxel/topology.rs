@@ -0,0 +1,507 @@
+//! Operations on the set of active voxels, independent of their values
+use super::*;
+use std::collections::HashMap;
+
+/// Neighbourhood of a voxel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Connectivity {
+    /// Voxels sharing a face
+    #[default]
+    Six,
+    /// Voxels sharing a face or an edge
+    Eighteen,
+    /// Voxels sharing a face, an edge or a corner
+    TwentySix,
+}
+
+impl Connectivity {
+    /// Get the offsets to the neighbours of a voxel
+    pub fn offsets(self) -> Vec<Vec3i> {
+        let max_nonzero = match self {
+            Self::Six => 1,
+            Self::Eighteen => 2,
+            Self::TwentySix => 3,
+        };
+        Bounds3i::new(Vec3i::new(-1, -1, -1), Vec3i::new(2, 2, 2)).coords()
+            .filter(|o| {
+                let nonzero = (o.x != 0) as i32 + (o.y != 0) as i32 + (o.z != 0) as i32;
+                nonzero > 0 && nonzero <= max_nonzero
+            })
+            .collect()
+    }
+}
+
+/// Set of voxel coordinates stored as one bit mask per leaf
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveMask {
+    leaf_log2: u32,
+    leaves: HashMap<Vec3i, Vec<u64>>,
+}
+
+impl ActiveMask {
+    /// Create an empty mask with leaves of `2^leaf_log2` voxels per side
+    pub fn new(leaf_log2: u32) -> Self {
+        Self { leaf_log2, leaves: HashMap::new() }
+    }
+
+    /// Create the mask of the active voxels of a volume, with the same leaves
+    pub fn from_volume<T: VoxelData + Clone + 'static>(volume: &VoxelVolume<T>) -> Self {
+        let mut mask = Self::new(volume.leaf_log2());
+        for (coord, _) in volume.active_voxels() {
+            mask.insert(coord);
+        }
+        mask
+    }
+
+    fn leaf_key(&self, coord: Vec3i) -> Vec3i {
+        let size = 1 << self.leaf_log2;
+        Vec3i::new(coord.x & !(size - 1), coord.y & !(size - 1), coord.z & !(size - 1))
+    }
+
+    /// Key of the leaf containing a voxel and the index of its bit
+    fn locate(&self, coord: Vec3i) -> (Vec3i, usize) {
+        let key = self.leaf_key(coord);
+        let local = coord - key;
+        let index = local.x as usize | (local.y as usize) << self.leaf_log2 | (local.z as usize) << (2 * self.leaf_log2);
+        (key, index)
+    }
+
+    fn words_per_leaf(&self) -> usize {
+        (1usize << (3 * self.leaf_log2)).div_ceil(64)
+    }
+
+    /// Check if a voxel is in the mask
+    pub fn contains(&self, coord: Vec3i) -> bool {
+        let (key, index) = self.locate(coord);
+        self.leaves.get(&key).is_some_and(|bits| bits[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    /// Add a voxel, returning whether it was absent
+    pub fn insert(&mut self, coord: Vec3i) -> bool {
+        let (key, index) = self.locate(coord);
+        let words = self.words_per_leaf();
+        let word = &mut self.leaves.entry(key).or_insert_with(|| vec![0; words])[index / 64];
+        let absent = *word & (1 << (index % 64)) == 0;
+        *word |= 1 << (index % 64);
+        absent
+    }
+
+    /// Remove a voxel, returning whether it was present
+    pub fn remove(&mut self, coord: Vec3i) -> bool {
+        let (key, index) = self.locate(coord);
+        let Some(bits) = self.leaves.get_mut(&key) else { return false };
+        let present = bits[index / 64] & (1 << (index % 64)) != 0;
+        bits[index / 64] &= !(1 << (index % 64));
+        if bits.iter().all(|&word| word == 0) {
+            self.leaves.remove(&key);
+        }
+        present
+    }
+
+    /// Get the number of voxels in the mask
+    pub fn len(&self) -> usize {
+        self.leaves.values().flatten().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Check if the mask is empty
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Get the log2 of the leaf size, in voxels per side
+    pub fn leaf_log2(&self) -> u32 {
+        self.leaf_log2
+    }
+
+    /// Get the keys (lower corners) of the leaves holding voxels, in z, y, x order
+    pub fn leaf_keys(&self) -> Vec<Vec3i> {
+        let mut keys: Vec<Vec3i> = self.leaves.keys().copied().collect();
+        keys.sort_by_key(|k| (k.z, k.y, k.x));
+        keys
+    }
+
+    /// Iterate over the voxels of a leaf
+    pub fn leaf_coords(&self, key: Vec3i) -> impl Iterator<Item = Vec3i> + '_ {
+        let log2 = self.leaf_log2;
+        let mask = (1usize << log2) - 1;
+        self.leaves.get(&key).into_iter().flat_map(move |bits| {
+            bits.iter().enumerate().flat_map(move |(w, &word)| {
+                (0..64).filter(move |b| word & (1 << b) != 0).map(move |b| {
+                    let index = w * 64 + b;
+                    key + Vec3i::new((index & mask) as i32, (index >> log2 & mask) as i32, (index >> (2 * log2)) as i32)
+                })
+            })
+        })
+    }
+
+    /// Iterate over the voxels, leaf by leaf in z, y, x order
+    pub fn coords(&self) -> impl Iterator<Item = Vec3i> + '_ {
+        self.leaf_keys().into_iter().flat_map(move |key| self.leaf_coords(key))
+    }
+
+    /// Add the voxels of another mask
+    pub fn union_with(&mut self, other: &Self) {
+        if other.leaf_log2 != self.leaf_log2 {
+            for coord in other.coords() {
+                self.insert(coord);
+            }
+            return;
+        }
+        for (key, bits) in &other.leaves {
+            match self.leaves.get_mut(key) {
+                Some(own) => own.iter_mut().zip(bits).for_each(|(a, b)| *a |= b),
+                None => {
+                    self.leaves.insert(*key, bits.clone());
+                }
+            }
+        }
+    }
+
+    /// Keep only the voxels also in another mask
+    pub fn intersect_with(&mut self, other: &Self) {
+        if other.leaf_log2 != self.leaf_log2 {
+            let outside: Vec<Vec3i> = self.coords().filter(|&c| !other.contains(c)).collect();
+            for coord in outside {
+                self.remove(coord);
+            }
+            return;
+        }
+        self.leaves.retain(|key, own| match other.leaves.get(key) {
+            Some(bits) => {
+                own.iter_mut().zip(bits).for_each(|(a, b)| *a &= b);
+                own.iter().any(|&word| word != 0)
+            }
+            None => false,
+        });
+    }
+
+    /// Remove the voxels in another mask
+    pub fn subtract_with(&mut self, other: &Self) {
+        if other.leaf_log2 != self.leaf_log2 {
+            for coord in other.coords() {
+                self.remove(coord);
+            }
+            return;
+        }
+        self.leaves.retain(|key, own| match other.leaves.get(key) {
+            Some(bits) => {
+                own.iter_mut().zip(bits).for_each(|(a, b)| *a &= !b);
+                own.iter().any(|&word| word != 0)
+            }
+            None => true,
+        });
+    }
+
+    /// Get the mask grown by one voxel in every direction of a neighbourhood
+    pub fn dilated(&self, connectivity: Connectivity) -> Self {
+        let mut result = self.clone();
+        for offset in connectivity.offsets() {
+            result.union_with(&self.shifted(offset));
+        }
+        result
+    }
+
+    /// Get the mask without the voxels that have a neighbour outside it
+    pub fn eroded(&self, connectivity: Connectivity) -> Self {
+        let mut result = self.clone();
+        for offset in connectivity.offsets() {
+            result.intersect_with(&self.shifted(offset));
+        }
+        result
+    }
+
+    /// Get the mask moved by an offset of at most one voxel along each axis
+    fn shifted(&self, offset: Vec3i) -> Self {
+        let mut result: Option<Self> = None;
+        for (axis, step) in [offset.x, offset.y, offset.z].into_iter().enumerate() {
+            if step != 0 {
+                result = Some(result.as_ref().unwrap_or(self).shifted_along(axis, step));
+            }
+        }
+        result.unwrap_or_else(|| self.clone())
+    }
+
+    /// Get the mask moved by one voxel along an axis, `step` being 1 or -1
+    ///
+    /// The bit words of each leaf are shifted as a whole; the voxels on the
+    /// face the shift leaves through go to the opposite face of the
+    /// neighbouring leaf.
+    fn shifted_along(&self, axis: usize, step: i32) -> Self {
+        let size = 1usize << self.leaf_log2;
+        let stride = 1usize << (axis as u32 * self.leaf_log2);
+        let face = self.face_bits(axis, if step > 0 { size - 1 } else { 0 });
+        let across = (size - 1) * stride;
+        let neighbour = match axis {
+            0 => Vec3i::new(step * size as i32, 0, 0),
+            1 => Vec3i::new(0, step * size as i32, 0),
+            _ => Vec3i::new(0, 0, step * size as i32),
+        };
+        let mut result = Self::new(self.leaf_log2);
+        for (key, bits) in &self.leaves {
+            let inner: Vec<u64> = bits.iter().zip(&face).map(|(b, f)| b & !f).collect();
+            let border: Vec<u64> = bits.iter().zip(&face).map(|(b, f)| b & f).collect();
+            let (inner, border) = if step > 0 {
+                (shift_up(&inner, stride), shift_down(&border, across))
+            } else {
+                (shift_down(&inner, stride), shift_up(&border, across))
+            };
+            result.add_words(*key, &inner);
+            result.add_words(*key + neighbour, &border);
+        }
+        result
+    }
+
+    /// Bit words of a leaf with the voxels at `plane` along an axis set
+    fn face_bits(&self, axis: usize, plane: usize) -> Vec<u64> {
+        let mut words = vec![0; self.words_per_leaf()];
+        let mask = (1usize << self.leaf_log2) - 1;
+        for index in 0..1usize << (3 * self.leaf_log2) {
+            if (index >> (axis as u32 * self.leaf_log2)) & mask == plane {
+                words[index / 64] |= 1 << (index % 64);
+            }
+        }
+        words
+    }
+
+    /// Add the voxels of a leaf given as bit words
+    fn add_words(&mut self, key: Vec3i, words: &[u64]) {
+        if words.iter().all(|&word| word == 0) {
+            return;
+        }
+        match self.leaves.get_mut(&key) {
+            Some(own) => own.iter_mut().zip(words).for_each(|(a, b)| *a |= b),
+            None => {
+                self.leaves.insert(key, words.to_vec());
+            }
+        }
+    }
+}
+
+/// Move the bits of a bit set towards higher indices, dropping those that overflow
+fn shift_up(words: &[u64], amount: usize) -> Vec<u64> {
+    let (skip, bit) = (amount / 64, amount % 64);
+    (0..words.len())
+        .map(|i| {
+            if i < skip {
+                return 0;
+            }
+            let carry = if bit > 0 && i > skip { words[i - skip - 1] >> (64 - bit) } else { 0 };
+            words[i - skip] << bit | carry
+        })
+        .collect()
+}
+
+/// Move the bits of a bit set towards lower indices, dropping those that underflow
+fn shift_down(words: &[u64], amount: usize) -> Vec<u64> {
+    let (skip, bit) = (amount / 64, amount % 64);
+    (0..words.len())
+        .map(|i| {
+            let Some(&word) = words.get(i + skip) else { return 0 };
+            let carry = match words.get(i + skip + 1) {
+                Some(&next) if bit > 0 => next << (64 - bit),
+                _ => 0,
+            };
+            word >> bit | carry
+        })
+        .collect()
+}
+
+impl<T: VoxelData + Clone + 'static> VoxelVolume<T> {
+    /// Get the mask of the active voxels
+    pub fn active_mask(&self) -> ActiveMask {
+        ActiveMask::from_volume(self)
+    }
+
+    /// Activate the inactive neighbours of active voxels, `iterations` times
+    ///
+    /// Values are needed to make voxels active, so each activated voxel
+    /// copies the value of its first active neighbour in the order of
+    /// [`Connectivity::offsets`].
+    ///
+    /// Returns the number of voxels activated.
+    pub fn dilate_active(&mut self, iterations: usize, connectivity: Connectivity) -> usize {
+        let offsets = connectivity.offsets();
+        let mut mask = self.active_mask();
+        let mut activated = 0;
+        for _ in 0..iterations {
+            let mut grown = mask.dilated(connectivity);
+            grown.subtract_with(&mask);
+            if grown.is_empty() {
+                break;
+            }
+            for coord in grown.coords() {
+                let source = offsets.iter().map(|&o| coord + o).find(|&n| mask.contains(n));
+                if let Some(source) = source {
+                    let value = self.get_voxel(source).clone();
+                    self.set_voxel(coord, value);
+                    activated += 1;
+                }
+            }
+            mask.union_with(&grown);
+        }
+        activated
+    }
+
+    /// Deactivate active voxels with an inactive neighbour, `iterations` times
+    ///
+    /// Returns the number of voxels deactivated.
+    pub fn erode_active(&mut self, iterations: usize, connectivity: Connectivity) -> usize {
+        let mut mask = self.active_mask();
+        let mut eroded = mask.clone();
+        for _ in 0..iterations {
+            eroded = eroded.eroded(connectivity);
+        }
+        mask.subtract_with(&eroded);
+        self.deactivate(&mask)
+    }
+
+    /// Activate the voxels active in another volume, with a value
+    ///
+    /// `value` should be active. Voxels already active keep their value.
+    /// Returns the number of voxels activated.
+    pub fn topology_union<U: VoxelData + Clone + 'static>(&mut self, other: &VoxelVolume<U>, value: T) -> usize {
+        let mut added = other.active_mask();
+        added.subtract_with(&self.active_mask());
+        for coord in added.coords() {
+            self.set_voxel(coord, value.clone());
+        }
+        added.len()
+    }
+
+    /// Deactivate the voxels inactive in another volume
+    ///
+    /// Returns the number of voxels deactivated.
+    pub fn topology_intersection<U: VoxelData + Clone + 'static>(&mut self, other: &VoxelVolume<U>) -> usize {
+        let mut removed = self.active_mask();
+        removed.subtract_with(&other.active_mask());
+        self.deactivate(&removed)
+    }
+
+    /// Deactivate the voxels active in another volume
+    ///
+    /// Returns the number of voxels deactivated.
+    pub fn topology_difference<U: VoxelData + Clone + 'static>(&mut self, other: &VoxelVolume<U>) -> usize {
+        let mut removed = self.active_mask();
+        removed.intersect_with(&other.active_mask());
+        self.deactivate(&removed)
+    }
+
+    /// Remove the voxels of a mask
+    fn deactivate(&mut self, mask: &ActiveMask) -> usize {
+        for coord in mask.coords() {
+            self.remove_voxel(coord);
+        }
+        mask.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_data::tests::config_with_size;
+    use test_log::test;
+
+    fn volume(volume_config_type: VolumeConfigType) -> VoxelVolume<u32> {
+        VoxelVolume::with_config(VolumeConfig { volume_config_type, ..config_with_size(1.0) })
+    }
+
+    #[test]
+    fn test_connectivity() {
+        assert_eq!(Connectivity::Six.offsets().len(), 6);
+        assert_eq!(Connectivity::Eighteen.offsets().len(), 18);
+        assert_eq!(Connectivity::TwentySix.offsets().len(), 26);
+    }
+
+    #[test]
+    fn test_mask_operations() {
+        let mut mask = ActiveMask::new(2);
+        assert!(mask.insert(Vec3i::new(3, 3, 3)));
+        assert!(!mask.insert(Vec3i::new(3, 3, 3)));
+        assert!(mask.insert(Vec3i::new(-1, 0, 0)));
+        assert_eq!(mask.leaf_keys(), vec![Vec3i::new(-4, 0, 0), Vec3i::zero()]);
+        assert_eq!(mask.coords().collect::<Vec<_>>(), vec![Vec3i::new(-1, 0, 0), Vec3i::new(3, 3, 3)]);
+
+        // Dilation crosses leaf boundaries
+        let dilated = mask.dilated(Connectivity::TwentySix);
+        assert_eq!(dilated.len(), 2 * 27);
+        assert!(dilated.contains(Vec3i::new(4, 4, 4)));
+        assert_eq!(dilated.eroded(Connectivity::TwentySix), mask);
+
+        let mut other = ActiveMask::new(1);
+        other.insert(Vec3i::new(3, 3, 3));
+        other.insert(Vec3i::new(5, 5, 5));
+        let mut union = mask.clone();
+        union.union_with(&other);
+        assert_eq!(union.len(), 3);
+        let mut intersection = mask.clone();
+        intersection.intersect_with(&other);
+        assert_eq!(intersection.coords().collect::<Vec<_>>(), vec![Vec3i::new(3, 3, 3)]);
+        let mut difference = mask.clone();
+        difference.subtract_with(&union);
+        assert!(difference.is_empty());
+    }
+
+    #[test]
+    fn test_morphology_matches_neighbour_lookups() {
+        for leaf_log2 in [1, 2, 3] {
+            let mut mask = ActiveMask::new(leaf_log2);
+            for coord in Bounds3i::new(Vec3i::new(-9, -9, -9), Vec3i::new(10, 10, 10)).coords() {
+                // A solid block with a scattered shell, across leaves of every size
+                let solid = coord.x.abs().max(coord.y.abs()).max(coord.z.abs()) < 5;
+                if solid || (coord.x * 7 + coord.y * 13 + coord.z * 5).rem_euclid(11) < 6 {
+                    mask.insert(coord);
+                }
+            }
+            for connectivity in [Connectivity::Six, Connectivity::Eighteen, Connectivity::TwentySix] {
+                let offsets = connectivity.offsets();
+                let mut dilated = mask.clone();
+                let mut eroded = mask.clone();
+                for coord in mask.coords() {
+                    for &offset in &offsets {
+                        dilated.insert(coord + offset);
+                    }
+                    if offsets.iter().any(|&offset| !mask.contains(coord + offset)) {
+                        eroded.remove(coord);
+                    }
+                }
+                assert_eq!(mask.dilated(connectivity), dilated, "{} {:?}", leaf_log2, connectivity);
+                assert!(!eroded.is_empty());
+                assert_eq!(mask.eroded(connectivity), eroded, "{} {:?}", leaf_log2, connectivity);
+            }
+        }
+    }
+
+    #[test]
+    fn test_volume_topology() {
+        let mut a = volume(VolumeConfigType::Hashx2x1);
+        for coord in Bounds3i::new(Vec3i::zero(), Vec3i::new(4, 4, 4)).coords() {
+            a.set_voxel(coord, 7);
+        }
+        a.set_voxel(Vec3i::new(0, 1, 1), 9);
+
+        let mut dilated = a.clone();
+        assert_eq!(dilated.dilate_active(1, Connectivity::Six), 6 * 16);
+        assert_eq!(*dilated.get_voxel(Vec3i::new(-1, 1, 1)), 9);
+        // Only voxels of the cube with at most one coordinate on its boundary keep all 26 neighbours
+        assert_eq!(dilated.erode_active(1, Connectivity::TwentySix), 6 * 16 + 64 - 32);
+        assert_eq!(dilated.active_count(), 32);
+        let mut cube = a.clone();
+        assert_eq!(cube.erode_active(2, Connectivity::Six), 64);
+
+        let mut b = volume(VolumeConfigType::Default);
+        for coord in Bounds3i::new(Vec3i::new(2, 0, 0), Vec3i::new(6, 4, 4)).coords() {
+            b.set_voxel(coord, 1);
+        }
+        let mut union = a.clone();
+        assert_eq!(union.topology_union(&b, 3), 32);
+        assert_eq!(union.active_count(), 96);
+        assert_eq!(*union.get_voxel(Vec3i::new(2, 0, 0)), 7);
+        assert_eq!(*union.get_voxel(Vec3i::new(5, 0, 0)), 3);
+        let mut intersection = a.clone();
+        assert_eq!(intersection.topology_intersection(&b), 32);
+        assert_eq!(intersection.active_count(), 32);
+        let mut difference = a.clone();
+        assert_eq!(difference.topology_difference(&b), 32);
+        assert!(!difference.is_active(Vec3i::new(2, 0, 0)) && difference.is_active(Vec3i::new(1, 0, 0)));
+    }
+}
@@ -1,8 +1,9 @@
 use crate::voxel::VoxelData;
+use crate::math::Vec3f;
 
-mod bool_voxel;
-mod float_voxel;
-mod int_voxel;
+mod bool_voxel; pub use bool_voxel::BoolVoxel;
+mod float_voxel; pub use float_voxel::FloatVoxel;
+mod int_voxel; pub use int_voxel::IntVoxel;
 
 // Implement VoxelData for common primitive types
 impl VoxelData for f32 {
@@ -40,3 +41,72 @@ impl VoxelData for bool {
     }
     fn background() -> Self { false }
 }
+
+impl VoxelData for Vec3f {
+    fn is_active(&self) -> bool {
+        *self != Vec3f::zero()
+    }
+    fn background() -> Self { Vec3f::zero() }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::voxel::{VoxelVolume, VolumeConfig, CompressionType, VolumeConfigType, SignedDistance, FromSignedDistance};
+
+    /// Signed distance voxel for narrow-band test volumes
+    ///
+    /// Voxels closer to the surface than `SdfVoxel::BAND` world units are
+    /// active. The background sits on the outer edge of the band, so algorithms
+    /// reading an inactive voxel see it as far outside.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) struct SdfVoxel(pub f32);
+
+    impl SdfVoxel {
+        /// Half width of the active band, three voxels of the default test config
+        pub(crate) const BAND: f32 = 0.3;
+    }
+
+    impl VoxelData for SdfVoxel {
+        fn is_active(&self) -> bool {
+            self.0.abs() < Self::BAND
+        }
+        fn background() -> Self { Self(Self::BAND) }
+    }
+
+    impl SignedDistance for SdfVoxel {
+        fn signed_distance(&self) -> f32 {
+            self.0
+        }
+    }
+
+    impl FromSignedDistance for SdfVoxel {
+        fn from_signed_distance(distance: f32) -> Self {
+            Self(distance)
+        }
+    }
+
+    /// Uncompressed hash configuration with voxels of `leaf_voxel_size` units
+    pub(crate) fn config_with_size(leaf_voxel_size: f32) -> VolumeConfig {
+        VolumeConfig {
+            compression: CompressionType::None,
+            leaf_voxel_size,
+            volume_config_type: VolumeConfigType::Hashx2x1,
+        }
+    }
+
+    /// Uncompressed hash configuration with voxels of 0.1 units
+    pub(crate) fn config() -> VolumeConfig {
+        config_with_size(0.1)
+    }
+
+    /// Signed distance field of a sphere, written over a box half a unit larger
+    ///
+    /// Voxels outside the band are stored inactive, so the inside keeps its sign.
+    pub(crate) fn sphere_volume(center: Vec3f, radius: f32) -> VoxelVolume<SdfVoxel> {
+        let mut volume = VoxelVolume::with_config(config());
+        let extent = Vec3f::new(1.0, 1.0, 1.0).scale(radius + 0.5);
+        volume.fill_bounds(center - extent, center + extent, |p| Some(SdfVoxel((p - center).length() - radius)));
+        volume
+    }
+}